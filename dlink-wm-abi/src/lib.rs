@@ -0,0 +1,187 @@
+//! # DlinkWM ABI
+//!
+//! The wire format of the `dlinkwm_host` imports, shared by the host (`dlink-wm`)
+//! and guests (`dlink-wm-guest`) so that the two cannot drift apart.
+//!
+//! ## universal_invoke
+//!
+//! ```text
+//! universal_invoke(method_name_ptr, method_name_len, format_type, params_ptr, params_len, ret_ptr) -> status
+//! ```
+//!
+//! The guest passes the UTF-8 method name, a `format_type` (see `format`) and the
+//! serialized parameters. The host writes a response to `ret_ptr`: a
+//! `ResponseHeader` of `RESPONSE_HEADER_SIZE` bytes followed by the response data.
+//! The returned status is one of the codes in `status`. When the host itself fails
+//! (status other than `STATUS_OK`), the data is the error message, cut to
+//! `LEGACY_ERROR_MESSAGE_LIMIT` bytes.
+//!
+//! The buffer at `ret_ptr` is usually allocated with `host_malloc` and released with
+//! `host_free`. The host does not know its size, so `universal_invoke` is kept for
+//! existing guests only; new guests call `universal_invoke_v2`.
+//!
+//! ## universal_invoke_v2
+//!
+//! ```text
+//! universal_invoke_v2(method_name_ptr, method_name_len, format_type, params_ptr, params_len, ret_ptr, ret_capacity) -> status
+//! take_response(ret_ptr, ret_capacity) -> status
+//! ```
+//!
+//! The guest also passes the capacity of the buffer at `ret_ptr`, and the host never
+//! writes past it. If the response does not fit, the host returns
+//! `STATUS_BUFFER_TOO_SMALL`, writes only the `ResponseHeader` (whose `len` is the
+//! size of the response data, so the buffer needs `RESPONSE_HEADER_SIZE + len`
+//! bytes) and keeps the response. The guest then fetches it with `take_response`
+//! into a buffer large enough, without running the host method again. A kept
+//! response is replaced by the next `universal_invoke_v2` call and does not outlive
+//! the entry call of the host.
+//!
+//! If the host itself fails (e.g. an unknown method), its error message is cut to
+//! the capacity instead. Nothing is written to a buffer smaller than the header.
+
+#![no_std]
+
+/// Import module of the host functions.
+pub const IMPORT_MODULE: &str = "dlinkwm_host";
+
+/// Name of the host method call import.
+pub const UNIVERSAL_INVOKE: &str = "universal_invoke";
+
+/// Name of the host method call import taking the capacity of the return buffer.
+pub const UNIVERSAL_INVOKE_V2: &str = "universal_invoke_v2";
+
+/// Name of the import fetching a response that did not fit the return buffer.
+pub const TAKE_RESPONSE: &str = "take_response";
+
+/// Name of the host allocation import.
+pub const HOST_MALLOC: &str = "host_malloc";
+
+/// Name of the host deallocation import.
+pub const HOST_FREE: &str = "host_free";
+
+/// Size of the response header written before the response data.
+pub const RESPONSE_HEADER_SIZE: usize = 8;
+
+/// Maximum length of the error message `universal_invoke` writes after the header
+/// when the host fails, so a failed call writes at most 264 bytes.
+pub const LEGACY_ERROR_MESSAGE_LIMIT: usize = 256;
+
+/// Alignment of every block returned by `host_malloc`.
+pub const HOST_MALLOC_ALIGN: usize = 8;
+
+/// # Formats
+///
+/// `format_type` identifiers of the serialization formats.
+pub mod format {
+    /// JSON
+    pub const JSON: i32 = 0;
+    /// Bincode
+    pub const BINCODE: i32 = 1;
+    /// Protocol Buffers
+    pub const PROTOBUF: i32 = 2;
+    /// FlatBuffers (FlexBuffers encoding)
+    pub const FLATBUFFERS: i32 = 3;
+}
+
+/// # Status Codes
+///
+/// Status codes of the host/guest ABI. Codes `1`-`99` can be returned to guests by
+/// the `dlinkwm_host` imports; codes `100` and above are only reported on the host side.
+pub mod status {
+    /// Success
+    pub const STATUS_OK: i32 = 0;
+    /// No host method is registered under the requested name
+    pub const STATUS_METHOD_NOT_FOUND: i32 = 1;
+    /// Unknown serialization format or data that cannot be (de)serialized
+    pub const STATUS_FORMAT_ERROR: i32 = 2;
+    /// The host method handler returned an error
+    pub const STATUS_EXECUTION_ERROR: i32 = 3;
+    /// The module does not export a `memory`
+    pub const STATUS_MEMORY_NOT_FOUND: i32 = 4;
+    /// A string read from guest memory (e.g. the method name) is not valid UTF-8
+    pub const STATUS_INVALID_UTF8: i32 = 5;
+    /// A pointer/length pair lies outside the guest memory
+    pub const STATUS_MEMORY_ACCESS: i32 = 6;
+    /// `host_permissions` does not allow the module to call the method
+    pub const STATUS_PERMISSION_DENIED: i32 = 7;
+    /// The response does not fit the return buffer; the header holds its size
+    pub const STATUS_BUFFER_TOO_SMALL: i32 = 8;
+    /// `take_response` was called without a response kept by the host
+    pub const STATUS_NO_PENDING_RESPONSE: i32 = 9;
+    /// File system error on the host
+    pub const STATUS_IO_ERROR: i32 = 100;
+    /// Invalid or unreadable configuration
+    pub const STATUS_CONFIG_ERROR: i32 = 101;
+    /// Function is not a configured entry function
+    pub const STATUS_ENTRY_NOT_ALLOWED: i32 = 102;
+    /// Export is missing from the module
+    pub const STATUS_EXPORT_NOT_FOUND: i32 = 103;
+    /// Export exists but is not a function
+    pub const STATUS_NOT_A_FUNCTION: i32 = 104;
+    /// Function signature does not match the call
+    pub const STATUS_SIGNATURE_MISMATCH: i32 = 105;
+    /// Compilation, linking or instantiation failed
+    pub const STATUS_WASM_ERROR: i32 = 106;
+    /// The guest trapped during a call
+    pub const STATUS_TRAP: i32 = 107;
+    /// `host_free` on a pointer not returned by `host_malloc`
+    pub const STATUS_INVALID_FREE: i32 = 108;
+    /// `host_free` on an already freed pointer
+    pub const STATUS_DOUBLE_FREE: i32 = 109;
+    /// File watcher could not be created or started
+    pub const STATUS_WATCHER_ERROR: i32 = 110;
+    /// The guest used up the fuel budget of the call
+    pub const STATUS_OUT_OF_FUEL: i32 = 111;
+    /// The guest exceeded the wall-clock budget of the call
+    pub const STATUS_TIMEOUT: i32 = 112;
+    /// The guest exceeded a memory, table, instance or memory count limit
+    pub const STATUS_RESOURCE_LIMIT: i32 = 113;
+    /// The guest's `dlinkwm_init` export returned a failure status
+    pub const STATUS_INIT_FAILED: i32 = 114;
+    /// The operation does not support the module kind (core module or component)
+    pub const STATUS_UNSUPPORTED_MODULE_KIND: i32 = 115;
+    /// The file changed since it was loaded and must be hot reloaded first
+    pub const STATUS_SOURCE_CHANGED: i32 = 116;
+}
+
+/// # Response Header
+///
+/// The header of a `universal_invoke` response: whether the host method succeeded
+/// (4 bytes, little-endian `1` or `0`) and the length of the data that follows (4
+/// bytes, little-endian).
+///
+/// # Example
+///
+/// ```rust
+/// use dlink_wm_abi::ResponseHeader;
+///
+/// let header = ResponseHeader { success: true, len: 5 };
+/// let bytes = header.encode();
+/// assert_eq!(bytes, [1, 0, 0, 0, 5, 0, 0, 0]);
+/// assert_eq!(ResponseHeader::decode(bytes), header);
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ResponseHeader {
+    /// Whether the host method succeeded; on failure the data is an error message
+    pub success: bool,
+    /// Length of the response data in bytes
+    pub len: u32,
+}
+
+impl ResponseHeader {
+    /// Encodes the header as written to guest memory.
+    pub fn encode(self) -> [u8; RESPONSE_HEADER_SIZE] {
+        let status: u32 = if self.success { 1 } else { 0 };
+        let mut bytes = [0u8; RESPONSE_HEADER_SIZE];
+        bytes[..4].copy_from_slice(&status.to_le_bytes());
+        bytes[4..].copy_from_slice(&self.len.to_le_bytes());
+        bytes
+    }
+
+    /// Decodes a header read from guest memory.
+    pub fn decode(bytes: [u8; RESPONSE_HEADER_SIZE]) -> Self {
+        let status = u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]);
+        let len = u32::from_le_bytes([bytes[4], bytes[5], bytes[6], bytes[7]]);
+        ResponseHeader { success: status == 1, len }
+    }
+}
@@ -0,0 +1,57 @@
+//! The `#[entry]` attribute of guests.
+
+use proc_macro2::TokenStream;
+use quote::{format_ident, quote};
+use syn::{FnArg, ItemFn, Pat, ReturnType};
+use crate::NameArgs;
+
+/// Keeps the function and adds an exported wrapper converting its return value.
+pub(crate) fn generate(args: NameArgs, func: ItemFn) -> syn::Result<TokenStream> {
+    let sig = &func.sig;
+    if let Some(asyncness) = &sig.asyncness {
+        return Err(syn::Error::new_spanned(asyncness, "entry functions cannot be async"));
+    }
+    if !sig.generics.params.is_empty() {
+        return Err(syn::Error::new_spanned(&sig.generics, "entry functions cannot be generic"));
+    }
+
+    let mut params = Vec::new();
+    let mut param_types = Vec::new();
+    for input in sig.inputs.iter() {
+        match input {
+            FnArg::Typed(arg) => match &*arg.pat {
+                Pat::Ident(pat) => {
+                    params.push(pat.ident.clone());
+                    param_types.push(arg.ty.clone());
+                }
+                other => return Err(syn::Error::new_spanned(other, "expected a parameter name")),
+            },
+            FnArg::Receiver(receiver) => {
+                return Err(syn::Error::new_spanned(receiver, "entry functions cannot take `self`"))
+            }
+        }
+    }
+
+    let name = &sig.ident;
+    let export_name = args.name.map_or_else(|| name.to_string(), |name| name.value());
+    let wrapper = format_ident!("__dlinkwm_entry_{}", name);
+    let ret = match &sig.output {
+        ReturnType::Default => quote!(()),
+        ReturnType::Type(_, ty) => quote!(#ty),
+    };
+    let call = if sig.unsafety.is_some() {
+        quote!(unsafe { #name(#(#params),*) })
+    } else {
+        quote!(#name(#(#params),*))
+    };
+
+    Ok(quote! {
+        #func
+
+        #[doc(hidden)]
+        #[unsafe(export_name = #export_name)]
+        pub extern "C" fn #wrapper(#(#params: #param_types),*) -> <#ret as ::dlink_wm_guest::EntryReturn>::Abi {
+            ::dlink_wm_guest::EntryReturn::into_abi(#call)
+        }
+    })
+}
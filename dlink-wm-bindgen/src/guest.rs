@@ -0,0 +1,60 @@
+//! Guest side bindings: a function per host method, calling `dlink_wm_guest::invoke`.
+
+use proc_macro2::TokenStream;
+use quote::quote;
+use crate::types::{self, Flavor};
+use crate::Wit;
+
+/// Generates the guest modules of the world's interfaces.
+pub(crate) fn generate(wit: &Wit) -> Result<TokenStream, String> {
+    let flavor = Flavor {
+        string: quote!(::dlink_wm_guest::__private::String),
+        vec: quote!(::dlink_wm_guest::__private::Vec),
+        serde: quote!(::dlink_wm_guest::__private::serde),
+        serde_attrs: quote!(#[serde(crate = "::dlink_wm_guest::__private::serde")]),
+    };
+
+    let mut modules = TokenStream::new();
+    for id in types::imported_interfaces(wit)? {
+        let interface = &wit.resolve.interfaces[id];
+        let module = types::interface_module(wit, id)?;
+        let docs = types::docs(&interface.docs);
+        let items = types::render_interface_types(wit, &flavor, id)?;
+
+        let mut functions = Vec::new();
+        for func in interface.functions.values() {
+            let signature = types::signature(wit, &flavor, func)?;
+            let method_name = types::method_name(wit, id, func)?;
+            let func_docs = types::docs(&func.docs);
+            let name = &signature.name;
+            let params = &signature.params;
+            let param_types = &signature.param_types;
+            let result = &signature.result;
+            let params_struct = types::params_struct(&flavor, &signature, quote!(::dlink_wm_guest::__private::serde::Serialize));
+            let method_doc = format!(" Calls the host method `{}`.", method_name);
+            let separator = func.docs.contents.as_ref().map(|_| quote!(#[doc = ""]));
+
+            functions.push(quote! {
+                #func_docs
+                #separator
+                #[doc = #method_doc]
+                pub fn #name(#(#params: #param_types),*) -> ::dlink_wm_guest::Result<#result> {
+                    #params_struct
+                    ::dlink_wm_guest::invoke(#method_name, &Params { #(#params),* })
+                }
+            });
+        }
+
+        modules.extend(quote! {
+            #docs
+            #[allow(dead_code, clippy::all)]
+            pub mod #module {
+                #items
+
+                #(#functions)*
+            }
+        });
+    }
+
+    Ok(modules)
+}
@@ -0,0 +1,84 @@
+//! Host side bindings: a `Host` trait and `register` per interface.
+
+use proc_macro2::TokenStream;
+use quote::quote;
+use crate::types::{self, Flavor};
+use crate::Wit;
+
+/// Generates the host modules of the world's interfaces.
+pub(crate) fn generate(wit: &Wit) -> Result<TokenStream, String> {
+    let flavor = Flavor {
+        string: quote!(::std::string::String),
+        vec: quote!(::std::vec::Vec),
+        serde: quote!(::dlink_wm::__private::serde),
+        serde_attrs: quote!(#[serde(crate = "::dlink_wm::__private::serde")]),
+    };
+
+    let mut modules = TokenStream::new();
+    for id in types::imported_interfaces(wit)? {
+        let interface = &wit.resolve.interfaces[id];
+        let module = types::interface_module(wit, id)?;
+        let docs = types::docs(&interface.docs);
+        let items = types::render_interface_types(wit, &flavor, id)?;
+
+        let mut methods = Vec::new();
+        let mut method_names = Vec::new();
+        let mut registrations = Vec::new();
+        for func in interface.functions.values() {
+            let signature = types::signature(wit, &flavor, func)?;
+            let method_name = types::method_name(wit, id, func)?;
+            let func_docs = types::docs(&func.docs);
+            let name = &signature.name;
+            let params = &signature.params;
+            let param_types = &signature.param_types;
+            let result = &signature.result;
+            let params_struct = types::params_struct(&flavor, &signature, quote!(::dlink_wm::__private::serde::Deserialize));
+
+            methods.push(quote! {
+                #func_docs
+                fn #name(&self, #(#params: #param_types),*) -> ::dlink_wm::__private::anyhow::Result<#result>;
+            });
+            registrations.push(quote! {
+                {
+                    #params_struct
+                    let host = ::std::sync::Arc::clone(&host);
+                    registered &= registry.register_typed(#method_name, move |params: Params| {
+                        host.#name(#(params.#params),*)
+                    });
+                }
+            });
+            method_names.push(method_name);
+        }
+
+        let interface_name = types::interface_name(wit, id)?;
+        let trait_doc = format!(" Host methods of the `{}` interface.", interface_name);
+        modules.extend(quote! {
+            #docs
+            #[allow(dead_code, clippy::all)]
+            pub mod #module {
+                #items
+
+                #[doc = #trait_doc]
+                pub trait Host: Send + Sync + 'static {
+                    #(#methods)*
+                }
+
+                /// Host method names of the interface, in declaration order.
+                pub const METHODS: &[&str] = &[#(#method_names),*];
+
+                /// Registers the methods of `host` under the names in `METHODS`.
+                ///
+                /// Returns `false` if one of them replaced a method already in `registry`.
+                pub fn register<T: Host>(
+                    registry: &::dlink_wm::registry::HostMethodRegistry,
+                    host: ::std::sync::Arc<T>,
+                ) -> bool {
+                    let mut registered = true;
+                    #(#registrations)*
+                    registered
+                }
+            }
+        });
+    }
+    Ok(modules)
+}
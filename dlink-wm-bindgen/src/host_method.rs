@@ -0,0 +1,167 @@
+//! The `#[dlink_host_method]` attribute of the host.
+
+use proc_macro2::TokenStream;
+use quote::{quote, ToTokens};
+use syn::{Expr, FnArg, GenericArgument, ItemFn, Lit, Meta, Pat, PathArguments, ReturnType, Type};
+use crate::NameArgs;
+
+/// Keeps the function and adds a struct implementing `HostMethodDef` for it.
+pub(crate) fn generate(args: NameArgs, func: ItemFn) -> syn::Result<TokenStream> {
+    let sig = &func.sig;
+    if let Some(asyncness) = &sig.asyncness {
+        return Err(syn::Error::new_spanned(asyncness, "async host methods are registered with `register_typed_async`"));
+    }
+    if let Some(unsafety) = &sig.unsafety {
+        return Err(syn::Error::new_spanned(unsafety, "host methods cannot be unsafe"));
+    }
+    if !sig.generics.params.is_empty() {
+        return Err(syn::Error::new_spanned(&sig.generics, "host methods cannot be generic"));
+    }
+
+    // Split the parameters into the payload and the call context
+    let mut args_in_call = Vec::new();
+    let mut params = Vec::new();
+    let mut param_types = Vec::new();
+    for input in sig.inputs.iter() {
+        let arg = match input {
+            FnArg::Typed(arg) => arg,
+            FnArg::Receiver(receiver) => {
+                return Err(syn::Error::new_spanned(receiver, "host methods cannot take `self`"))
+            }
+        };
+        if is_host_context(&arg.ty) {
+            args_in_call.push(quote!(__ctx));
+            continue;
+        }
+        let ident = match &*arg.pat {
+            Pat::Ident(pat) => pat.ident.clone(),
+            other => return Err(syn::Error::new_spanned(other, "expected a parameter name")),
+        };
+        args_in_call.push(quote!(#ident));
+        params.push(ident);
+        param_types.push((*arg.ty).clone());
+    }
+
+    let decode = match params.len() {
+        0 => quote!(),
+        1 => {
+            let param = &params[0];
+            let ty = &param_types[0];
+            quote! {
+                let #param: #ty = ::dlink_wm::codec::Decode::decode(&__params, __format)?;
+            }
+        }
+        _ => quote! {
+            #[derive(::dlink_wm::__private::serde::Deserialize)]
+            #[serde(crate = "::dlink_wm::__private::serde")]
+            struct Params {
+                #(#params: #param_types,)*
+            }
+            let Params { #(#params),* } = ::dlink_wm::codec::Decode::decode(&__params, __format)?;
+        },
+    };
+
+    let name = &sig.ident;
+    let vis = &func.vis;
+    let (call, returns) = match &sig.output {
+        ReturnType::Default => (quote!(#name(#(#args_in_call),*)), "()".to_string()),
+        ReturnType::Type(_, ty) => match result_ok_type(ty) {
+            Some(ok) => (quote!(#name(#(#args_in_call),*)?), type_name(ok)),
+            None => (quote!(#name(#(#args_in_call),*)), type_name(ty)),
+        },
+    };
+
+    let method_name = args.name.map_or_else(|| name.to_string(), |name| name.value());
+    let param_names = params.iter().map(|param| param.to_string());
+    let param_type_names = param_types.iter().map(type_name);
+    let docs = docs(&func);
+    let struct_doc = format!(" Host method `{}`; register it with `HostMethodRegistry::register_method`.", method_name);
+
+    Ok(quote! {
+        #func
+
+        #[doc = #struct_doc]
+        #[allow(non_camel_case_types)]
+        #vis struct #name {}
+
+        impl ::dlink_wm::registry::HostMethodDef for #name {
+            const SCHEMA: ::dlink_wm::registry::MethodSchema = ::dlink_wm::registry::MethodSchema {
+                name: #method_name,
+                params: &[#(::dlink_wm::registry::ParamSchema { name: #param_names, ty: #param_type_names }),*],
+                returns: #returns,
+                docs: #docs,
+            };
+
+            fn handler() -> ::dlink_wm::host_import::MethodHandler {
+                ::std::sync::Arc::new(
+                    |__ctx: &mut ::dlink_wm::host_import::HostContext<'_>,
+                     __params: ::std::vec::Vec<u8>,
+                     __format: ::dlink_wm::host_import::SerializationFormat|
+                     -> ::dlink_wm::__private::anyhow::Result<(bool, ::std::vec::Vec<u8>)> {
+                        #decode
+                        let __result = #call;
+                        ::std::result::Result::Ok((true, ::dlink_wm::codec::Encode::encode(&__result, __format)?))
+                    },
+                )
+            }
+        }
+    })
+}
+
+/// Checks whether a parameter is the `&mut HostContext` of the call.
+fn is_host_context(ty: &Type) -> bool {
+    match ty {
+        Type::Reference(reference) => match &*reference.elem {
+            Type::Path(path) => path.path.segments.last().is_some_and(|segment| segment.ident == "HostContext"),
+            _ => false,
+        },
+        _ => false,
+    }
+}
+
+/// Gets `T` of a `Result<T>` or `Result<T, E>` return type.
+fn result_ok_type(ty: &Type) -> Option<&Type> {
+    let Type::Path(path) = ty else {
+        return None;
+    };
+    let segment = path.path.segments.last()?;
+    if segment.ident != "Result" {
+        return None;
+    }
+    let PathArguments::AngleBracketed(args) = &segment.arguments else {
+        return None;
+    };
+    match args.args.first()? {
+        GenericArgument::Type(ok) => Some(ok),
+        _ => None,
+    }
+}
+
+/// Renders a type as written in the signature, e.g. `Vec<String>`.
+fn type_name(ty: &Type) -> String {
+    let mut name = ty.to_token_stream().to_string();
+    for (spaced, tight) in [(" :: ", "::"), (":: ", "::"), (" < ", "<"), ("< ", "<"), (" <", "<"), (" >", ">"), (" ,", ","), ("& ", "&"), ("( ", "("), (" )", ")")] {
+        name = name.replace(spaced, tight);
+    }
+    name
+}
+
+/// Joins the `///` documentation of a function.
+fn docs(func: &ItemFn) -> String {
+    let lines: Vec<String> = func
+        .attrs
+        .iter()
+        .filter(|attr| attr.path().is_ident("doc"))
+        .filter_map(|attr| match &attr.meta {
+            Meta::NameValue(meta) => match &meta.value {
+                Expr::Lit(expr) => match &expr.lit {
+                    Lit::Str(doc) => Some(doc.value().trim().to_string()),
+                    _ => None,
+                },
+                _ => None,
+            },
+            _ => None,
+        })
+        .collect();
+    lines.join("\n").trim().to_string()
+}
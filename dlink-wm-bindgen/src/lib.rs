@@ -0,0 +1,311 @@
+//! # DlinkWM Bindgen
+//!
+//! Generates typed bindings for DlinkWM host methods from a WIT package, at
+//! build time:
+//!
+//! - **host_bindings!**: For the host. A `Host` trait per interface to implement, and a
+//!   `register` function adding its methods to a `HostMethodRegistry`
+//! - **guest_bindings!**: For guests. A function per host method that serializes the
+//!   parameters and calls it through the `dlink-wm-guest` SDK
+//! - **entry**: For guests. Exports a function as an entry function
+//! - **dlink_host_method**: For the host. Generates the handler and schema of a host
+//!   method from a Rust function
+//!
+//! Both macros take the path of a `.wit` file or of a WIT package directory,
+//! relative to the crate's `Cargo.toml`, or the WIT source itself with
+//! `inline = "..."`. A world is selected with `world = "..."` if the package has
+//! several:
+//!
+//! ```wit
+//! package example:users
+//!
+//! interface users {
+//!   record user { id: u64, name: string }
+//!   get-user: func(id: u64) -> option<user>
+//! }
+//!
+//! world plugin { import users }
+//! ```
+//!
+//! Every interface imported by the world becomes a module named after it. WIT
+//! types map to Rust as follows:
+//!
+//! | WIT | Rust |
+//! |-----|------|
+//! | `bool`, `u8`..`u64`, `s8`..`s64`, `float32`, `float64`, `char` | `bool`, `u8`..`u64`, `i8`..`i64`, `f32`, `f64`, `char` |
+//! | `string` | `String` |
+//! | `list<T>` | `Vec<T>` |
+//! | `option<T>` | `Option<T>` |
+//! | `result<T, E>` | `Result<T, E>` |
+//! | `tuple<A, B>` | `(A, B)` |
+//! | `record`, `enum`, `variant` | `struct` or `enum` deriving serde's `Serialize` and `Deserialize` |
+//! | `type x = T` | `pub type X = T` |
+//!
+//! `flags`, `resource`, `union`, `future` and `stream` are not supported.
+//!
+//! ## Wire Format
+//!
+//! A function `get-user` of interface `users` is registered as the host method
+//! `users.get-user`. Its parameters are a JSON object keyed by the WIT parameter
+//! names (`{"id": 1}`), and its result is the JSON encoding of the return value, so
+//! guests written in other languages can call the same methods. Records and
+//! variants keep their WIT field and case names.
+//!
+//! ## Re-exports
+//!
+//! The generated code refers to `dlink_wm` or `dlink_wm_guest`, so the macros are
+//! meant to be used through `dlink_wm::host_bindings!`, `dlink_wm::dlink_host_method`,
+//! `dlink_wm_guest::guest_bindings!` and `dlink_wm_guest::entry`.
+
+use proc_macro::TokenStream;
+use proc_macro2::Span;
+use std::path::{Path, PathBuf};
+use syn::parse::{Parse, ParseStream};
+use syn::{parse_macro_input, Ident, ItemFn, LitStr, Token};
+use wit_parser::{Resolve, UnresolvedPackage, WorldId};
+
+mod entry;
+mod guest;
+mod host;
+mod host_method;
+mod types;
+
+/// # Host Bindings
+///
+/// Generates, for every interface imported by the world, a module with the WIT
+/// types, a `Host` trait with a method per WIT function, `METHODS` listing the host
+/// method names and `register`, which adds the methods of a `Host` to a registry.
+///
+/// The generated code refers to the crate as `::dlink_wm`; use it through the
+/// `dlink_wm::host_bindings!` re-export.
+///
+/// # Example
+///
+/// ```rust,ignore
+/// dlink_wm::host_bindings!("wit/users.wit");
+///
+/// struct Users;
+///
+/// impl users::Host for Users {
+///     fn get_user(&self, id: u64) -> anyhow::Result<Option<users::User>> {
+///         Ok(Some(users::User { id, name: "WASM".to_string() }))
+///     }
+/// }
+///
+/// users::register(&registry, std::sync::Arc::new(Users));
+/// ```
+#[proc_macro]
+pub fn host_bindings(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as BindgenInput);
+    expand(input, host::generate)
+}
+
+/// # Guest Bindings
+///
+/// Generates, for every interface imported by the world, a module with the WIT
+/// types and a function per WIT function that calls the host method through
+/// `dlink_wm_guest::invoke`.
+///
+/// The generated code refers to the guest SDK as `::dlink_wm_guest`; use it through
+/// the `dlink_wm_guest::guest_bindings!` re-export.
+///
+/// # Example
+///
+/// ```rust,ignore
+/// dlink_wm_guest::guest_bindings!("wit/users.wit");
+///
+/// let user: Option<users::User> = users::get_user(7)?;
+/// ```
+#[proc_macro]
+pub fn guest_bindings(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as BindgenInput);
+    expand(input, guest::generate)
+}
+
+/// # Entry Function
+///
+/// Exports a guest function with the C ABI under its name, or under `name` with
+/// `#[entry(name = "...")]`. The return value is converted with
+/// `dlink_wm_guest::EntryReturn`; use it through the `dlink_wm_guest::entry`
+/// re-export.
+///
+/// # Example
+///
+/// ```rust,ignore
+/// #[dlink_wm_guest::entry]
+/// fn dlinkwm_init() -> i32 {
+///     0
+/// }
+/// ```
+#[proc_macro_attribute]
+pub fn entry(args: TokenStream, item: TokenStream) -> TokenStream {
+    let args = parse_macro_input!(args as NameArgs);
+    let func = parse_macro_input!(item as ItemFn);
+    entry::generate(args, func).unwrap_or_else(syn::Error::into_compile_error).into()
+}
+
+/// # Host Method
+///
+/// Turns a function into a host method definition: a struct named after the
+/// function implementing `dlink_wm::registry::HostMethodDef`, whose handler decodes
+/// the parameters in the guest's format, calls the function and encodes the result.
+/// The method is registered under the function's name, or under `name` with
+/// `#[dlink_host_method(name = "...")]`.
+///
+/// - No parameters: the payload is ignored
+/// - One parameter: the payload is the parameter
+/// - Several parameters: the payload is an object (a sequence in Bincode) of the
+///   parameters by name
+/// - A `&mut HostContext` parameter receives the context of the call and is not
+///   part of the payload
+///
+/// The function may return a value or a `Result` whose error converts into
+/// `anyhow::Error`. Use it through the `dlink_wm::dlink_host_method` re-export.
+///
+/// # Example
+///
+/// ```rust,ignore
+/// #[dlink_host_method]
+/// fn greet(params: GreetParams) -> anyhow::Result<String> {
+///     Ok(format!("Hello, {}!", params.name))
+/// }
+///
+/// registry.register_method::<greet>();
+/// ```
+#[proc_macro_attribute]
+pub fn dlink_host_method(args: TokenStream, item: TokenStream) -> TokenStream {
+    let args = parse_macro_input!(args as NameArgs);
+    let func = parse_macro_input!(item as ItemFn);
+    host_method::generate(args, func).unwrap_or_else(syn::Error::into_compile_error).into()
+}
+
+/// Arguments of `#[entry]` and `#[dlink_host_method]`: an optional `name = "..."`.
+struct NameArgs {
+    name: Option<LitStr>,
+}
+
+impl Parse for NameArgs {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        if input.is_empty() {
+            return Ok(NameArgs { name: None });
+        }
+        let key: Ident = input.parse()?;
+        if key != "name" {
+            return Err(syn::Error::new(key.span(), "unknown option, expected `name`"));
+        }
+        input.parse::<Token![=]>()?;
+        let name = input.parse()?;
+        input.parse::<Option<Token![,]>>()?;
+        Ok(NameArgs { name: Some(name) })
+    }
+}
+
+/// Where the WIT package comes from.
+enum WitSource {
+    Path(LitStr),
+    Inline(LitStr),
+}
+
+/// Arguments of `host_bindings!` and `guest_bindings!`.
+struct BindgenInput {
+    source: WitSource,
+    world: Option<LitStr>,
+}
+
+impl Parse for BindgenInput {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        let source = if input.peek(LitStr) {
+            WitSource::Path(input.parse()?)
+        } else {
+            let key: Ident = input.parse()?;
+            if key != "inline" {
+                return Err(syn::Error::new(key.span(), "expected a path or `inline = \"...\"`"));
+            }
+            input.parse::<Token![=]>()?;
+            WitSource::Inline(input.parse()?)
+        };
+
+        let mut world = None;
+        while input.parse::<Option<Token![,]>>()?.is_some() {
+            if input.is_empty() {
+                break;
+            }
+            let key: Ident = input.parse()?;
+            if key != "world" {
+                return Err(syn::Error::new(key.span(), "unknown option, expected `world`"));
+            }
+            input.parse::<Token![=]>()?;
+            world = Some(input.parse()?);
+        }
+
+        Ok(BindgenInput { source, world })
+    }
+}
+
+/// A parsed WIT package with the selected world.
+pub(crate) struct Wit {
+    pub resolve: Resolve,
+    pub world: WorldId,
+}
+
+/// Loads the WIT package and runs a generator on it, turning errors into
+/// `compile_error!`.
+fn expand(input: BindgenInput, generate: fn(&Wit) -> Result<proc_macro2::TokenStream, String>) -> TokenStream {
+    let span = match &input.source {
+        WitSource::Path(lit) | WitSource::Inline(lit) => lit.span(),
+    };
+    let result = load(&input).and_then(|(wit, files)| {
+        let tokens = generate(&wit)?;
+        // Rebuild when a WIT file changes
+        let files = files.iter().map(|file| file.display().to_string());
+        Ok(quote::quote! {
+            #(const _: &[u8] = include_bytes!(#files);)*
+            #tokens
+        })
+    });
+
+    match result {
+        Ok(tokens) => tokens.into(),
+        Err(message) => syn::Error::new(span, message).to_compile_error().into(),
+    }
+}
+
+/// Parses the WIT package and selects the world.
+///
+/// Returns the package with the files it was read from.
+fn load(input: &BindgenInput) -> Result<(Wit, Vec<PathBuf>), String> {
+    let mut resolve = Resolve::new();
+    let (package, files) = match &input.source {
+        WitSource::Path(path) => {
+            let manifest_dir = std::env::var("CARGO_MANIFEST_DIR").map_err(|err| err.to_string())?;
+            let path = Path::new(&manifest_dir).join(path.value());
+            if path.is_dir() {
+                resolve.push_dir(&path).map_err(|err| format!("{:#}", err))?
+            } else {
+                let package = UnresolvedPackage::parse_file(&path).map_err(|err| format!("{:#}", err))?;
+                let files = package.source_files().map(Path::to_path_buf).collect();
+                (resolve.push(package).map_err(|err| format!("{:#}", err))?, files)
+            }
+        }
+        WitSource::Inline(source) => {
+            let package = UnresolvedPackage::parse(Path::new("inline.wit"), &source.value())
+                .map_err(|err| format!("{:#}", err))?;
+            (resolve.push(package).map_err(|err| format!("{:#}", err))?, Vec::new())
+        }
+    };
+
+    let world_name = input.world.as_ref().map(LitStr::value);
+    let world = resolve
+        .select_world(package, world_name.as_deref())
+        .map_err(|err| format!("{:#}", err))?;
+
+    Ok((Wit { resolve, world }, files))
+}
+
+/// Creates an identifier, escaping Rust keywords.
+pub(crate) fn ident(name: &str) -> Ident {
+    match name {
+        "self" | "super" | "crate" | "Self" => Ident::new(&format!("{}_", name), Span::call_site()),
+        _ => syn::parse_str::<Ident>(name).unwrap_or_else(|_| Ident::new_raw(name, Span::call_site())),
+    }
+}
@@ -0,0 +1,321 @@
+//! Rust types for WIT types, shared by the host and guest generators.
+
+use heck::{ToSnakeCase, ToUpperCamelCase};
+use proc_macro2::TokenStream;
+use quote::quote;
+use wit_parser::{Docs, Function, InterfaceId, Results, Type, TypeDefKind, TypeId, TypeOwner, WorldItem};
+use crate::{ident, Wit};
+
+/// Paths that differ between host and guest code.
+pub(crate) struct Flavor {
+    /// Path of `String`
+    pub string: TokenStream,
+    /// Path of `Vec`
+    pub vec: TokenStream,
+    /// Path of the `serde` crate
+    pub serde: TokenStream,
+    /// Attributes added to every serde derive
+    pub serde_attrs: TokenStream,
+}
+
+/// Lists the interfaces imported by the world.
+pub(crate) fn imported_interfaces(wit: &Wit) -> Result<Vec<InterfaceId>, String> {
+    let world = &wit.resolve.worlds[wit.world];
+    let mut interfaces = Vec::new();
+    for (key, item) in world.imports.iter() {
+        match item {
+            WorldItem::Interface(id) => interfaces.push(*id),
+            WorldItem::Function(func) => {
+                return Err(format!(
+                    "function `{}` is imported directly by world `{}`; declare it in an interface",
+                    func.name, world.name
+                ))
+            }
+            WorldItem::Type(_) => {
+                return Err(format!(
+                    "type `{}` is imported directly by world `{}`; declare it in an interface",
+                    wit.resolve.name_world_key(key),
+                    world.name
+                ))
+            }
+        }
+    }
+    Ok(interfaces)
+}
+
+/// WIT name of an interface.
+pub(crate) fn interface_name(wit: &Wit, id: InterfaceId) -> Result<&str, String> {
+    wit.resolve.interfaces[id]
+        .name
+        .as_deref()
+        .ok_or_else(|| "inline interfaces in worlds are not supported; declare a named interface".to_string())
+}
+
+/// Module generated for an interface.
+pub(crate) fn interface_module(wit: &Wit, id: InterfaceId) -> Result<syn::Ident, String> {
+    Ok(ident(&interface_name(wit, id)?.to_snake_case()))
+}
+
+/// Host method name of a function, `<interface>.<function>`.
+pub(crate) fn method_name(wit: &Wit, id: InterfaceId, func: &Function) -> Result<String, String> {
+    Ok(format!("{}.{}", interface_name(wit, id)?, func.name))
+}
+
+/// `#[doc]` attributes of WIT documentation.
+pub(crate) fn docs(docs: &Docs) -> TokenStream {
+    let lines = docs.contents.iter().flat_map(|contents| contents.lines()).map(|line| format!(" {}", line.trim()));
+    quote! { #(#[doc = #lines])* }
+}
+
+/// Rust type of a WIT type.
+pub(crate) fn render_type(wit: &Wit, flavor: &Flavor, ty: &Type) -> Result<TokenStream, String> {
+    Ok(match ty {
+        Type::Bool => quote!(bool),
+        Type::U8 => quote!(u8),
+        Type::U16 => quote!(u16),
+        Type::U32 => quote!(u32),
+        Type::U64 => quote!(u64),
+        Type::S8 => quote!(i8),
+        Type::S16 => quote!(i16),
+        Type::S32 => quote!(i32),
+        Type::S64 => quote!(i64),
+        Type::Float32 => quote!(f32),
+        Type::Float64 => quote!(f64),
+        Type::Char => quote!(char),
+        Type::String => flavor.string.clone(),
+        Type::Id(id) => render_type_id(wit, flavor, *id)?,
+    })
+}
+
+/// Rust type of a WIT type definition, named or anonymous.
+fn render_type_id(wit: &Wit, flavor: &Flavor, id: TypeId) -> Result<TokenStream, String> {
+    let def = &wit.resolve.types[id];
+    if let Some(name) = &def.name {
+        let module = match def.owner {
+            TypeOwner::Interface(owner) => interface_module(wit, owner)?,
+            _ => return Err(format!("type `{}` must be declared in an interface", name)),
+        };
+        let name = ident(&name.to_upper_camel_case());
+        return Ok(quote!(super::#module::#name));
+    }
+    render_anonymous(wit, flavor, &def.kind)
+}
+
+/// Rust type of an anonymous WIT type such as `list<T>`.
+fn render_anonymous(wit: &Wit, flavor: &Flavor, kind: &TypeDefKind) -> Result<TokenStream, String> {
+    let render_opt = |ty: &Option<Type>| match ty {
+        Some(ty) => render_type(wit, flavor, ty),
+        None => Ok(quote!(())),
+    };
+    Ok(match kind {
+        TypeDefKind::List(ty) => {
+            let ty = render_type(wit, flavor, ty)?;
+            let vec = &flavor.vec;
+            quote!(#vec<#ty>)
+        }
+        TypeDefKind::Option(ty) => {
+            let ty = render_type(wit, flavor, ty)?;
+            quote!(::core::option::Option<#ty>)
+        }
+        TypeDefKind::Result(result) => {
+            let ok = render_opt(&result.ok)?;
+            let err = render_opt(&result.err)?;
+            quote!(::core::result::Result<#ok, #err>)
+        }
+        TypeDefKind::Tuple(tuple) => {
+            let types = tuple.types.iter().map(|ty| render_type(wit, flavor, ty)).collect::<Result<Vec<_>, _>>()?;
+            quote!((#(#types,)*))
+        }
+        TypeDefKind::Type(ty) => render_type(wit, flavor, ty)?,
+        other => return Err(format!("WIT type `{}` is not supported", kind_name(other))),
+    })
+}
+
+/// Rust items for the named types of an interface.
+pub(crate) fn render_interface_types(wit: &Wit, flavor: &Flavor, id: InterfaceId) -> Result<TokenStream, String> {
+    let serde = &flavor.serde;
+    let serde_attrs = &flavor.serde_attrs;
+    let derives = quote! {
+        #[derive(Debug, Clone, PartialEq, #serde::Serialize, #serde::Deserialize)]
+        #serde_attrs
+    };
+
+    let mut items = TokenStream::new();
+    for (name, type_id) in wit.resolve.interfaces[id].types.iter() {
+        let def = &wit.resolve.types[*type_id];
+        let type_docs = docs(&def.docs);
+        let rust_name = ident(&name.to_upper_camel_case());
+        let item = match &def.kind {
+            TypeDefKind::Record(record) => {
+                let fields = record
+                    .fields
+                    .iter()
+                    .map(|field| {
+                        let field_docs = docs(&field.docs);
+                        let wit_name = &field.name;
+                        let field_name = ident(&field.name.to_snake_case());
+                        let ty = render_type(wit, flavor, &field.ty)?;
+                        Ok(quote! {
+                            #field_docs
+                            #[serde(rename = #wit_name)]
+                            pub #field_name: #ty,
+                        })
+                    })
+                    .collect::<Result<Vec<_>, String>>()?;
+                quote! {
+                    #type_docs
+                    #derives
+                    pub struct #rust_name {
+                        #(#fields)*
+                    }
+                }
+            }
+            TypeDefKind::Enum(enum_) => {
+                let cases = enum_.cases.iter().map(|case| {
+                    let case_docs = docs(&case.docs);
+                    let wit_name = &case.name;
+                    let case_name = ident(&case.name.to_upper_camel_case());
+                    quote! {
+                        #case_docs
+                        #[serde(rename = #wit_name)]
+                        #case_name,
+                    }
+                });
+                quote! {
+                    #type_docs
+                    #[derive(Copy, Eq)]
+                    #derives
+                    pub enum #rust_name {
+                        #(#cases)*
+                    }
+                }
+            }
+            TypeDefKind::Variant(variant) => {
+                let cases = variant
+                    .cases
+                    .iter()
+                    .map(|case| {
+                        let case_docs = docs(&case.docs);
+                        let wit_name = &case.name;
+                        let case_name = ident(&case.name.to_upper_camel_case());
+                        let payload = match &case.ty {
+                            Some(ty) => {
+                                let ty = render_type(wit, flavor, ty)?;
+                                quote!((#ty))
+                            }
+                            None => quote!(),
+                        };
+                        Ok(quote! {
+                            #case_docs
+                            #[serde(rename = #wit_name)]
+                            #case_name #payload,
+                        })
+                    })
+                    .collect::<Result<Vec<_>, String>>()?;
+                quote! {
+                    #type_docs
+                    #derives
+                    pub enum #rust_name {
+                        #(#cases)*
+                    }
+                }
+            }
+            kind @ (TypeDefKind::Type(_)
+            | TypeDefKind::List(_)
+            | TypeDefKind::Option(_)
+            | TypeDefKind::Result(_)
+            | TypeDefKind::Tuple(_)) => {
+                let ty = render_anonymous(wit, flavor, kind)?;
+                quote! {
+                    #type_docs
+                    pub type #rust_name = #ty;
+                }
+            }
+            other => return Err(format!("WIT type `{}` ({}) is not supported", name, kind_name(other))),
+        };
+        items.extend(item);
+    }
+    Ok(items)
+}
+
+/// Rust function name and parameters of a WIT function.
+pub(crate) struct Signature {
+    pub name: syn::Ident,
+    /// Rust parameter names
+    pub params: Vec<syn::Ident>,
+    /// WIT parameter names
+    pub wit_params: Vec<String>,
+    pub param_types: Vec<TokenStream>,
+    pub result: TokenStream,
+}
+
+/// Maps a WIT function to Rust.
+///
+/// Functions without results return `()`, a single result is returned as is and
+/// several named results as a tuple.
+pub(crate) fn signature(wit: &Wit, flavor: &Flavor, func: &Function) -> Result<Signature, String> {
+    let mut params = Vec::new();
+    let mut wit_params = Vec::new();
+    let mut param_types = Vec::new();
+    for (name, ty) in func.params.iter() {
+        params.push(ident(&name.to_snake_case()));
+        wit_params.push(name.clone());
+        param_types.push(render_type(wit, flavor, ty)?);
+    }
+
+    let result = match &func.results {
+        Results::Anon(ty) => render_type(wit, flavor, ty)?,
+        Results::Named(results) if results.len() == 1 => render_type(wit, flavor, &results[0].1)?,
+        Results::Named(results) => {
+            let types = results.iter().map(|(_, ty)| render_type(wit, flavor, ty)).collect::<Result<Vec<_>, _>>()?;
+            quote!((#(#types,)*))
+        }
+    };
+
+    Ok(Signature {
+        name: ident(&func.name.to_snake_case()),
+        params,
+        wit_params,
+        param_types,
+        result,
+    })
+}
+
+/// Struct holding the parameters of a host method on the wire.
+pub(crate) fn params_struct(flavor: &Flavor, signature: &Signature, derive: TokenStream) -> TokenStream {
+    let serde_attrs = &flavor.serde_attrs;
+    let fields = signature
+        .params
+        .iter()
+        .zip(&signature.wit_params)
+        .zip(&signature.param_types)
+        .map(|((name, wit_name), ty)| quote!(#[serde(rename = #wit_name)] #name: #ty,));
+    quote! {
+        #[derive(#derive)]
+        #serde_attrs
+        struct Params {
+            #(#fields)*
+        }
+    }
+}
+
+/// WIT keyword of an unsupported type.
+fn kind_name(kind: &TypeDefKind) -> &'static str {
+    match kind {
+        TypeDefKind::Record(_) => "record",
+        TypeDefKind::Resource => "resource",
+        TypeDefKind::Handle(_) => "handle",
+        TypeDefKind::Flags(_) => "flags",
+        TypeDefKind::Tuple(_) => "tuple",
+        TypeDefKind::Variant(_) => "variant",
+        TypeDefKind::Enum(_) => "enum",
+        TypeDefKind::Option(_) => "option",
+        TypeDefKind::Result(_) => "result",
+        TypeDefKind::Union(_) => "union",
+        TypeDefKind::List(_) => "list",
+        TypeDefKind::Future(_) => "future",
+        TypeDefKind::Stream(_) => "stream",
+        TypeDefKind::Type(_) => "type",
+        TypeDefKind::Unknown => "unknown",
+    }
+}
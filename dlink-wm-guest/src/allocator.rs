@@ -0,0 +1,70 @@
+//! # Host Allocator
+//!
+//! `HostAllocator` is a global allocator that serves Rust allocations from
+//! `host_malloc` and `host_free`, so `no_std` guests need no allocator of their own.
+
+use core::alloc::{GlobalAlloc, Layout};
+use dlink_wm_abi::HOST_MALLOC_ALIGN;
+use crate::ffi;
+
+/// Bytes in front of an over-aligned block holding the pointer to free.
+const BASE_PTR_SIZE: usize = core::mem::size_of::<i32>();
+
+/// # Host Allocator
+///
+/// Global allocator backed by the host. Blocks are `HOST_MALLOC_ALIGN`-aligned;
+/// larger alignments are served by over-allocating.
+///
+/// The host allocator only hands out memory it grew itself, so it can be mixed with
+/// other allocators in the same module.
+///
+/// # Example
+///
+/// ```rust
+/// use dlink_wm_guest::HostAllocator;
+///
+/// #[cfg(target_arch = "wasm32")]
+/// #[global_allocator]
+/// static ALLOCATOR: HostAllocator = HostAllocator;
+/// ```
+pub struct HostAllocator;
+
+unsafe impl GlobalAlloc for HostAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        if layout.align() <= HOST_MALLOC_ALIGN {
+            return host_malloc(layout.size());
+        }
+
+        // Align inside a larger block and remember where the block starts
+        let Some(size) = layout.size().checked_add(layout.align() + BASE_PTR_SIZE) else {
+            return core::ptr::null_mut();
+        };
+        let base = host_malloc(size);
+        if base.is_null() {
+            return base;
+        }
+        let addr = base as usize + BASE_PTR_SIZE;
+        let aligned = (addr + layout.align() - 1) & !(layout.align() - 1);
+        let ptr = aligned as *mut u8;
+        unsafe { (ptr.sub(BASE_PTR_SIZE) as *mut i32).write_unaligned(base as usize as i32) };
+        ptr
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        let base = if layout.align() <= HOST_MALLOC_ALIGN {
+            ptr as usize as i32
+        } else {
+            unsafe { (ptr.sub(BASE_PTR_SIZE) as *const i32).read_unaligned() }
+        };
+        unsafe { ffi::host_free(base) };
+    }
+}
+
+/// Calls `host_malloc`, mapping failures to a null pointer.
+fn host_malloc(size: usize) -> *mut u8 {
+    let Ok(size) = i32::try_from(size) else {
+        return core::ptr::null_mut();
+    };
+    let ptr = unsafe { ffi::host_malloc(size) };
+    ptr as usize as *mut u8
+}
@@ -0,0 +1,58 @@
+//! # Entry Points
+//!
+//! Return values of functions exported with `#[entry]`.
+
+use core::fmt::Display;
+
+/// # Entry Return
+///
+/// A return type of an `#[entry]` function. `Abi` is the type returned to the host.
+///
+/// Integers, floats and `()` are returned as they are. `Result<T, E>` returns `T` on
+/// success and traps on error, which the host reports as `STATUS_TRAP`.
+pub trait EntryReturn {
+    /// Type returned by the exported function
+    type Abi;
+
+    /// Converts the return value for the host.
+    fn into_abi(self) -> Self::Abi;
+}
+
+macro_rules! entry_return {
+    ($($ty:ty),*) => {
+        $(
+            impl EntryReturn for $ty {
+                type Abi = $ty;
+
+                fn into_abi(self) -> Self::Abi {
+                    self
+                }
+            }
+        )*
+    };
+}
+
+entry_return!((), i32, u32, i64, u64, f32, f64);
+
+impl<T: EntryReturn, E: Display> EntryReturn for Result<T, E> {
+    type Abi = T::Abi;
+
+    fn into_abi(self) -> Self::Abi {
+        match self {
+            Ok(value) => value.into_abi(),
+            Err(err) => trap(&err),
+        }
+    }
+}
+
+/// Aborts the current call.
+#[cfg(target_arch = "wasm32")]
+fn trap(_err: &dyn Display) -> ! {
+    core::arch::wasm32::unreachable()
+}
+
+/// Aborts the current call.
+#[cfg(not(target_arch = "wasm32"))]
+fn trap(err: &dyn Display) -> ! {
+    panic!("entry function failed: {}", err)
+}
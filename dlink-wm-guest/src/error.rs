@@ -0,0 +1,62 @@
+//! # Errors
+//!
+//! `Error` is returned by `invoke` and by the functions of `guest_bindings!`.
+
+use alloc::string::String;
+use core::fmt;
+use dlink_wm_abi::status;
+
+/// # Guest Error
+///
+/// Error of a host method call.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Error {
+    /// The host rejected the call with a status code from `dlink_wm_abi::status`,
+    /// e.g. `STATUS_METHOD_NOT_FOUND`
+    Status {
+        /// Status code returned by `universal_invoke_v2`
+        code: i32,
+        /// Error message written by the host, if any
+        message: String,
+    },
+    /// The host method ran and reported a failure
+    Failed(String),
+    /// The parameters or the response could not be (de)serialized
+    Serialization(String),
+    /// The response does not fit into the buffer
+    ResponseTooLarge {
+        /// Size of the response data reported by the host
+        len: usize,
+    },
+    /// `host_malloc` could not allocate a buffer
+    OutOfMemory,
+}
+
+impl Error {
+    /// Returns the status code of the error, using the codes of `dlink_wm_abi::status`
+    /// for errors detected in the guest.
+    pub fn code(&self) -> i32 {
+        match self {
+            Error::Status { code, .. } => *code,
+            Error::Failed(_) => status::STATUS_EXECUTION_ERROR,
+            Error::Serialization(_) => status::STATUS_FORMAT_ERROR,
+            Error::ResponseTooLarge { .. } => status::STATUS_MEMORY_ACCESS,
+            Error::OutOfMemory => status::STATUS_RESOURCE_LIMIT,
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Status { code, message } => write!(f, "host returned status {}: {}", code, message),
+            Error::Failed(message) => write!(f, "host method failed: {}", message),
+            Error::Serialization(message) => write!(f, "serialization error: {}", message),
+            Error::ResponseTooLarge { len } => write!(f, "response of {} bytes does not fit the buffer", len),
+            Error::OutOfMemory => write!(f, "host_malloc could not allocate a buffer"),
+        }
+    }
+}
+
+/// Result type of the guest SDK.
+pub type Result<T> = core::result::Result<T, Error>;
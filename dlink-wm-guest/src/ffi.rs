@@ -0,0 +1,20 @@
+//! Raw `dlinkwm_host` imports. Names and signatures follow `dlink_wm_abi`.
+
+#[link(wasm_import_module = "dlinkwm_host")]
+unsafe extern "C" {
+    pub(crate) fn universal_invoke_v2(
+        method_name_ptr: i32,
+        method_name_len: i32,
+        format_type: i32,
+        params_ptr: i32,
+        params_len: i32,
+        ret_ptr: i32,
+        ret_capacity: i32,
+    ) -> i32;
+
+    pub(crate) fn take_response(ret_ptr: i32, ret_capacity: i32) -> i32;
+
+    pub(crate) fn host_malloc(size: i32) -> i32;
+
+    pub(crate) fn host_free(ptr: i32);
+}
@@ -0,0 +1,185 @@
+//! # Host Method Calls
+//!
+//! `invoke` and `invoke_raw` wrap `universal_invoke_v2`: they allocate the response
+//! buffer with `host_malloc`, fetch responses that do not fit it with
+//! `take_response`, decode the `ResponseHeader` and free the buffer again.
+
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+use dlink_wm_abi::{format, status, ResponseHeader, RESPONSE_HEADER_SIZE};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use crate::error::{Error, Result};
+use crate::ffi;
+
+/// Size of the buffer first offered for a response, including the header.
+///
+/// Larger responses are fetched into a buffer of their size, at the cost of a
+/// second call into the host.
+pub const RESPONSE_CAPACITY: usize = 4 * 1024;
+
+/// # Format
+///
+/// Serialization format of the parameters and the response of a call.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    /// JSON serialization format
+    Json,
+    /// Bincode serialization format
+    Bincode,
+    /// Protocol Buffers serialization format
+    Protobuf,
+    /// FlatBuffers (FlexBuffers encoding) serialization format
+    FlatBuffers,
+}
+
+impl Format {
+    /// Returns the `format_type` identifier passed to `universal_invoke_v2`.
+    pub fn format_type(self) -> i32 {
+        match self {
+            Format::Json => format::JSON,
+            Format::Bincode => format::BINCODE,
+            Format::Protobuf => format::PROTOBUF,
+            Format::FlatBuffers => format::FLATBUFFERS,
+        }
+    }
+}
+
+/// # Invoke a Host Method
+///
+/// Calls a host method with JSON parameters and decodes its JSON response.
+///
+/// # Parameters
+///
+/// - `method`: Name of the host method
+/// - `params`: Parameters of the method
+///
+/// # Errors
+///
+/// - `Error::Status` if the host rejected the call, e.g. for an unknown method
+/// - `Error::Failed` if the host method returned a failure
+/// - `Error::Serialization` if the parameters or the response are not valid JSON for
+///   their types
+///
+/// # Example
+///
+/// ```rust
+/// use dlink_wm_guest::invoke;
+/// use serde::Serialize;
+///
+/// #[derive(Serialize)]
+/// struct GreetParams<'a> {
+///     name: &'a str,
+/// }
+///
+/// fn greet(name: &str) -> dlink_wm_guest::Result<String> {
+///     invoke("greet", &GreetParams { name })
+/// }
+/// ```
+pub fn invoke<P, R>(method: &str, params: &P) -> Result<R>
+where
+    P: Serialize + ?Sized,
+    R: DeserializeOwned,
+{
+    let params = serde_json::to_vec(params).map_err(|err| Error::Serialization(err.to_string()))?;
+    let response = invoke_raw(method, Format::Json, &params)?;
+    serde_json::from_slice(&response).map_err(|err| Error::Serialization(err.to_string()))
+}
+
+/// # Invoke a Host Method with Raw Bytes
+///
+/// Calls a host method with parameters already serialized in `format` and returns
+/// the serialized response.
+///
+/// # Errors
+///
+/// - `Error::Status` if the host rejected the call
+/// - `Error::Failed` if the host method returned a failure
+/// - `Error::ResponseTooLarge` if the response does not fit the buffer the host asked for
+/// - `Error::OutOfMemory` if the response buffer cannot be allocated
+pub fn invoke_raw(method: &str, format: Format, params: &[u8]) -> Result<Vec<u8>> {
+    let mut buffer = HostBuffer::new(RESPONSE_CAPACITY)?;
+
+    let mut code = unsafe {
+        ffi::universal_invoke_v2(
+            method.as_ptr() as usize as i32,
+            method.len() as i32,
+            format.format_type(),
+            params.as_ptr() as usize as i32,
+            params.len() as i32,
+            buffer.ptr,
+            buffer.capacity as i32,
+        )
+    };
+    if code == status::STATUS_BUFFER_TOO_SMALL {
+        // The header holds the size of the response the host kept
+        let len = buffer.read_header().len as usize;
+        // The length comes from the host; `host_malloc` takes an i32 size
+        let required = RESPONSE_HEADER_SIZE
+            .checked_add(len)
+            .filter(|&required| i32::try_from(required).is_ok())
+            .ok_or(Error::ResponseTooLarge { len })?;
+        buffer = HostBuffer::new(required)?;
+        code = unsafe { ffi::take_response(buffer.ptr, buffer.capacity as i32) };
+    }
+
+    let header = buffer.read_header();
+    let len = header.len as usize;
+    if len > buffer.capacity - RESPONSE_HEADER_SIZE {
+        return Err(Error::ResponseTooLarge { len });
+    }
+    let data = buffer.read_data(len);
+
+    if code != status::STATUS_OK {
+        let message = String::from_utf8_lossy(&data).into_owned();
+        return Err(Error::Status { code, message });
+    }
+    if !header.success {
+        return Err(Error::Failed(String::from_utf8_lossy(&data).into_owned()));
+    }
+    Ok(data)
+}
+
+/// A block of guest memory from `host_malloc` receiving a response, freed on drop.
+struct HostBuffer {
+    ptr: i32,
+    capacity: usize,
+}
+
+impl HostBuffer {
+    fn new(capacity: usize) -> Result<Self> {
+        let ptr = unsafe { ffi::host_malloc(capacity as i32) };
+        if ptr == 0 {
+            return Err(Error::OutOfMemory);
+        }
+        let buffer = HostBuffer { ptr, capacity };
+        // The host leaves the header untouched if it cannot write a response
+        buffer.write_header(ResponseHeader { success: false, len: 0 });
+        Ok(buffer)
+    }
+
+    fn as_ptr(&self) -> *mut u8 {
+        self.ptr as usize as *mut u8
+    }
+
+    fn write_header(&self, header: ResponseHeader) {
+        let bytes = header.encode();
+        unsafe { core::ptr::copy_nonoverlapping(bytes.as_ptr(), self.as_ptr(), RESPONSE_HEADER_SIZE) };
+    }
+
+    fn read_header(&self) -> ResponseHeader {
+        let mut bytes = [0u8; RESPONSE_HEADER_SIZE];
+        unsafe { core::ptr::copy_nonoverlapping(self.as_ptr(), bytes.as_mut_ptr(), RESPONSE_HEADER_SIZE) };
+        ResponseHeader::decode(bytes)
+    }
+
+    fn read_data(&self, len: usize) -> Vec<u8> {
+        unsafe { core::slice::from_raw_parts(self.as_ptr().add(RESPONSE_HEADER_SIZE), len).to_vec() }
+    }
+}
+
+impl Drop for HostBuffer {
+    fn drop(&mut self) {
+        unsafe { ffi::host_free(self.ptr) };
+    }
+}
@@ -0,0 +1,126 @@
+//! # DlinkWM Guest SDK
+//!
+//! Safe wrappers for WASM modules loaded by DlinkWM, in place of hand-written
+//! `dlinkwm_host` imports and response parsing:
+//!
+//! - **invoke**: Calls a host method with serde parameters and decodes the response
+//! - **HostAllocator**: Global allocator backed by `host_malloc` and `host_free`
+//! - **entry**: Attribute exporting a function as an entry function
+//! - **guest_bindings!**: Typed functions for the host methods of a WIT file
+//! - **Error**: Typed errors carrying the status codes of `dlink_wm_abi::status`
+//!
+//! The crate is `no_std` and needs an allocator, e.g. `HostAllocator`. With the
+//! `panic-handler` feature it also defines a panic handler that traps, so a
+//! `no_std` plugin needs no other boilerplate. Calls use the wire format of
+//! `dlink_wm_abi`, the crate the host implements `universal_invoke_v2` with.
+//!
+//! # Example
+//!
+//! ```rust
+//! #![no_std]
+//! # extern crate std;
+//! extern crate alloc;
+//!
+//! use alloc::string::String;
+//! use dlink_wm_guest::{entry, invoke, HostAllocator};
+//! use serde::Serialize;
+//!
+//! #[cfg(target_arch = "wasm32")]
+//! #[global_allocator]
+//! static ALLOCATOR: HostAllocator = HostAllocator;
+//!
+//! #[derive(Serialize)]
+//! struct GreetParams<'a> {
+//!     name: &'a str,
+//! }
+//!
+//! /// Exported as `dlinkwm_call_host_method`; an error traps the call
+//! #[entry]
+//! fn dlinkwm_call_host_method() -> dlink_wm_guest::Result<i32> {
+//!     let greeting: String = invoke("custom_greet", &GreetParams { name: "WASM" })?;
+//!     Ok(greeting.len() as i32)
+//! }
+//! # fn main() {}
+//! ```
+
+#![no_std]
+
+extern crate alloc;
+
+mod allocator;
+mod entry;
+mod error;
+mod ffi;
+mod invoke;
+
+pub use allocator::HostAllocator;
+pub use entry::EntryReturn;
+pub use error::{Error, Result};
+pub use invoke::{invoke, invoke_raw, Format, RESPONSE_CAPACITY};
+pub use dlink_wm_abi as abi;
+
+/// # Entry Function
+///
+/// Exports a function to the host under its name, or under `name` with
+/// `#[entry(name = "...")]`. Parameters must be WASM value types; the return type
+/// must implement `EntryReturn`.
+///
+/// # Example
+///
+/// ```rust
+/// #[dlink_wm_guest::entry(name = "add")]
+/// fn add_numbers(a: i32, b: i32) -> i32 {
+///     a + b
+/// }
+///
+/// assert_eq!(add_numbers(2, 3), 5);
+/// ```
+pub use dlink_wm_bindgen::entry;
+
+/// # Guest Bindings
+///
+/// Generates a module per interface of a WIT world with the WIT types and a
+/// function per host method, which calls it through `invoke`. See the
+/// `dlink-wm-bindgen` crate for the type mapping and the wire format.
+///
+/// # Example
+///
+/// ```rust
+/// dlink_wm_guest::guest_bindings!(inline = "
+///     package example:users
+///
+///     interface users {
+///       record user { id: u64, display-name: string }
+///       get-user: func(id: u64) -> option<user>
+///     }
+///
+///     world plugin { import users }
+/// ");
+///
+/// fn display_name(id: u64) -> dlink_wm_guest::Result<String> {
+///     let user = users::get_user(id)?;
+///     Ok(user.map(|user| user.display_name).unwrap_or_default())
+/// }
+///
+/// fn main() {
+///     // Records keep their WIT field names on the wire
+///     let user = users::User { id: 1, display_name: "WASM".into() };
+///     assert_eq!(serde_json::to_string(&user).unwrap(), r#"{"id":1,"display-name":"WASM"}"#);
+/// }
+/// ```
+pub use dlink_wm_bindgen::guest_bindings;
+
+/// Traps on panics, for `no_std` guests.
+#[cfg(all(feature = "panic-handler", target_arch = "wasm32"))]
+#[panic_handler]
+fn panic(_info: &core::panic::PanicInfo) -> ! {
+    core::arch::wasm32::unreachable()
+}
+
+#[doc(hidden)]
+pub mod __private {
+    //! Items used by the code of `guest_bindings!` and `#[entry]`.
+    pub use alloc::string::String;
+    pub use alloc::vec::Vec;
+    pub use serde;
+}
@@ -1,15 +1,17 @@
 //! DlinkWM Custom Host Methods Example
 //! Demonstrates how to register custom host methods dynamically in application code
 
-use dlink_wm::host_import::{register_host_method, SerializationFormat};
+use dlink_wm::codec::JsonParams;
+use dlink_wm::dlink_host_method;
+use dlink_wm::host_import::register_host_method_def;
 use dlink_wm::wasm_manager::{WasmInstanceCache, WasmHotReloader, call_wasm_function};
 use dlink_wm::config::{DynamicConfig, create_default_config_if_missing, get_default_config_path};
 use std::sync::Arc;
 use clap::Parser;
 use env_logger::Env;
-use anyhow::{anyhow, Result as AnyResult, Result};
+use anyhow::{anyhow, Result};
 use std::io::{BufRead, Write};
-use serde::{Serialize, Deserialize};
+use serde::Deserialize;
 
 /// Example command line arguments
 #[derive(Parser, Debug)]
@@ -25,27 +27,18 @@ struct Args {
     config_path: Option<String>,
 }
 
-/// JSON params wrapper (replicated from the host_import module for example purposes)
-#[derive(Debug, Serialize, Deserialize)]
-struct JsonParams<T> {
-    data: T,
+/// Parameters of the `custom_greet` method
+#[derive(Debug, Deserialize)]
+struct GreetParams {
+    name: String,
 }
 
-// -------------------------- Custom Host Method Handlers --------------------------
+// -------------------------- Custom Host Methods --------------------------
 
-/// Custom greeting method handler - 返回字符串给WASM
-fn custom_greet_handler(params_bytes: Vec<u8>, format: SerializationFormat) -> AnyResult<(bool, Vec<u8>)> {
-    match format {
-        SerializationFormat::Json => {
-            #[derive(Debug, Serialize, Deserialize)]
-            struct GreetParams { name: String }
-            
-            let params: JsonParams<GreetParams> = serde_json::from_slice(&params_bytes)?;
-            let result = format!("Hello from custom handler, {}!", params.data.name);
-            Ok((true, serde_json::to_vec(&result)?))
-        },
-        _ => Err(anyhow!("Format not supported for custom_greet method")),
-    }
+/// Custom greeting method - 返回字符串给WASM
+#[dlink_host_method]
+fn custom_greet(params: JsonParams<GreetParams>) -> String {
+    format!("Hello from custom handler, {}!", params.data.name)
 }
 
 fn main() -> Result<()> {
@@ -67,7 +60,7 @@ fn main() -> Result<()> {
     println!("🔧 Registering custom host methods...");
     
     // Register only the custom greeting method
-    if register_host_method("custom_greet", custom_greet_handler) {
+    if register_host_method_def::<custom_greet>() {
         println!("✅ Successfully registered 'custom_greet' method");
     } else {
         println!("⚠️  Failed to register 'custom_greet' method (already exists)");
@@ -98,8 +91,8 @@ fn main() -> Result<()> {
     let instance_cache = Arc::new(WasmInstanceCache::new());
     println!("✅ WASM instance cache created");
 
-    // If hot reload is enabled, start hot reload monitoring
-    if args.hot_reload {
+    // If hot reload is enabled, start hot reload monitoring (kept running by its handle)
+    let _hot_reload_handle = if args.hot_reload {
         println!("� Enabling hot reload functionality");
         
         // Extract WASM file directory
@@ -111,13 +104,16 @@ fn main() -> Result<()> {
         
         // Start hot reload manager
         let hot_reloader = WasmHotReloader::new(instance_cache.clone(), wasm_dir);
-        hot_reloader.start();
+        let handle = hot_reloader.start()?;
         
         println!("✅ Hot reload monitoring started");
         println!("💡 Tip: Changes to WASM module will take effect automatically after rebuilding");
         println!("   Changes to configuration file will also be detected");
         println!();
-    }
+        Some(handle)
+    } else {
+        None
+    };
 
     println!("🎉 Custom host methods registered and available for WASM to use");
     println!("💡 Available host methods:");
@@ -0,0 +1,198 @@
+//! # Guest Memory Allocator
+//!
+//! This module provides the allocator that backs the `host_malloc` and `host_free`
+//! host imports. Each store owns its own `GuestAllocator`, so allocations made by one
+//! instance never affect another.
+//!
+//! The allocator never touches memory that the guest already owns. When it runs out
+//! of free space, the host grows the guest's linear memory with `memory.grow` and
+//! manages only the pages it added itself. Guest-side allocators that also use
+//! `memory.grow` therefore never overlap with host allocations.
+
+use std::collections::{BTreeMap, HashMap};
+use crate::error::{DlinkError, Result};
+
+/// Size of a WASM linear memory page in bytes.
+pub const WASM_PAGE_SIZE: u64 = 65536;
+
+/// Alignment (in bytes) of every block returned by the allocator.
+pub const ALLOC_ALIGN: u64 = dlink_wm_abi::HOST_MALLOC_ALIGN as u64;
+
+/// # Allocator Statistics
+///
+/// Per-store accounting for host allocations, useful to detect leaks in guests
+/// that allocate through `host_malloc` and forget to call `host_free`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct AllocatorStats {
+    /// Bytes currently handed out to the guest (after alignment rounding)
+    pub bytes_in_use: u64,
+    /// Number of allocations that have not been freed yet
+    pub live_allocations: u64,
+    /// Total number of successful allocations since the store was created
+    pub total_allocations: u64,
+    /// Total number of successful frees since the store was created
+    pub total_frees: u64,
+    /// Highest value `bytes_in_use` has reached
+    pub peak_bytes_in_use: u64,
+    /// Bytes of linear memory the allocator has obtained through `memory.grow`
+    pub heap_bytes: u64,
+}
+
+/// # Guest Allocator
+///
+/// A first-fit free-list allocator over the regions of guest linear memory that the
+/// host has grown. Adjacent free blocks are coalesced on free.
+///
+/// The allocator only does bookkeeping; growing the memory is the caller's job.
+/// When `allocate` returns `None`, grow the memory by `pages_for(size)` pages and
+/// hand the new region to `add_region` before retrying.
+///
+/// # Example
+///
+/// ```rust
+/// use dlink_wm::allocator::{GuestAllocator, WASM_PAGE_SIZE};
+///
+/// let mut allocator = GuestAllocator::new();
+/// assert_eq!(allocator.allocate(100), None);
+///
+/// // Pretend the memory was grown from one page to two
+/// allocator.add_region(WASM_PAGE_SIZE as u32, WASM_PAGE_SIZE as u32);
+/// let a = allocator.allocate(100).unwrap();
+/// let b = allocator.allocate(100).unwrap();
+/// assert_ne!(a, b);
+///
+/// allocator.free(a).unwrap();
+/// assert!(allocator.free(a).is_err()); // Double free is refused
+/// assert_eq!(allocator.stats().live_allocations, 1);
+/// ```
+#[derive(Debug, Default)]
+pub struct GuestAllocator {
+    /// Free blocks keyed by start offset, value is the block length
+    free_blocks: BTreeMap<u64, u64>,
+    /// Live allocations keyed by start offset, value is the block length
+    allocations: HashMap<u64, u64>,
+    /// Allocation accounting
+    stats: AllocatorStats,
+}
+
+impl GuestAllocator {
+    /// Creates an empty allocator that owns no memory yet.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns the number of WASM pages needed to satisfy an allocation of `size` bytes.
+    pub fn pages_for(size: u32) -> u64 {
+        align_up(size as u64).div_ceil(WASM_PAGE_SIZE).max(1)
+    }
+
+    /// Hands a region of linear memory to the allocator.
+    ///
+    /// # Parameters
+    ///
+    /// - `start`: Offset of the first byte of the region
+    /// - `len`: Length of the region in bytes
+    pub fn add_region(&mut self, start: u32, len: u32) {
+        let mut start = start as u64;
+        let end = start + len as u64;
+        // Never hand out offset 0, it is the null pointer for guests
+        if start == 0 {
+            start = ALLOC_ALIGN;
+        }
+        let start = align_up(start);
+        if start >= end {
+            return;
+        }
+        self.stats.heap_bytes += end - start;
+        self.insert_free_block(start, end - start);
+    }
+
+    /// Allocates `size` bytes from the free list.
+    ///
+    /// # Returns
+    ///
+    /// The offset of the allocated block, or `None` if no free block is large enough
+    /// or `size` is zero.
+    pub fn allocate(&mut self, size: u32) -> Option<u32> {
+        if size == 0 {
+            return None;
+        }
+        let size = align_up(size as u64);
+        let (&start, &len) = self.free_blocks.iter().find(|(_, &len)| len >= size)?;
+        self.free_blocks.remove(&start);
+        if len > size {
+            self.free_blocks.insert(start + size, len - size);
+        }
+        self.allocations.insert(start, size);
+
+        self.stats.bytes_in_use += size;
+        self.stats.live_allocations += 1;
+        self.stats.total_allocations += 1;
+        self.stats.peak_bytes_in_use = self.stats.peak_bytes_in_use.max(self.stats.bytes_in_use);
+        Some(start as u32)
+    }
+
+    /// Frees a block previously returned by `allocate`.
+    ///
+    /// # Returns
+    ///
+    /// The size of the freed block in bytes.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error without changing any state if:
+    /// - The pointer was already freed (`DlinkError::DoubleFree`)
+    /// - The pointer was never returned by this allocator (`DlinkError::InvalidFree`)
+    pub fn free(&mut self, ptr: u32) -> Result<u64> {
+        let ptr = ptr as u64;
+        match self.allocations.remove(&ptr) {
+            Some(size) => {
+                self.insert_free_block(ptr, size);
+                self.stats.bytes_in_use -= size;
+                self.stats.live_allocations -= 1;
+                self.stats.total_frees += 1;
+                Ok(size)
+            },
+            None if self.is_free(ptr) => Err(DlinkError::DoubleFree(ptr as u32)),
+            None => Err(DlinkError::InvalidFree(ptr as u32)),
+        }
+    }
+
+    /// Returns the size of the live allocation starting at `ptr`, if any.
+    pub fn allocation_size(&self, ptr: u32) -> Option<u64> {
+        self.allocations.get(&(ptr as u64)).copied()
+    }
+
+    /// Returns a snapshot of the allocation accounting.
+    pub fn stats(&self) -> AllocatorStats {
+        self.stats
+    }
+
+    /// Checks whether `ptr` lies inside a free block.
+    fn is_free(&self, ptr: u64) -> bool {
+        self.free_blocks
+            .range(..=ptr)
+            .next_back()
+            .is_some_and(|(&start, &len)| ptr < start + len)
+    }
+
+    /// Inserts a free block, coalescing it with its neighbours.
+    fn insert_free_block(&mut self, mut start: u64, mut len: u64) {
+        if let Some((&prev_start, &prev_len)) = self.free_blocks.range(..start).next_back() {
+            if prev_start + prev_len == start {
+                self.free_blocks.remove(&prev_start);
+                start = prev_start;
+                len += prev_len;
+            }
+        }
+        if let Some(next_len) = self.free_blocks.remove(&(start + len)) {
+            len += next_len;
+        }
+        self.free_blocks.insert(start, len);
+    }
+}
+
+/// Rounds `value` up to the allocator alignment.
+fn align_up(value: u64) -> u64 {
+    value.div_ceil(ALLOC_ALIGN) * ALLOC_ALIGN
+}
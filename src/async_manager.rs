@@ -0,0 +1,438 @@
+//! # Async WASM Management
+//!
+//! This module provides `AsyncWasmInstanceCache`, the async counterpart of
+//! `WasmInstanceCache` for tokio-based hosts. Guest calls are futures, and host
+//! methods registered with `register_async` suspend the guest while they run.
+//!
+//! The cache owns an engine with wasmtime's async support. A background
+//! `EpochTicker` ticks it every `async_yield_interval_ms`, so long-running guests
+//! yield to the executor regularly and calls with a `timeout_ms` are interrupted
+//! within one interval of their deadline.
+
+use std::collections::HashMap;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, RwLock};
+use std::task::{Context, Poll, Wake, Waker};
+use std::time::Duration;
+use tokio::sync::Mutex;
+use wasmtime::{Engine, Instance, InstancePre, Linker, Store, Val, WasmParams, WasmResults};
+use crate::config::{DlinkWMConfig, EngineConfig};
+use crate::error::{DlinkError, Result};
+use crate::host_import::{create_async_engine, create_async_store, create_dlinkwm_async_linker, HostState};
+use crate::limits::{EpochTicker, ExecutionBudget, ResourceLimits};
+use crate::module_cache::ModuleCache;
+use crate::registry::HostMethodRegistry;
+use crate::wasm_manager::{call_error, export_func};
+
+/// # Async Cached Instance
+///
+/// A handle to an instantiated WASM module and its store, locked across awaits.
+pub type AsyncInstanceStore = Arc<Mutex<(Instance, Store<HostState>)>>;
+
+/// # Async WASM Instance Cache
+///
+/// Caches one instance per WASM file on an async engine. Calls to the same file
+/// are serialized by the instance's lock; calls to different files run
+/// concurrently.
+///
+/// # Example
+///
+/// ```rust
+/// use dlink_wm::async_manager::AsyncWasmInstanceCache;
+/// use dlink_wm::config::DlinkWMConfig;
+/// use dlink_wm::host_import::{AsyncHostContext, SerializationFormat};
+/// use dlink_wm::registry::HostMethodRegistry;
+/// use std::sync::{Arc, RwLock};
+/// use std::time::Duration;
+///
+/// // A guest calling the host method "slow_echo" through universal_invoke
+/// let wasm_path = std::env::temp_dir().join("dlinkwm_async_doctest.wasm");
+/// std::fs::write(&wasm_path, r#"(module
+///     (import "dlinkwm_host" "universal_invoke" (func $invoke (param i32 i32 i32 i32 i32 i32) (result i32)))
+///     (memory (export "memory") 1)
+///     (data (i32.const 0) "slow_echo")
+///     (data (i32.const 16) "hi")
+///     (func (export "run") (result i32)
+///         (call $invoke (i32.const 0) (i32.const 9) (i32.const 0) (i32.const 16) (i32.const 2) (i32.const 64)))
+///     (func (export "response_len") (result i32) (i32.load (i32.const 68))))"#).unwrap();
+/// let wasm_path = wasm_path.to_str().unwrap();
+///
+/// let mut config = DlinkWMConfig::default();
+/// config.entry_functions.insert(wasm_path.to_string(), vec!["run".to_string(), "response_len".to_string()]);
+/// let registry = Arc::new(HostMethodRegistry::new());
+/// registry.register_async("slow_echo", Arc::new(|_ctx: AsyncHostContext, params: Vec<u8>, _format: SerializationFormat| {
+///     Box::pin(async move {
+///         tokio::time::sleep(Duration::from_millis(10)).await;
+///         Ok((true, params))
+///     })
+/// }));
+/// let cache = AsyncWasmInstanceCache::with_registry(Arc::new(RwLock::new(config)), registry);
+///
+/// let runtime = tokio::runtime::Runtime::new().unwrap();
+/// runtime.block_on(async {
+///     let status: i32 = cache.call(wasm_path, "run", ()).await.unwrap();
+///     assert_eq!(status, 0);
+///     let len: i32 = cache.call(wasm_path, "response_len", ()).await.unwrap();
+///     assert_eq!(len, 2);
+/// });
+/// ```
+pub struct AsyncWasmInstanceCache {
+    /// Async engine shared by every module and store of the cache
+    engine: Engine,
+    /// Linker with the WASI and DlinkWM host imports, built once per cache
+    linker: Linker<HostState>,
+    /// Cache of compiled WASM modules keyed by content hash
+    module_cache: Arc<ModuleCache>,
+    /// Cache of instantiated WASM modules (each file has one instance)
+    instance_cache: RwLock<HashMap<String, AsyncInstanceStore>>,
+    /// Configuration used to validate entry functions and budgets
+    config: Arc<RwLock<DlinkWMConfig>>,
+    /// Host methods visible to modules loaded by this cache
+    registry: Arc<HostMethodRegistry>,
+    /// Ticks the engine so that guests yield and time out
+    _ticker: EpochTicker,
+}
+
+impl AsyncWasmInstanceCache {
+    /// Creates a new async cache bound to a configuration.
+    ///
+    /// The cache gets its own host method registry, which inherits from
+    /// `HostMethodRegistry::global()`.
+    ///
+    /// # Parameters
+    ///
+    /// - `config`: Shared configuration used to validate entry functions
+    pub fn with_config(config: Arc<RwLock<DlinkWMConfig>>) -> Self {
+        let registry = Arc::new(HostMethodRegistry::with_parent(HostMethodRegistry::global()));
+        Self::with_registry(config, registry)
+    }
+
+    /// Creates a new async cache bound to a configuration and a host method registry.
+    ///
+    /// # Parameters
+    ///
+    /// - `config`: Shared configuration used to validate entry functions
+    /// - `registry`: Host methods visible to modules loaded by this cache
+    pub fn with_registry(config: Arc<RwLock<DlinkWMConfig>>, registry: Arc<HostMethodRegistry>) -> Self {
+        let (engine_config, module_cache_config) = {
+            let config = config.read().unwrap();
+            (config.engine.clone(), config.module_cache.clone())
+        };
+        let engine = create_async_engine(&engine_config).unwrap_or_else(|e| {
+            log::error!("[Engine] Invalid engine configuration, using defaults: {}", e);
+            create_async_engine(&EngineConfig::default()).expect("the default engine configuration is valid")
+        });
+        let linker = create_dlinkwm_async_linker(&engine, Arc::clone(&registry)).expect("host imports are defined once");
+        let module_cache = Arc::new(ModuleCache::new(engine.clone(), &module_cache_config));
+        let interval = Duration::from_millis(engine_config.async_yield_interval_ms.max(1));
+        let ticker = EpochTicker::start(engine.clone(), interval);
+        Self {
+            engine,
+            linker,
+            module_cache,
+            instance_cache: RwLock::new(HashMap::new()),
+            config,
+            registry,
+            _ticker: ticker,
+        }
+    }
+
+    /// Gets the async engine shared by all modules and instances of this cache.
+    pub fn engine(&self) -> &Engine {
+        &self.engine
+    }
+
+    /// Gets the compiled module cache.
+    pub fn module_cache(&self) -> &ModuleCache {
+        &self.module_cache
+    }
+
+    /// Gets a handle to the configuration the cache validates against.
+    pub fn config(&self) -> Arc<RwLock<DlinkWMConfig>> {
+        Arc::clone(&self.config)
+    }
+
+    /// Gets the host method registry of this cache.
+    pub fn registry(&self) -> Arc<HostMethodRegistry> {
+        Arc::clone(&self.registry)
+    }
+
+    /// Loads and instantiates a WASM file, or returns its cached instance.
+    ///
+    /// The file is read and compiled on tokio's blocking thread pool, so a
+    /// compilation does not stall the executor.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the file cannot be read, compiled or instantiated.
+    pub async fn load_and_instantiate(&self, wasm_path: &str) -> Result<AsyncInstanceStore> {
+        if let Some(instance_store) = self.instance_cache.read().unwrap().get(wasm_path) {
+            return Ok(Arc::clone(instance_store));
+        }
+
+        let instance_store = self.build(wasm_path).await?;
+        let mut cache_write = self.instance_cache.write().unwrap();
+        Ok(Arc::clone(cache_write.entry(wasm_path.to_string()).or_insert(instance_store)))
+    }
+
+    /// Compiles and instantiates a WASM file without caching the instance.
+    async fn build(&self, wasm_path: &str) -> Result<AsyncInstanceStore> {
+        let module_cache = Arc::clone(&self.module_cache);
+        let path = wasm_path.to_string();
+        let module = tokio::task::spawn_blocking(move || -> Result<_> {
+            let wasm_bytes = std::fs::read(&path)?;
+            Ok(module_cache.get_or_compile(&wasm_bytes)?.1)
+        })
+        .await
+        .map_err(|e| DlinkError::Wasm(e.into()))??;
+        let instance_pre = self.linker.instantiate_pre(&module)?;
+        let (instance, store) = self.instantiate(wasm_path, &instance_pre).await?;
+        Ok(Arc::new(Mutex::new((instance, store))))
+    }
+
+    /// Instantiates a pre-linked module in a new store with the module's WASI
+    /// sandbox, resource limits and execution budget.
+    async fn instantiate(&self, wasm_path: &str, instance_pre: &InstancePre<HostState>) -> Result<(Instance, Store<HostState>)> {
+        let module_config = self.config.read().unwrap().module_config(wasm_path);
+
+        let mut store = create_async_store(&self.engine, &module_config.wasi)?;
+        store.data_mut().set_module_path(wasm_path);
+        store.data_mut().set_config(self.config());
+        store.data_mut().set_registry(self.registry());
+
+        // Install the module's resource limits; count limits are read on installation
+        store.data_mut().limiter_mut().set_limits(ResourceLimits::from_config(&module_config));
+        store.limiter(|state| state.limiter_mut());
+        store.data().limiter().check_module(wasm_path, instance_pre.module())?;
+
+        // Instantiate module, running any start function under the module's budget
+        let budget = ExecutionBudget::from_config(&module_config);
+        budget.apply_async(&mut store)?;
+        match Cooperative::new(instance_pre.instantiate_async(&mut store)).await {
+            Ok(instance) => Ok((instance, store)),
+            Err(e) => Err(call_error(&mut store, &budget, "start", e)),
+        }
+    }
+
+    /// Calls an entry function with statically typed parameters and results.
+    ///
+    /// See `WasmInstanceCache::call`; the guest runs until it completes, yielding
+    /// to the executor on every epoch tick and while async host methods run.
+    ///
+    /// # Errors
+    ///
+    /// Returns the same errors as `WasmInstanceCache::call`.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use dlink_wm::async_manager::AsyncWasmInstanceCache;
+    /// use dlink_wm::config::{DlinkWMConfig, ModuleConfig};
+    /// use dlink_wm::error::DlinkError;
+    /// use std::sync::{Arc, RwLock};
+    ///
+    /// // A guest that never returns still lets other tasks run and times out
+    /// let wasm_path = std::env::temp_dir().join("dlinkwm_async_spin_doctest.wasm");
+    /// std::fs::write(&wasm_path, r#"(module (func (export "spin") (loop br 0)))"#).unwrap();
+    /// let wasm_path = wasm_path.to_str().unwrap();
+    ///
+    /// let mut config = DlinkWMConfig::default();
+    /// config.entry_functions.insert(wasm_path.to_string(), vec!["spin".to_string()]);
+    /// config.modules.insert(wasm_path.to_string(), ModuleConfig {
+    ///     timeout_ms: Some(100),
+    ///     ..Default::default()
+    /// });
+    /// let cache = AsyncWasmInstanceCache::with_config(Arc::new(RwLock::new(config)));
+    ///
+    /// let runtime = tokio::runtime::Builder::new_current_thread().enable_all().build().unwrap();
+    /// runtime.block_on(async {
+    ///     let timer = tokio::spawn(tokio::time::sleep(std::time::Duration::from_millis(10)));
+    ///     let result = cache.call::<(), ()>(wasm_path, "spin", ()).await;
+    ///     assert!(matches!(result, Err(DlinkError::Timeout { .. })));
+    ///     assert!(timer.is_finished());
+    /// });
+    /// ```
+    pub async fn call<Params, Results>(&self, wasm_path: &str, func_name: &str, args: Params) -> Result<Results>
+    where
+        Params: WasmParams + Sync,
+        Results: WasmResults,
+    {
+        self.check_entry_function(wasm_path, func_name)?;
+        let instance_store = self.load_and_instantiate(wasm_path).await?;
+        let mut guard = instance_store.lock().await;
+        let (instance, ref mut store) = *guard;
+
+        let func = export_func(instance, store, func_name)?;
+        let typed_func = func.typed::<Params, Results>(&*store).map_err(|e| DlinkError::SignatureMismatch {
+            function: func_name.to_string(),
+            detail: e.to_string(),
+        })?;
+        let budget = self.prepare_call(store)?;
+        match Cooperative::new(typed_func.call_async(&mut *store, args)).await {
+            Ok(results) => Ok(results),
+            Err(e) => Err(call_error(store, &budget, func_name, e)),
+        }
+    }
+
+    /// Calls an entry function with dynamically typed parameters and results.
+    ///
+    /// # Errors
+    ///
+    /// Returns the same errors as `WasmInstanceCache::call_dynamic`.
+    pub async fn call_dynamic(&self, wasm_path: &str, func_name: &str, args: &[Val]) -> Result<Vec<Val>> {
+        self.check_entry_function(wasm_path, func_name)?;
+        let instance_store = self.load_and_instantiate(wasm_path).await?;
+        let mut guard = instance_store.lock().await;
+        let (instance, ref mut store) = *guard;
+
+        // Validate the arguments against the function signature
+        let func = export_func(instance, store, func_name)?;
+        let func_type = func.ty(&*store);
+        let param_types: Vec<_> = func_type.params().collect();
+        let arg_types: Vec<_> = args.iter().map(Val::ty).collect();
+        if param_types != arg_types {
+            return Err(DlinkError::SignatureMismatch {
+                function: func_name.to_string(),
+                detail: format!("expected parameters {:?}, got {:?}", param_types, arg_types),
+            });
+        }
+
+        let mut results = vec![Val::I32(0); func_type.results().len()];
+        let budget = self.prepare_call(store)?;
+        match Cooperative::new(func.call_async(&mut *store, args, &mut results)).await {
+            Ok(()) => Ok(results),
+            Err(e) => Err(call_error(store, &budget, func_name, e)),
+        }
+    }
+
+    /// Removes the cached instance of a WASM file; the next call reinstantiates it.
+    pub fn clear_cache(&self, wasm_path: &str) {
+        self.instance_cache.write().unwrap().remove(wasm_path);
+    }
+
+    /// Replaces the cached instance of a WASM file with a new one.
+    ///
+    /// The new instance is created before it replaces the cached one, so calls
+    /// already running finish on the previous instance.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the file cannot be reloaded and reinstantiated; the
+    /// previous instance then stays cached.
+    pub async fn hot_reload(&self, wasm_path: &str) -> Result<AsyncInstanceStore> {
+        let instance_store = self.build(wasm_path).await?;
+        self.instance_cache.write().unwrap().insert(wasm_path.to_string(), Arc::clone(&instance_store));
+        Ok(instance_store)
+    }
+
+    /// Validates an entry function against the configuration.
+    fn check_entry_function(&self, wasm_path: &str, func_name: &str) -> Result<()> {
+        let config = self.config.read().unwrap();
+        if !config.is_entry_function(wasm_path, func_name) {
+            return Err(DlinkError::EntryFunctionNotAllowed {
+                function: func_name.to_string(),
+                wasm_path: wasm_path.to_string(),
+                allowed: config.entry_functions.get(wasm_path).cloned().unwrap_or_default(),
+            });
+        }
+        Ok(())
+    }
+
+    /// Refuels the store, sets the call deadline, refreshes the module's limits and
+    /// drops a response an earlier call did not take.
+    fn prepare_call(&self, store: &mut Store<HostState>) -> Result<ExecutionBudget> {
+        let module_config = self.config.read().unwrap().module_config(store.data().module_path());
+        store.data_mut().clear_pending_response();
+        let limiter = store.data_mut().limiter_mut();
+        limiter.set_limits(ResourceLimits::from_config(&module_config));
+        limiter.take_violation();
+
+        let budget = ExecutionBudget::from_config(&module_config);
+        budget.apply_async(store)?;
+        Ok(budget)
+    }
+}
+
+/// Runs a guest future so that its yields let the runtime poll timers and I/O.
+///
+/// Wasmtime yields by waking the task before returning `Pending`, which tokio
+/// treats as ready work: its timer and I/O drivers would only be polled every few
+/// dozen yields. Such self-wakes are replaced by `tokio::task::yield_now`, which
+/// defers the wake until the drivers have been polled.
+struct Cooperative<F> {
+    /// Guest future
+    inner: Pin<Box<F>>,
+    /// Waker handed to the guest future
+    waker: Arc<SelfWakeDetector>,
+    /// Pending yield to the runtime
+    yield_now: Option<Pin<Box<dyn Future<Output = ()> + Send>>>,
+}
+
+impl<F: Future> Cooperative<F> {
+    fn new(inner: F) -> Self {
+        Self {
+            inner: Box::pin(inner),
+            waker: Arc::new(SelfWakeDetector::default()),
+            yield_now: None,
+        }
+    }
+}
+
+impl<F: Future> Future for Cooperative<F> {
+    type Output = F::Output;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<F::Output> {
+        let this = self.get_mut();
+        if let Some(yield_now) = this.yield_now.as_mut() {
+            if yield_now.as_mut().poll(cx).is_pending() {
+                return Poll::Pending;
+            }
+            this.yield_now = None;
+        }
+
+        *this.waker.task.lock().unwrap() = Some(cx.waker().clone());
+        this.waker.woken.store(false, Ordering::SeqCst);
+        this.waker.polling.store(true, Ordering::SeqCst);
+        let waker = Waker::from(Arc::clone(&this.waker));
+        let result = this.inner.as_mut().poll(&mut Context::from_waker(&waker));
+        this.waker.polling.store(false, Ordering::SeqCst);
+
+        if result.is_pending() && this.waker.woken.swap(false, Ordering::SeqCst) {
+            // The guest yielded: come back once the runtime has polled its drivers
+            let mut yield_now: Pin<Box<dyn Future<Output = ()> + Send>> = Box::pin(tokio::task::yield_now());
+            if yield_now.as_mut().poll(cx).is_pending() {
+                this.yield_now = Some(yield_now);
+            } else {
+                cx.waker().wake_by_ref();
+            }
+        }
+        result
+    }
+}
+
+/// Waker of a `Cooperative` future, recording wakes that happen while it is polled.
+#[derive(Default)]
+struct SelfWakeDetector {
+    /// Set while the guest future is polled
+    polling: AtomicBool,
+    /// Set when the guest future woke itself during the poll
+    woken: AtomicBool,
+    /// Waker of the task running the future
+    task: std::sync::Mutex<Option<Waker>>,
+}
+
+impl Wake for SelfWakeDetector {
+    fn wake(self: Arc<Self>) {
+        self.wake_by_ref();
+    }
+
+    fn wake_by_ref(self: &Arc<Self>) {
+        if self.polling.load(Ordering::SeqCst) {
+            self.woken.store(true, Ordering::SeqCst);
+        } else if let Some(task) = self.task.lock().unwrap().as_ref() {
+            task.wake_by_ref();
+        }
+    }
+}
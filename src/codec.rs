@@ -0,0 +1,160 @@
+//! # Codecs
+//!
+//! This module implements the serialization formats listed in `SerializationFormat`
+//! so that host methods never have to decode guest bytes by hand.
+//!
+//! - **JSON**: `serde_json`, for any serde type
+//! - **Bincode**: `bincode`, for any serde type
+//! - **FlatBuffers**: schema-less FlexBuffers (`flexbuffers`), for any serde type; schema-based
+//!   FlatBuffers tables are not supported
+//! - **Protobuf**: `prost`, for message types wrapped in `Proto<M>`
+//!
+//! Types are encoded through the `Encode` trait and decoded through `Decode`. Both
+//! are implemented for every serde type, and for `Proto<M>` for `prost` messages.
+
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use crate::error::{DlinkError, Result};
+use crate::host_import::SerializationFormat;
+
+/// # Encode
+///
+/// Serializes a value in the format requested by the guest.
+pub trait Encode {
+    /// Encodes `self` in the given format.
+    ///
+    /// # Errors
+    ///
+    /// Returns `DlinkError::Serialization` if the value cannot be encoded in `format`.
+    fn encode(&self, format: SerializationFormat) -> Result<Vec<u8>>;
+}
+
+/// # Decode
+///
+/// Deserializes a value from bytes in the format chosen by the guest.
+pub trait Decode: Sized {
+    /// Decodes a value of this type from `bytes` in the given format.
+    ///
+    /// # Errors
+    ///
+    /// Returns `DlinkError::Serialization` if the bytes are not a valid encoding.
+    fn decode(bytes: &[u8], format: SerializationFormat) -> Result<Self>;
+}
+
+impl<T: Serialize + ?Sized> Encode for T {
+    fn encode(&self, format: SerializationFormat) -> Result<Vec<u8>> {
+        match format {
+            SerializationFormat::Json => Ok(serde_json::to_vec(self)?),
+            SerializationFormat::Bincode => bincode::serialize(self).map_err(serialization_error),
+            SerializationFormat::FlatBuffers => flexbuffers::to_vec(self).map_err(serialization_error),
+            SerializationFormat::Protobuf => Err(DlinkError::Serialization(
+                "Protobuf encoding requires a prost message wrapped in Proto<M>".to_string(),
+            )),
+        }
+    }
+}
+
+impl<T: DeserializeOwned> Decode for T {
+    fn decode(bytes: &[u8], format: SerializationFormat) -> Result<Self> {
+        match format {
+            SerializationFormat::Json => Ok(serde_json::from_slice(bytes)?),
+            SerializationFormat::Bincode => bincode::deserialize(bytes).map_err(serialization_error),
+            SerializationFormat::FlatBuffers => flexbuffers::from_slice(bytes).map_err(serialization_error),
+            SerializationFormat::Protobuf => Err(DlinkError::Serialization(
+                "Protobuf decoding requires a prost message wrapped in Proto<M>".to_string(),
+            )),
+        }
+    }
+}
+
+/// # Protobuf Message Wrapper
+///
+/// Wraps a `prost` message so it can be used wherever `Encode`/`Decode` are expected.
+/// Only `SerializationFormat::Protobuf` is supported for wrapped messages.
+///
+/// # Example
+///
+/// ```rust
+/// use dlink_wm::codec::{decode, encode, Proto};
+/// use dlink_wm::host_import::SerializationFormat;
+///
+/// #[derive(Clone, PartialEq, prost::Message)]
+/// struct Greeting {
+///     #[prost(string, tag = "1")]
+///     text: String,
+/// }
+///
+/// let message = Proto(Greeting { text: "hello".to_string() });
+/// let bytes = encode(&message, SerializationFormat::Protobuf).unwrap();
+/// let decoded: Proto<Greeting> = decode(&bytes, SerializationFormat::Protobuf).unwrap();
+/// assert_eq!(decoded, message);
+///
+/// // Protobuf messages are not available in the serde formats
+/// assert!(encode(&message, SerializationFormat::Json).is_err());
+/// ```
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Proto<M>(pub M);
+
+impl<M: prost::Message> Encode for Proto<M> {
+    fn encode(&self, format: SerializationFormat) -> Result<Vec<u8>> {
+        match format {
+            SerializationFormat::Protobuf => Ok(self.0.encode_to_vec()),
+            other => Err(DlinkError::Serialization(format!(
+                "Protobuf message cannot be encoded as {:?}",
+                other
+            ))),
+        }
+    }
+}
+
+impl<M: prost::Message + Default> Decode for Proto<M> {
+    fn decode(bytes: &[u8], format: SerializationFormat) -> Result<Self> {
+        match format {
+            SerializationFormat::Protobuf => M::decode(bytes).map(Proto).map_err(serialization_error),
+            other => Err(DlinkError::Serialization(format!(
+                "Protobuf message cannot be decoded from {:?}",
+                other
+            ))),
+        }
+    }
+}
+
+/// # JSON Params Envelope
+///
+/// Envelope used by guests that wrap their parameters in a `data` field, e.g.
+/// `{"data":{"name":"WASM"}}`. Use it as the parameter type of a typed handler to
+/// accept such payloads.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct JsonParams<T> {
+    /// The wrapped parameters
+    pub data: T,
+}
+
+/// Encodes a value in the given format.
+///
+/// # Example
+///
+/// ```rust
+/// use dlink_wm::codec::{decode, encode};
+/// use dlink_wm::host_import::SerializationFormat;
+///
+/// for format in [SerializationFormat::Json, SerializationFormat::Bincode, SerializationFormat::FlatBuffers] {
+///     let bytes = encode(&("hello".to_string(), 42u32), format).unwrap();
+///     let value: (String, u32) = decode(&bytes, format).unwrap();
+///     assert_eq!(value, ("hello".to_string(), 42));
+/// }
+/// ```
+pub fn encode<T: Encode + ?Sized>(value: &T, format: SerializationFormat) -> Result<Vec<u8>> {
+    value.encode(format)
+}
+
+/// Decodes a value from bytes in the given format.
+///
+/// See `encode` for an example.
+pub fn decode<T: Decode>(bytes: &[u8], format: SerializationFormat) -> Result<T> {
+    T::decode(bytes, format)
+}
+
+/// Converts a codec error into `DlinkError::Serialization`.
+fn serialization_error(e: impl std::fmt::Display) -> DlinkError {
+    DlinkError::Serialization(e.to_string())
+}
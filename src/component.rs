@@ -0,0 +1,203 @@
+//! # Components
+//!
+//! This module adds WebAssembly components next to core modules. `WasmInstanceCache`
+//! tells the two apart by their header (see `is_component`) and instantiates
+//! components with a linker from `create_component_linker`, which provides the
+//! WASI preview2 command imports and the `dlinkwm:host/host` interface.
+//!
+//! ## Host Interface
+//!
+//! The WIT package of the host interface is shipped as `wit/dlinkwm.wit` and
+//! available as `WIT`:
+//!
+//! ```wit
+//! interface host {
+//!   enum format { json, bincode, protobuf, flatbuffers }
+//!   record invoke-error { code: s32, message: string }
+//!   invoke: func(method: string, format: format, params: list<u8>) -> result<list<u8>, invoke-error>
+//! }
+//! ```
+//!
+//! `invoke` is the component counterpart of `universal_invoke`: methods are resolved
+//! in the same registries, subject to the same `host_permissions`, and errors carry
+//! the status codes of the `error` module. Handlers see a `HostContext` without
+//! memory, since components pass parameters by value.
+//!
+//! ## Limitations
+//!
+//! Components have no instance pools, state migration or lifecycle exports, and
+//! `AsyncWasmInstanceCache` only loads core modules.
+
+use std::sync::Arc;
+use wasmtime::component::{ComponentType, Lift, Linker, Lower};
+use wasmtime::{Engine, StoreContextMut};
+use crate::error::{Result, STATUS_EXECUTION_ERROR};
+use crate::host_import::{invoke_component_host_method, HostState, SerializationFormat};
+use crate::registry::HostMethodRegistry;
+
+/// Name of the host interface imported by components.
+pub const HOST_INTERFACE: &str = "dlinkwm:host/host";
+
+/// WIT package defining the host interface and the `plugin` world.
+pub const WIT: &str = include_str!("../wit/dlinkwm.wit");
+
+/// Magic number shared by core modules and components.
+const WASM_MAGIC: &[u8] = b"\0asm";
+
+/// Layer field of the binary header identifying a component.
+const COMPONENT_LAYER: [u8; 2] = [1, 0];
+
+/// # Invoke Format
+///
+/// The `format` enum of the host interface.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ComponentType, Lift, Lower)]
+#[component(enum)]
+pub enum InvokeFormat {
+    /// JSON serialization format
+    #[component(name = "json")]
+    Json,
+    /// Bincode serialization format
+    #[component(name = "bincode")]
+    Bincode,
+    /// Protocol Buffers serialization format
+    #[component(name = "protobuf")]
+    Protobuf,
+    /// FlatBuffers serialization format
+    #[component(name = "flatbuffers")]
+    FlatBuffers,
+}
+
+impl From<InvokeFormat> for SerializationFormat {
+    fn from(format: InvokeFormat) -> Self {
+        match format {
+            InvokeFormat::Json => SerializationFormat::Json,
+            InvokeFormat::Bincode => SerializationFormat::Bincode,
+            InvokeFormat::Protobuf => SerializationFormat::Protobuf,
+            InvokeFormat::FlatBuffers => SerializationFormat::FlatBuffers,
+        }
+    }
+}
+
+/// # Invoke Error
+///
+/// The `invoke-error` record of the host interface.
+#[derive(Debug, Clone, PartialEq, Eq, ComponentType, Lift, Lower)]
+#[component(record)]
+pub struct InvokeError {
+    /// Status code from the table in the `error` module
+    pub code: i32,
+    /// Human-readable error message
+    pub message: String,
+}
+
+/// # Detect Components
+///
+/// Checks whether WASM binary or text is a component rather than a core module.
+///
+/// # Parameters
+///
+/// - `wasm_bytes`: Contents of a `.wasm` or `.wat` file
+///
+/// # Returns
+///
+/// `true` for a component binary or text starting with `(component`.
+///
+/// # Example
+///
+/// ```rust
+/// use dlink_wm::component::is_component;
+///
+/// assert!(is_component(b"(component)"));
+/// assert!(is_component(b";; plugin\n(component)"));
+/// assert!(!is_component(b"(module)"));
+/// ```
+pub fn is_component(wasm_bytes: &[u8]) -> bool {
+    if wasm_bytes.starts_with(WASM_MAGIC) {
+        return wasm_bytes.get(6..8) == Some(&COMPONENT_LAYER[..]);
+    }
+    skip_wat_trivia(wasm_bytes).starts_with(b"(component")
+}
+
+/// Skips the whitespace and comments at the start of WASM text.
+fn skip_wat_trivia(mut text: &[u8]) -> &[u8] {
+    loop {
+        text = text.trim_ascii_start();
+        if text.starts_with(b";;") {
+            let end = text.iter().position(|&b| b == b'\n').unwrap_or(text.len());
+            text = &text[end..];
+        } else if text.starts_with(b"(;") {
+            let end = text.windows(2).position(|w| w == b";)").map_or(text.len(), |end| end + 2);
+            text = &text[end..];
+        } else {
+            return text;
+        }
+    }
+}
+
+/// # Create Component Linker
+///
+/// Creates a linker for components with the WASI preview2 command imports and the
+/// `dlinkwm:host/host` interface. Stores instantiated with it must come from
+/// `host_import::create_component_store`.
+///
+/// # Parameters
+///
+/// - `engine`: Engine created by `create_engine`
+/// - `registry`: Host method registry that `invoke` resolves methods in, unless
+///   the store's `HostState` names another one
+///
+/// # Errors
+///
+/// Returns `DlinkError::Wasm` if an import cannot be defined.
+///
+/// # Example
+///
+/// ```rust
+/// use dlink_wm::component::create_component_linker;
+/// use dlink_wm::config::EngineConfig;
+/// use dlink_wm::host_import::create_engine;
+/// use dlink_wm::registry::HostMethodRegistry;
+///
+/// let engine = create_engine(&EngineConfig::default()).unwrap();
+/// let linker = create_component_linker(&engine, HostMethodRegistry::global()).unwrap();
+/// ```
+pub fn create_component_linker(engine: &Engine, registry: Arc<HostMethodRegistry>) -> Result<Linker<HostState>> {
+    let mut linker = Linker::new(engine);
+
+    // Register WASI preview2 imports
+    wasmtime_wasi::preview2::command::sync::add_to_linker(&mut linker)?;
+
+    // Register the host interface
+    linker.instance(HOST_INTERFACE)?.func_wrap(
+        "invoke",
+        move |store: StoreContextMut<'_, HostState>, (method, format, params): (String, InvokeFormat, Vec<u8>)| {
+            Ok((invoke(&registry, store, method, format, params),))
+        },
+    )?;
+
+    Ok(linker)
+}
+
+/// Runs the host method behind a call of `invoke`.
+fn invoke(
+    registry: &Arc<HostMethodRegistry>,
+    store: StoreContextMut<'_, HostState>,
+    method: String,
+    format: InvokeFormat,
+    params: Vec<u8>,
+) -> std::result::Result<Vec<u8>, InvokeError> {
+    match invoke_component_host_method(registry, store, method, format.into(), params) {
+        Ok((true, response)) => Ok(response),
+        Ok((false, response)) => Err(InvokeError {
+            code: STATUS_EXECUTION_ERROR,
+            message: String::from_utf8_lossy(&response).into_owned(),
+        }),
+        Err(err) => {
+            log::warn!("[ComponentInvoke] {}", err);
+            Err(InvokeError {
+                code: err.code(),
+                message: err.to_string(),
+            })
+        }
+    }
+}
@@ -4,7 +4,10 @@ use std::io::Write;
 use std::path::Path;
 use std::sync::{Arc, RwLock};
 use notify::{Watcher, RecursiveMode, RecommendedWatcher, EventKind, Config};
-use anyhow::Result;
+use crate::error::{DlinkError, Result};
+use crate::events::{ReloadEvent, ReloadEventKind, ReloadEvents, ReloadTimer};
+use crate::module_cache::ModuleCache;
+use crate::utils::glob_match;
 use std::thread;
 use std::sync::mpsc::channel;
 
@@ -27,13 +30,370 @@ pub struct DlinkWMConfig {
     /// "wasm/hello_simple.wasm" = ["dlinkwm_simple_entry"]
     /// ```
     pub entry_functions: std::collections::HashMap<String, Vec<String>>,
+
+    /// # Host Method Permissions
+    /// 
+    /// Defines which host methods each WASM file may call through `universal_invoke`.
+    /// - **Key**: WASM file path or glob pattern (`*` and `?` wildcards)
+    /// - **Value**: List of host method names or glob patterns the file may invoke
+    /// 
+    /// When the table is empty, every module may call every host method. Once it
+    /// contains at least one entry, a module may only call the methods listed under
+    /// the keys matching its path; modules matching no key are denied everything.
+    /// 
+    /// Example TOML configuration:
+    /// ```toml
+    /// [host_permissions]
+    /// "wasm/wasm_test.wasm" = ["custom_greet"]
+    /// "plugins/*.wasm" = ["log", "db.*"]
+    /// ```
+    #[serde(default)]
+    pub host_permissions: std::collections::HashMap<String, Vec<String>>,
+
+    /// # Per-module Settings
+    /// 
+    /// Defines execution settings for individual WASM files.
+    /// - **Key**: WASM file path (relative or absolute)
+    /// - **Value**: Settings applied to the instances of this file
+    /// 
+    /// Example TOML configuration:
+    /// ```toml
+    /// [modules."wasm/wasm_test.wasm"]
+    /// fuel = 10000000
+    /// timeout_ms = 500
+    /// max_memory_pages = 256
+    /// ```
+    #[serde(default)]
+    pub modules: std::collections::HashMap<String, ModuleConfig>,
+
+    /// # Engine Settings
+    /// 
+    /// Compilation settings of the engine shared by all modules of a
+    /// `WasmInstanceCache`. The engine is created with the cache, so changes only
+    /// apply to caches created afterwards.
+    /// 
+    /// Example TOML configuration:
+    /// ```toml
+    /// [engine]
+    /// opt_level = "speed_and_size"
+    /// simd = true
+    /// threads = false
+    /// bulk_memory = true
+    /// ```
+    #[serde(default)]
+    pub engine: EngineConfig,
+
+    /// # Compiled Module Cache
+    /// 
+    /// Where compiled modules are stored on disk and when they are evicted. Modules
+    /// are keyed by the SHA-256 of their bytes, so identical files at different
+    /// paths, and the same file across restarts, are compiled once.
+    /// 
+    /// Example TOML configuration:
+    /// ```toml
+    /// [module_cache]
+    /// dir = ".dlinkwm/cache"
+    /// max_bytes = 268435456
+    /// max_age_secs = 604800
+    /// max_in_memory = 64
+    /// ```
+    #[serde(default)]
+    pub module_cache: ModuleCacheConfig,
+
+    /// # Hot Reload Settings
+    /// 
+    /// How reloaded modules are published. A reload only replaces the running
+    /// version once the new one is compiled and instantiated; the previous version
+    /// is kept for `probation_calls` calls and restored if the new one traps.
+    /// 
+    /// Example TOML configuration:
+    /// ```toml
+    /// [hot_reload]
+    /// probation_calls = 3
+    /// debounce_ms = 300
+    /// ```
+    #[serde(default)]
+    pub hot_reload: HotReloadConfig,
+}
+
+/// # Hot Reload Configuration
+/// 
+/// Settings of the hot reload of WASM files.
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq, Eq)]
+#[serde(default)]
+pub struct HotReloadConfig {
+    /// Number of calls a reloaded module must complete without trapping before the
+    /// previous version is released; a trap within them rolls the module back.
+    /// `0` releases the previous version immediately.
+    pub probation_calls: u64,
+    /// Time in milliseconds a file must stay unchanged before `WasmHotReloader`
+    /// reloads it, so that partially written files are not loaded
+    pub debounce_ms: u64,
+}
+
+impl Default for HotReloadConfig {
+    fn default() -> Self {
+        Self {
+            probation_calls: 3,
+            debounce_ms: 300,
+        }
+    }
+}
+
+/// # Module Cache Configuration
+/// 
+/// Settings of the compiled module cache. Without a `dir`, compiled modules are
+/// only kept in memory.
+#[derive(Debug, Deserialize, Serialize, Clone, Default, PartialEq)]
+pub struct ModuleCacheConfig {
+    /// Directory for serialized compiled modules
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub dir: Option<String>,
+    /// Maximum total size of the artifacts in `dir`; least recently used artifacts are evicted first
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_bytes: Option<u64>,
+    /// Evicts modules that have not been used for this many seconds
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_age_secs: Option<u64>,
+    /// Maximum number of compiled modules kept in memory; least recently used modules are dropped first
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_in_memory: Option<usize>,
+}
+
+/// # Engine Configuration
+/// 
+/// Compilation settings of a wasmtime engine. The defaults match wasmtime's.
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
+#[serde(default)]
+pub struct EngineConfig {
+    /// Cranelift optimisation level
+    pub opt_level: OptLevelConfig,
+    /// Enables the WASM SIMD proposal
+    pub simd: bool,
+    /// Enables the WASM threads proposal (shared memories and atomics)
+    pub threads: bool,
+    /// Enables the WASM bulk memory proposal; disabling it also disables reference types
+    pub bulk_memory: bool,
+    /// Allocates instances from wasmtime's pooling allocator instead of on demand
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub pooling_allocator: Option<PoolingAllocatorConfig>,
+    /// Interval in milliseconds at which guests running on an async engine yield to
+    /// the executor; also the precision of their timeouts
+    pub async_yield_interval_ms: u64,
+}
+
+impl Default for EngineConfig {
+    fn default() -> Self {
+        Self {
+            opt_level: OptLevelConfig::Speed,
+            simd: true,
+            threads: false,
+            bulk_memory: true,
+            pooling_allocator: None,
+            async_yield_interval_ms: 10,
+        }
+    }
+}
+
+/// # Pooling Allocator Configuration
+/// 
+/// Sizes of wasmtime's pooling instance allocator, which reserves memory for a fixed
+/// number of instances up front so that instantiation is cheap. Instances beyond
+/// `total_instances`, or memories larger than `max_memory_pages`, fail to instantiate.
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
+#[serde(default)]
+pub struct PoolingAllocatorConfig {
+    /// Maximum number of instances alive at the same time, across all modules
+    pub total_instances: u32,
+    /// Maximum size of each linear memory in 64 KiB pages
+    pub max_memory_pages: u64,
+}
+
+impl Default for PoolingAllocatorConfig {
+    fn default() -> Self {
+        Self {
+            total_instances: 100,
+            max_memory_pages: 160,
+        }
+    }
+}
+
+/// # Optimisation Level
+/// 
+/// Cranelift optimisation level used to compile modules.
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum OptLevelConfig {
+    /// No optimisations, fastest compilation
+    None,
+    /// Optimise for execution speed
+    #[default]
+    Speed,
+    /// Optimise for execution speed and code size
+    SpeedAndSize,
+}
+
+/// # Module Configuration
+/// 
+/// Execution settings for a single WASM file. Every setting is optional; an empty
+/// table leaves the module unrestricted.
+#[derive(Debug, Deserialize, Serialize, Clone, Default, PartialEq)]
+pub struct ModuleConfig {
+    /// Fuel units each call may consume (roughly one per executed instruction).
+    /// The store is refuelled to this amount before every call.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub fuel: Option<u64>,
+    /// Wall-clock budget of each call in milliseconds, enforced with epoch interruption
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub timeout_ms: Option<u64>,
+    /// Maximum size of each linear memory in 64 KiB pages
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_memory_pages: Option<u64>,
+    /// Maximum number of elements in each table
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_table_elements: Option<u32>,
+    /// Maximum number of instances in the store
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_instances: Option<usize>,
+    /// Maximum number of linear memories in the store
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_memories: Option<usize>,
+    /// WASI sandbox of the module
+    #[serde(default)]
+    pub wasi: WasiConfig,
+    /// Pool of instances for concurrent calls; without it all calls share one instance
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pool: Option<PoolConfig>,
+    /// Configuration passed as JSON to the module's `dlinkwm_init` export
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub init: Option<toml::Value>,
+    /// Interval of the `dlinkwm_health` checks of a `HealthMonitor` in milliseconds;
+    /// the module is not checked when unset
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub health_interval_ms: Option<u64>,
+}
+
+/// # Pool Configuration
+/// 
+/// Sizes the pool of instances of a module. Each pooled instance has its own store,
+/// so calls can run concurrently but guest state is not shared between them.
+/// 
+/// Example TOML configuration:
+/// ```toml
+/// [modules."wasm/worker.wasm".pool]
+/// max_size = 8
+/// min_idle = 2
+/// max_idle = 4
+/// recycle_after = 10000
+/// ```
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq, Eq)]
+#[serde(default)]
+pub struct PoolConfig {
+    /// Maximum number of instances, idle and in use; further checkouts wait
+    pub max_size: usize,
+    /// Number of idle instances kept ready, created when the pool is created and
+    /// after instances are recycled
+    pub min_idle: usize,
+    /// Maximum number of idle instances; instances returned beyond it are dropped
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_idle: Option<usize>,
+    /// Drops an instance after it has been checked out this many times
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub recycle_after: Option<u64>,
+}
+
+impl Default for PoolConfig {
+    fn default() -> Self {
+        Self {
+            max_size: 4,
+            min_idle: 0,
+            max_idle: None,
+            recycle_after: None,
+        }
+    }
+}
+
+/// # WASI Configuration
+/// 
+/// Describes the WASI environment a module runs in. The default inherits the host's
+/// stdio and grants no arguments, environment variables or directories.
+/// 
+/// Example TOML configuration:
+/// ```toml
+/// [modules."wasm/tool.wasm".wasi]
+/// args = ["tool", "--verbose"]
+/// env = { RUST_LOG = "info" }
+/// stdout = "capture"
+/// stderr = { file = "logs/tool.err" }
+/// preopens = [
+///     { host = "data", guest = "/data", read_only = true },
+///     { host = "out", guest = "/out" },
+/// ]
+/// ```
+#[derive(Debug, Deserialize, Serialize, Clone, Default, PartialEq)]
+pub struct WasiConfig {
+    /// Command-line arguments, including the program name
+    #[serde(default)]
+    pub args: Vec<String>,
+    /// Environment variables
+    #[serde(default)]
+    pub env: std::collections::HashMap<String, String>,
+    /// Host directories exposed to the module
+    #[serde(default)]
+    pub preopens: Vec<PreopenConfig>,
+    /// Standard input (`capture` is not available for input)
+    #[serde(default)]
+    pub stdin: StdioConfig,
+    /// Standard output
+    #[serde(default)]
+    pub stdout: StdioConfig,
+    /// Standard error
+    #[serde(default)]
+    pub stderr: StdioConfig,
+}
+
+/// # Preopened Directory
+/// 
+/// A host directory made visible to the module under a guest path.
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
+pub struct PreopenConfig {
+    /// Directory on the host
+    pub host: String,
+    /// Path under which the module sees the directory
+    pub guest: String,
+    /// Denies creating, modifying, renaming and removing files in the directory
+    #[serde(default)]
+    pub read_only: bool,
+}
+
+/// # Stdio Configuration
+/// 
+/// Where a standard stream of the module is connected.
+#[derive(Debug, Deserialize, Serialize, Clone, Default, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum StdioConfig {
+    /// Use the host process's stream
+    #[default]
+    Inherit,
+    /// Discard output, provide no input
+    Null,
+    /// Collect output in a buffer readable through `WasmInstanceCache::take_captured_output`
+    Capture,
+    /// Append output to a file, or read input from a file
+    File(String),
 }
 
 impl Default for DlinkWMConfig {
-    /// Creates a default configuration with empty entry functions mapping.
+    /// Creates a default configuration with empty entry functions, permissions and module
+    /// mappings, default engine settings and an in-memory module cache.
     fn default() -> Self {
         Self {
             entry_functions: std::collections::HashMap::new(),
+            host_permissions: std::collections::HashMap::new(),
+            modules: std::collections::HashMap::new(),
+            engine: EngineConfig::default(),
+            module_cache: ModuleCacheConfig::default(),
+            hot_reload: HotReloadConfig::default(),
         }
     }
 }
@@ -87,6 +447,75 @@ impl DlinkWMConfig {
         file.write_all(toml_str.as_bytes())?;
         Ok(())
     }
+
+    /// Checks whether a function is configured as an entry function for a WASM file.
+    /// 
+    /// # Parameters
+    /// 
+    /// - `file_path`: Path to the WASM file containing the function
+    /// - `func_name`: Name of the exported function
+    /// 
+    /// # Returns
+    /// 
+    /// `true` if the function is listed in `entry_functions` for the file, `false` otherwise.
+    pub fn is_entry_function(&self, file_path: &str, func_name: &str) -> bool {
+        self.entry_functions
+            .get(file_path)
+            .is_some_and(|functions| functions.iter().any(|f| f == func_name))
+    }
+
+    /// Checks whether a WASM file may call a host method through `universal_invoke`.
+    /// 
+    /// # Parameters
+    /// 
+    /// - `file_path`: Path to the calling WASM file
+    /// - `method_name`: Name of the host method
+    /// 
+    /// # Returns
+    /// 
+    /// `true` if `host_permissions` is empty, or if a key matching `file_path`
+    /// lists a pattern matching `method_name`; `false` otherwise.
+    /// 
+    /// # Example
+    /// 
+    /// ```rust
+    /// use dlink_wm::config::DlinkWMConfig;
+    /// 
+    /// let config: DlinkWMConfig = toml::from_str(r#"
+    ///     [entry_functions]
+    /// 
+    ///     [host_permissions]
+    ///     "plugins/*.wasm" = ["log", "db.*"]
+    /// "#).unwrap();
+    /// 
+    /// assert!(config.is_host_method_allowed("plugins/a.wasm", "db.query"));
+    /// assert!(!config.is_host_method_allowed("plugins/a.wasm", "fs.write"));
+    /// assert!(!config.is_host_method_allowed("other/b.wasm", "log"));
+    /// ```
+    pub fn is_host_method_allowed(&self, file_path: &str, method_name: &str) -> bool {
+        if self.host_permissions.is_empty() {
+            return true;
+        }
+        self.host_permissions
+            .iter()
+            .filter(|(path_pattern, _)| glob_match(path_pattern, file_path))
+            .flat_map(|(_, methods)| methods.iter())
+            .any(|method_pattern| glob_match(method_pattern, method_name))
+    }
+
+    /// Gets the settings of a WASM file.
+    /// 
+    /// # Parameters
+    /// 
+    /// - `file_path`: Path to the WASM file
+    /// 
+    /// # Returns
+    /// 
+    /// The `[modules]` entry for the file, or an unrestricted `ModuleConfig` if the
+    /// file has no entry.
+    pub fn module_config(&self, file_path: &str) -> ModuleConfig {
+        self.modules.get(file_path).cloned().unwrap_or_default()
+    }
 }
 
 /// # Dynamic Configuration Manager
@@ -104,6 +533,8 @@ pub struct DynamicConfig {
     config_path: String,
     /// File watcher for detecting configuration changes
     watcher: Option<RecommendedWatcher>,
+    /// Subscribers to configuration change events
+    events: Arc<ReloadEvents>,
 }
 
 impl DynamicConfig {
@@ -129,6 +560,7 @@ impl DynamicConfig {
             config: Arc::new(RwLock::new(config)),
             config_path: config_path.to_string(),
             watcher: None,
+            events: Arc::new(ReloadEvents::new()),
         };
         
         Ok(dynamic_config)
@@ -138,8 +570,9 @@ impl DynamicConfig {
     /// 
     /// This function spawns a background thread that:
     /// 1. Watches the configuration file for modifications
-    /// 2. Reloads the configuration when changes are detected
+    /// 2. Reloads the configuration when its content changed
     /// 3. Updates the thread-safe configuration storage
+    /// 4. Emits a `ConfigChanged` or `ReloadFailed` event on `events()`
     /// 
     /// # Returns
     /// 
@@ -180,6 +613,9 @@ impl DynamicConfig {
         // Clone references for the watcher thread
         let config = Arc::clone(&self.config);
         let config_path = self.config_path.clone();
+        let events = Arc::clone(&self.events);
+        let mut last_hash = std::fs::read(&config_path).ok().map(|content| ModuleCache::content_hash(&content));
+        let mut failed_hash = None;
         
         // Start a thread to handle configuration change events
         thread::spawn(move || {
@@ -190,20 +626,8 @@ impl DynamicConfig {
                             Ok(event) => {
                                 // Only handle file modification events
                                 if let EventKind::Modify(_) = event.kind {
-                                    log::info!("[Config] Detected config file change, reloading...");
-                                    
                                     // Reload the configuration
-                                    match DlinkWMConfig::load_from_file(&config_path) {
-                                        Ok(new_config) => {
-                                            let mut current_config = config.write().unwrap();
-                                            *current_config = new_config;
-                                            log::info!("[Config] Config reloaded successfully");
-                                            log::debug!("[Config] New entry functions: {:?}", current_config.entry_functions);
-                                        }
-                                        Err(e) => {
-                                            log::error!("[Config] Failed to reload config: {}", e);
-                                        }
-                                    }
+                                    reload_config(&config, &config_path, &mut last_hash, &mut failed_hash, &events);
                                 }
                             }
                             Err(e) => {
@@ -225,6 +649,22 @@ impl DynamicConfig {
         Ok(())
     }
 
+    /// Gets the event bus on which configuration changes are emitted.
+    pub fn events(&self) -> Arc<ReloadEvents> {
+        Arc::clone(&self.events)
+    }
+
+    /// Replaces the event bus, e.g. with `WasmInstanceCache::events` to receive
+    /// module and configuration events on one subscription. Call it before
+    /// `start_watching`.
+    /// 
+    /// # Parameters
+    /// 
+    /// - `events`: Event bus to emit configuration changes on
+    pub fn set_events(&mut self, events: Arc<ReloadEvents>) {
+        self.events = events;
+    }
+
     /// Gets a reference to the current thread-safe configuration.
     /// 
     /// This returns an `Arc<RwLock<DlinkWMConfig>>` which allows multiple threads to
@@ -263,6 +703,60 @@ impl DynamicConfig {
     }
 }
 
+/// Reloads the configuration file if its content changed since `last_hash`, and
+/// emits the outcome on `events`. Content that already failed to load, whose hash
+/// is `failed_hash`, is skipped.
+fn reload_config(
+    config: &RwLock<DlinkWMConfig>,
+    config_path: &str,
+    last_hash: &mut Option<String>,
+    failed_hash: &mut Option<String>,
+    events: &ReloadEvents,
+) {
+    let mut timer = ReloadTimer::start();
+    let mut event = ReloadEvent {
+        kind: ReloadEventKind::ConfigChanged,
+        path: config_path.to_string(),
+        old_hash: last_hash.clone(),
+        new_hash: None,
+        timings: timer.finish(),
+    };
+
+    let result = std::fs::read(config_path).map_err(DlinkError::from).and_then(|content| {
+        let hash = ModuleCache::content_hash(&content);
+        event.new_hash = Some(hash.clone());
+        if last_hash.as_deref() == Some(hash.as_str()) || failed_hash.as_deref() == Some(hash.as_str()) {
+            return Ok(None);
+        }
+        let text = String::from_utf8(content).map_err(|e| DlinkError::Config(e.to_string()))?;
+        let new_config: DlinkWMConfig = toml::from_str(&text)?;
+        Ok(Some((hash, new_config)))
+    });
+    timer.compiled();
+
+    match result {
+        Ok(None) => {
+            log::debug!("[Config] Config file content unchanged");
+            return;
+        },
+        Ok(Some((hash, new_config))) => {
+            log::info!("[Config] Detected config file change, reloading...");
+            let mut current_config = config.write().unwrap();
+            *current_config = new_config;
+            *last_hash = Some(hash);
+            log::info!("[Config] Config reloaded successfully");
+            log::debug!("[Config] New entry functions: {:?}", current_config.entry_functions);
+        },
+        Err(e) => {
+            log::error!("[Config] Failed to reload config: {}", e);
+            failed_hash.clone_from(&event.new_hash);
+            event.kind = ReloadEventKind::ReloadFailed { error: e.to_string() };
+        },
+    }
+    event.timings = timer.finish();
+    events.emit(event);
+}
+
 /// Gets the default configuration file path.
 /// 
 /// # Returns
@@ -0,0 +1,290 @@
+//! # Error Handling
+//!
+//! This module defines `DlinkError`, the error type returned by all host-side APIs,
+//! and the status codes that `universal_invoke` returns to WASM modules.
+//!
+//! ## Status Code Table
+//!
+//! The codes below are part of the host/guest ABI and will not change. Codes `1`-`99`
+//! can be returned to guests by `universal_invoke`; codes `100` and above are only
+//! reported on the host side. They are defined in the `dlink-wm-abi` crate, which
+//! guests share with the host, and re-exported here.
+//!
+//! | Code | Constant | Meaning |
+//! |------|----------|---------|
+//! | 0 | `STATUS_OK` | Success |
+//! | 1 | `STATUS_METHOD_NOT_FOUND` | No host method is registered under the requested name |
+//! | 2 | `STATUS_FORMAT_ERROR` | Unknown serialization format or data that cannot be (de)serialized |
+//! | 3 | `STATUS_EXECUTION_ERROR` | The host method handler returned an error |
+//! | 4 | `STATUS_MEMORY_NOT_FOUND` | The module does not export a `memory` |
+//! | 5 | `STATUS_INVALID_UTF8` | A string read from guest memory (e.g. the method name) is not valid UTF-8 |
+//! | 6 | `STATUS_MEMORY_ACCESS` | A pointer/length pair lies outside the guest memory |
+//! | 7 | `STATUS_PERMISSION_DENIED` | `host_permissions` does not allow the module to call the method |
+//! | 8 | `STATUS_BUFFER_TOO_SMALL` | The response does not fit the return buffer; the header holds its size |
+//! | 9 | `STATUS_NO_PENDING_RESPONSE` | `take_response` was called without a response kept by the host |
+//! | 100 | `STATUS_IO_ERROR` | File system error on the host |
+//! | 101 | `STATUS_CONFIG_ERROR` | Invalid or unreadable configuration |
+//! | 102 | `STATUS_ENTRY_NOT_ALLOWED` | Function is not a configured entry function |
+//! | 103 | `STATUS_EXPORT_NOT_FOUND` | Export is missing from the module |
+//! | 104 | `STATUS_NOT_A_FUNCTION` | Export exists but is not a function |
+//! | 105 | `STATUS_SIGNATURE_MISMATCH` | Function signature does not match the call |
+//! | 106 | `STATUS_WASM_ERROR` | Compilation, linking or instantiation failed |
+//! | 107 | `STATUS_TRAP` | The guest trapped during a call |
+//! | 108 | `STATUS_INVALID_FREE` | `host_free` on a pointer not returned by `host_malloc` |
+//! | 109 | `STATUS_DOUBLE_FREE` | `host_free` on an already freed pointer |
+//! | 110 | `STATUS_WATCHER_ERROR` | File watcher could not be created or started |
+//! | 111 | `STATUS_OUT_OF_FUEL` | The guest used up the fuel budget of the call |
+//! | 112 | `STATUS_TIMEOUT` | The guest exceeded the wall-clock budget of the call |
+//! | 113 | `STATUS_RESOURCE_LIMIT` | The guest exceeded a memory, table, instance or memory count limit |
+//! | 114 | `STATUS_INIT_FAILED` | The guest's `dlinkwm_init` export returned a failure status |
+//! | 115 | `STATUS_UNSUPPORTED_MODULE_KIND` | The operation does not support the module kind (core module or component) |
+//! | 116 | `STATUS_SOURCE_CHANGED` | The file changed since it was loaded and must be hot reloaded first |
+//!
+//! When `universal_invoke` fails after the guest memory is available, the error
+//! message is written into the return buffer (with a failure status) so the guest
+//! can log it. `universal_invoke` does not know the size of the buffer and cuts the
+//! message to 256 bytes (`LEGACY_ERROR_MESSAGE_LIMIT`), so a failed call writes at
+//! most 264 bytes; `universal_invoke_v2` cuts it to the buffer's capacity.
+
+use thiserror::Error;
+
+pub use dlink_wm_abi::status::*;
+
+/// # DlinkWM Error
+///
+/// Error type shared by the host APIs and the `universal_invoke` protocol. Every
+/// variant maps to a stable status code through `DlinkError::code`.
+///
+/// # Example
+///
+/// ```rust
+/// use dlink_wm::error::{DlinkError, STATUS_METHOD_NOT_FOUND};
+///
+/// let err = DlinkError::MethodNotFound("custom_greet".to_string());
+/// assert_eq!(err.code(), STATUS_METHOD_NOT_FOUND);
+/// assert_eq!(err.to_string(), "Host method 'custom_greet' not found");
+/// ```
+#[derive(Debug, Error)]
+pub enum DlinkError {
+    /// No host method is registered under the requested name
+    #[error("Host method '{0}' not found")]
+    MethodNotFound(String),
+    /// The guest passed an unknown serialization format identifier
+    #[error("Unknown serialization format type: {0}")]
+    InvalidFormat(i32),
+    /// Data could not be serialized or deserialized
+    #[error("Serialization error: {0}")]
+    Serialization(String),
+    /// A host method handler returned an error
+    #[error("Host method '{method}' failed: {message}")]
+    HandlerFailed {
+        /// Name of the host method
+        method: String,
+        /// Error message reported by the handler
+        message: String,
+    },
+    /// The module does not export a `memory`
+    #[error("WASM module does not export a memory")]
+    MemoryNotFound,
+    /// A string read from guest memory is not valid UTF-8
+    #[error("String in WASM memory is not valid UTF-8")]
+    InvalidUtf8,
+    /// A read or write fell outside the guest memory
+    #[error("Out of bounds access to WASM memory at {ptr:#x} (length {len})")]
+    MemoryAccess {
+        /// Guest pointer of the access
+        ptr: i32,
+        /// Length of the access in bytes
+        len: usize,
+    },
+    /// The module is not allowed to call the host method
+    #[error("WASM module '{module}' is not allowed to call host method '{method}'")]
+    PermissionDenied {
+        /// Path of the calling module
+        module: String,
+        /// Name of the denied host method
+        method: String,
+    },
+    /// The response does not fit the return buffer passed to `universal_invoke_v2`
+    #[error("Response of {required} bytes does not fit the return buffer of {capacity} bytes")]
+    BufferTooSmall {
+        /// Size of the response including its header
+        required: usize,
+        /// Capacity of the return buffer
+        capacity: usize,
+    },
+    /// `take_response` was called without a response kept by the host
+    #[error("No pending response to take")]
+    NoPendingResponse,
+    /// File system error on the host
+    #[error("I/O error: {0}")]
+    Io(#[from] std::io::Error),
+    /// Invalid or unreadable configuration
+    #[error("Configuration error: {0}")]
+    Config(String),
+    /// The function is not a configured entry function for the WASM file
+    #[error("Function '{function}' is not configured as an entry function for WASM file '{wasm_path}'. Allowed functions: {allowed:?}")]
+    EntryFunctionNotAllowed {
+        /// Name of the requested function
+        function: String,
+        /// Path of the WASM file
+        wasm_path: String,
+        /// Entry functions configured for the file
+        allowed: Vec<String>,
+    },
+    /// The export is missing from the module
+    #[error("Export '{0}' not found in WASM module")]
+    ExportNotFound(String),
+    /// The export exists but is not a function
+    #[error("Export '{0}' is not a function")]
+    NotAFunction(String),
+    /// The function signature does not match the call
+    #[error("Function '{function}' has an incompatible signature: {detail}")]
+    SignatureMismatch {
+        /// Name of the function
+        function: String,
+        /// Description of the mismatch
+        detail: String,
+    },
+    /// Compilation, linking or instantiation failed
+    #[error("WASM error: {0:#}")]
+    Wasm(#[from] anyhow::Error),
+    /// The guest trapped during a call
+    #[error("WASM trap: {0:#}")]
+    Trap(anyhow::Error),
+    /// `host_free` was called with a pointer not returned by `host_malloc`
+    #[error("Invalid free of guest pointer {0:#x}")]
+    InvalidFree(u32),
+    /// `host_free` was called with an already freed pointer
+    #[error("Double free of guest pointer {0:#x}")]
+    DoubleFree(u32),
+    /// The file watcher could not be created or started
+    #[error("File watcher error: {0}")]
+    Watcher(#[from] notify::Error),
+    /// The guest used up the fuel budget of the call
+    #[error("Function '{function}' ran out of fuel (budget {fuel})")]
+    OutOfFuel {
+        /// Name of the function
+        function: String,
+        /// Fuel budget of the call
+        fuel: u64,
+    },
+    /// The guest exceeded the wall-clock budget of the call
+    #[error("Function '{function}' timed out after {timeout_ms} ms")]
+    Timeout {
+        /// Name of the function
+        function: String,
+        /// Wall-clock budget of the call in milliseconds
+        timeout_ms: u64,
+    },
+    /// The guest exceeded a memory, table, instance or memory count limit
+    #[error("WASM module '{module}' exceeded a resource limit: {detail}")]
+    ResourceLimitExceeded {
+        /// Path of the module
+        module: String,
+        /// Description of the limit and of the failure it caused
+        detail: String,
+    },
+    /// The guest's `dlinkwm_init` export refused to start
+    #[error("WASM module '{module}' failed to initialize with status {status}")]
+    InitFailed {
+        /// Path of the module
+        module: String,
+        /// Status returned by `dlinkwm_init`
+        status: i32,
+    },
+
+    /// The operation does not support the kind of the WASM file
+    #[error("WASM module '{wasm_path}' is not supported here: {detail}")]
+    UnsupportedModuleKind {
+        /// Path of the WASM file
+        wasm_path: String,
+        /// Why the module kind is not supported
+        detail: String,
+    },
+
+    /// A fresh instance was requested for a file that changed since it was loaded
+    #[error("WASM file '{wasm_path}' changed since it was loaded; hot reload it before asking for a fresh instance")]
+    SourceChanged {
+        /// Path of the WASM file
+        wasm_path: String,
+    },
+}
+
+impl DlinkError {
+    /// Returns the stable status code for this error.
+    ///
+    /// See the module documentation for the full code table.
+    pub fn code(&self) -> i32 {
+        match self {
+            DlinkError::MethodNotFound(_) => STATUS_METHOD_NOT_FOUND,
+            DlinkError::InvalidFormat(_) | DlinkError::Serialization(_) => STATUS_FORMAT_ERROR,
+            DlinkError::HandlerFailed { .. } => STATUS_EXECUTION_ERROR,
+            DlinkError::MemoryNotFound => STATUS_MEMORY_NOT_FOUND,
+            DlinkError::InvalidUtf8 => STATUS_INVALID_UTF8,
+            DlinkError::MemoryAccess { .. } => STATUS_MEMORY_ACCESS,
+            DlinkError::PermissionDenied { .. } => STATUS_PERMISSION_DENIED,
+            DlinkError::BufferTooSmall { .. } => STATUS_BUFFER_TOO_SMALL,
+            DlinkError::NoPendingResponse => STATUS_NO_PENDING_RESPONSE,
+            DlinkError::Io(_) => STATUS_IO_ERROR,
+            DlinkError::Config(_) => STATUS_CONFIG_ERROR,
+            DlinkError::EntryFunctionNotAllowed { .. } => STATUS_ENTRY_NOT_ALLOWED,
+            DlinkError::ExportNotFound(_) => STATUS_EXPORT_NOT_FOUND,
+            DlinkError::NotAFunction(_) => STATUS_NOT_A_FUNCTION,
+            DlinkError::SignatureMismatch { .. } => STATUS_SIGNATURE_MISMATCH,
+            DlinkError::Wasm(_) => STATUS_WASM_ERROR,
+            DlinkError::Trap(_) => STATUS_TRAP,
+            DlinkError::InvalidFree(_) => STATUS_INVALID_FREE,
+            DlinkError::DoubleFree(_) => STATUS_DOUBLE_FREE,
+            DlinkError::Watcher(_) => STATUS_WATCHER_ERROR,
+            DlinkError::OutOfFuel { .. } => STATUS_OUT_OF_FUEL,
+            DlinkError::Timeout { .. } => STATUS_TIMEOUT,
+            DlinkError::ResourceLimitExceeded { .. } => STATUS_RESOURCE_LIMIT,
+            DlinkError::InitFailed { .. } => STATUS_INIT_FAILED,
+            DlinkError::UnsupportedModuleKind { .. } => STATUS_UNSUPPORTED_MODULE_KIND,
+            DlinkError::SourceChanged { .. } => STATUS_SOURCE_CHANGED,
+        }
+    }
+
+    /// Checks whether the error means the guest trapped during a call, including
+    /// the traps raised when it exceeds its fuel, time or resource limits.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use dlink_wm::error::DlinkError;
+    ///
+    /// assert!(DlinkError::Timeout { function: "run".to_string(), timeout_ms: 100 }.is_trap());
+    /// assert!(!DlinkError::ExportNotFound("run".to_string()).is_trap());
+    /// ```
+    pub fn is_trap(&self) -> bool {
+        matches!(
+            self,
+            DlinkError::Trap(_)
+                | DlinkError::OutOfFuel { .. }
+                | DlinkError::Timeout { .. }
+                | DlinkError::ResourceLimitExceeded { .. }
+        )
+    }
+}
+
+impl From<serde_json::Error> for DlinkError {
+    fn from(e: serde_json::Error) -> Self {
+        DlinkError::Serialization(e.to_string())
+    }
+}
+
+impl From<toml::de::Error> for DlinkError {
+    fn from(e: toml::de::Error) -> Self {
+        DlinkError::Config(e.to_string())
+    }
+}
+
+impl From<toml::ser::Error> for DlinkError {
+    fn from(e: toml::ser::Error) -> Self {
+        DlinkError::Config(e.to_string())
+    }
+}
+
+/// Result type used by all host-side DlinkWM APIs.
+pub type Result<T> = std::result::Result<T, DlinkError>;
@@ -0,0 +1,239 @@
+//! # Reload Events
+//!
+//! This module lets applications learn about hot reloads. A `ReloadEvents` bus is
+//! owned by each `WasmInstanceCache` and `DynamicConfig`; pass the cache's bus to
+//! `DynamicConfig::set_events` to receive the events of both on one subscription.
+//! A `HealthMonitor` reports failed health checks on the bus of its cache.
+//!
+//! Subscribers either receive events on a channel (`subscribe`) or have a callback
+//! invoked (`on_event`). Callbacks run on the thread that performed the reload,
+//! after its locks are released, so they should return quickly; long work belongs
+//! behind a channel.
+
+use std::fmt;
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant, SystemTime};
+
+/// # Reload Event Kind
+///
+/// What happened to the file of a `ReloadEvent`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ReloadEventKind {
+    /// A new version of a WASM file was published
+    Reloaded,
+    /// A WASM file or the configuration could not be reloaded; the previous
+    /// version stays in use
+    ReloadFailed {
+        /// Description of the failure
+        error: String,
+    },
+    /// A reloaded WASM file trapped during its probation calls and its previous
+    /// version was restored
+    RolledBack {
+        /// The trap that caused the rollback
+        error: String,
+    },
+    /// A loaded WASM file was removed from disk; its loaded version stays in use
+    Removed,
+    /// The configuration file changed and was reloaded
+    ConfigChanged,
+    /// A `HealthMonitor` check of a loaded WASM file failed; the file stays in use
+    Unhealthy {
+        /// The status reported by the guest, or the error of the check
+        error: String,
+    },
+}
+
+/// # Reload Timings
+///
+/// Duration of the phases of a reload. Phases an event does not go through are zero.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ReloadTimings {
+    /// When the reload started
+    pub started_at: SystemTime,
+    /// Time spent reading and compiling the new content (parsing, for the
+    /// configuration)
+    pub compile: Duration,
+    /// Time spent instantiating the new version
+    pub instantiate: Duration,
+    /// Time from the start of the reload until it was published or failed
+    pub total: Duration,
+}
+
+/// # Reload Event
+///
+/// A change of a WASM file or of the configuration file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ReloadEvent {
+    /// What happened
+    pub kind: ReloadEventKind,
+    /// Path of the WASM file or configuration file, as it was loaded
+    pub path: String,
+    /// SHA-256 of the content in use before the event, if there was one
+    pub old_hash: Option<String>,
+    /// SHA-256 of the new content, if it could be read; for `RolledBack`, the
+    /// content that was restored
+    pub new_hash: Option<String>,
+    /// Duration of the reload
+    pub timings: ReloadTimings,
+}
+
+/// Identifies a callback registered with `ReloadEvents::on_event`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct SubscriptionId(u64);
+
+/// Callback invoked for every event.
+type ReloadCallback = Arc<dyn Fn(&ReloadEvent) + Send + Sync>;
+
+/// Subscribers of a bus, guarded by its mutex.
+#[derive(Default)]
+struct Subscribers {
+    /// Id of the next callback
+    next_id: u64,
+    /// Channels of `subscribe`; dropped receivers are removed on the next event
+    channels: Vec<Sender<ReloadEvent>>,
+    /// Callbacks of `on_event`
+    callbacks: Vec<(SubscriptionId, ReloadCallback)>,
+}
+
+/// # Reload Event Bus
+///
+/// Delivers `ReloadEvent`s to channels and callbacks.
+///
+/// # Example
+///
+/// ```rust
+/// use dlink_wm::events::ReloadEventKind;
+/// use dlink_wm::module_cache::ModuleCache;
+/// use dlink_wm::wasm_manager::WasmInstanceCache;
+///
+/// let wasm_path = std::env::temp_dir().join("dlinkwm_events_doctest.wasm");
+/// std::fs::write(&wasm_path, "(module)").unwrap();
+/// let wasm_path = wasm_path.to_str().unwrap();
+/// let cache = WasmInstanceCache::new();
+/// cache.load_and_instantiate(wasm_path).unwrap();
+/// let events = cache.events().subscribe();
+///
+/// std::fs::write(wasm_path, r#"(module (func (export "run")))"#).unwrap();
+/// cache.hot_reload(wasm_path).unwrap();
+/// let event = events.try_recv().unwrap();
+/// assert_eq!(event.kind, ReloadEventKind::Reloaded);
+/// assert_eq!(event.old_hash, Some(ModuleCache::content_hash(b"(module)")));
+/// assert_eq!(event.new_hash, cache.loaded_hash(wasm_path));
+///
+/// std::fs::write(wasm_path, "(module (func").unwrap();
+/// assert!(cache.hot_reload(wasm_path).is_err());
+/// assert!(matches!(events.try_recv().unwrap().kind, ReloadEventKind::ReloadFailed { .. }));
+/// ```
+#[derive(Default)]
+pub struct ReloadEvents {
+    subscribers: Mutex<Subscribers>,
+}
+
+impl fmt::Debug for ReloadEvents {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let subscribers = self.subscribers.lock().unwrap();
+        f.debug_struct("ReloadEvents")
+            .field("channels", &subscribers.channels.len())
+            .field("callbacks", &subscribers.callbacks.len())
+            .finish()
+    }
+}
+
+impl ReloadEvents {
+    /// Creates a bus without subscribers.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Subscribes a channel to the events. Dropping the receiver unsubscribes it.
+    pub fn subscribe(&self) -> Receiver<ReloadEvent> {
+        let (tx, rx) = mpsc::channel();
+        self.subscribers.lock().unwrap().channels.push(tx);
+        rx
+    }
+
+    /// Registers a callback invoked for every event.
+    ///
+    /// # Returns
+    ///
+    /// The id to pass to `unsubscribe`.
+    pub fn on_event(&self, callback: impl Fn(&ReloadEvent) + Send + Sync + 'static) -> SubscriptionId {
+        let mut subscribers = self.subscribers.lock().unwrap();
+        let id = SubscriptionId(subscribers.next_id);
+        subscribers.next_id += 1;
+        subscribers.callbacks.push((id, Arc::new(callback)));
+        id
+    }
+
+    /// Removes a callback registered with `on_event`.
+    ///
+    /// # Returns
+    ///
+    /// `true` if the callback was registered.
+    pub fn unsubscribe(&self, id: SubscriptionId) -> bool {
+        let mut subscribers = self.subscribers.lock().unwrap();
+        let before = subscribers.callbacks.len();
+        subscribers.callbacks.retain(|(callback_id, _)| *callback_id != id);
+        subscribers.callbacks.len() != before
+    }
+
+    /// Delivers an event to every subscriber.
+    pub(crate) fn emit(&self, event: ReloadEvent) {
+        log::debug!("[Events] {:?} {}", event.kind, event.path);
+        let callbacks: Vec<ReloadCallback> = {
+            let mut subscribers = self.subscribers.lock().unwrap();
+            subscribers.channels.retain(|tx| tx.send(event.clone()).is_ok());
+            subscribers.callbacks.iter().map(|(_, callback)| Arc::clone(callback)).collect()
+        };
+        for callback in callbacks {
+            callback(&event);
+        }
+    }
+}
+
+/// Measures the phases of a reload.
+pub(crate) struct ReloadTimer {
+    started_at: SystemTime,
+    start: Instant,
+    phase_start: Instant,
+    compile: Duration,
+    instantiate: Duration,
+}
+
+impl ReloadTimer {
+    /// Starts timing a reload.
+    pub(crate) fn start() -> Self {
+        let now = Instant::now();
+        Self {
+            started_at: SystemTime::now(),
+            start: now,
+            phase_start: now,
+            compile: Duration::ZERO,
+            instantiate: Duration::ZERO,
+        }
+    }
+
+    /// Ends the compile phase.
+    pub(crate) fn compiled(&mut self) {
+        self.compile = self.phase_start.elapsed();
+        self.phase_start = Instant::now();
+    }
+
+    /// Ends the instantiate phase.
+    pub(crate) fn instantiated(&mut self) {
+        self.instantiate = self.phase_start.elapsed();
+        self.phase_start = Instant::now();
+    }
+
+    /// Gets the timings of the phases so far.
+    pub(crate) fn finish(&self) -> ReloadTimings {
+        ReloadTimings {
+            started_at: self.started_at,
+            compile: self.compile,
+            instantiate: self.instantiate,
+            total: self.start.elapsed(),
+        }
+    }
+}
@@ -3,24 +3,45 @@
 //! This module defines the host functions that are exposed to WASM modules.
 //! It provides a universal invocation interface that allows WASM modules to call
 //! custom host methods dynamically, along with memory management functions.
+//! Components reach the same host methods through the `invoke` function of the
+//! `dlinkwm:host` WIT interface (see the `component` module).
 
-use wasmtime::{Caller, Store, Linker, Engine};
-use wasmtime_wasi::WasiCtx;
-use wasmtime_wasi::WasiCtxBuilder;
+use wasmtime::{AsContextMut, Caller, Store, StoreContextMut, Linker, Engine, Memory, OptLevel};
+use wasmtime::{InstanceAllocationStrategy, PoolingAllocationConfig};
+use wasmtime_wasi::preview2::{self, Table, WasiView};
+use wasmtime_wasi::{WasiCtx, WasiCtxBuilder};
+use crate::allocator::{AllocatorStats, GuestAllocator, WASM_PAGE_SIZE};
+use crate::codec::{Decode, Encode};
+use crate::config::{DlinkWMConfig, EngineConfig, OptLevelConfig, WasiConfig};
+use crate::limits::{check_call_deadline, check_call_deadline_async, ExecutionBudget, ModuleLimiter};
+use crate::wasi::{build_preview2_ctx, build_wasi_ctx, CaptureBuffers, CapturedOutput};
+use crate::registry::{HostMethod, HostMethodDef, HostMethodRegistry};
+use crate::error::{DlinkError, Result, STATUS_OK};
+use dlink_wm_abi::{
+    format, ResponseHeader, HOST_FREE, HOST_MALLOC, IMPORT_MODULE, LEGACY_ERROR_MESSAGE_LIMIT, RESPONSE_HEADER_SIZE,
+    TAKE_RESPONSE, UNIVERSAL_INVOKE, UNIVERSAL_INVOKE_V2,
+};
 use crate::utils::{read_wasm_memory, write_wasm_memory};
-use std::sync::{Arc, RwLock, LazyLock};
-use anyhow::{Result as AnyResult, Result};
+use std::sync::{Arc, RwLock};
+use anyhow::Result as AnyResult;
+use std::any::{Any, TypeId};
 use std::collections::HashMap;
+use std::future::Future;
+use std::pin::Pin;
+use std::time::Instant;
 
 // -------------------------- Universal Invocation Interface --------------------------
 
 /// # Method Handler Type
 /// 
-/// Type alias for host method handlers. These functions receive serialized parameters
-/// and return a serialized response along with a success status.
+/// Type alias for host method handlers. Handlers are shared closures, so they can
+/// capture state such as connection pools, configuration handles or counters.
+/// They receive the calling instance's context and serialized parameters, and
+/// return a serialized response along with a success status.
 /// 
 /// # Parameters
 /// 
+/// - `&mut HostContext`: Context of the calling instance (module path, memory, store data)
 /// - `Vec<u8>`: Serialized parameters in the specified format
 /// - `SerializationFormat`: Format used for serialization
 /// 
@@ -29,7 +50,107 @@ use std::collections::HashMap;
 /// A tuple containing:
 /// - `bool`: Success status (true for success, false for error)
 /// - `Vec<u8>`: Serialized response bytes
-pub type MethodHandler = fn(Vec<u8>, SerializationFormat) -> AnyResult<(bool, Vec<u8>)>;
+pub type MethodHandler =
+    Arc<dyn Fn(&mut HostContext<'_>, Vec<u8>, SerializationFormat) -> AnyResult<(bool, Vec<u8>)> + Send + Sync>;
+
+/// # Host Future
+/// 
+/// Future returned by async host method handlers, resolving to the same success
+/// status and serialized response as a `MethodHandler`.
+pub type HostFuture = Pin<Box<dyn Future<Output = AnyResult<(bool, Vec<u8>)>> + Send>>;
+
+/// # Async Method Handler Type
+/// 
+/// Type alias for async host method handlers. The guest is suspended while the
+/// returned future runs, so handlers can await I/O without blocking the executor.
+/// They can only be called by modules of an `AsyncWasmInstanceCache`.
+/// 
+/// # Parameters
+/// 
+/// - `AsyncHostContext`: Context of the calling instance (module path)
+/// - `Vec<u8>`: Serialized parameters in the specified format
+/// - `SerializationFormat`: Format used for serialization
+pub type AsyncMethodHandler =
+    Arc<dyn Fn(AsyncHostContext, Vec<u8>, SerializationFormat) -> HostFuture + Send + Sync>;
+
+/// # Async Host Context
+/// 
+/// Context handed to async host method handlers. Unlike `HostContext` it does not
+/// borrow the store, so the handler's future can be held across await points.
+#[derive(Debug, Clone)]
+pub struct AsyncHostContext {
+    /// Path of the calling module
+    module_path: String,
+}
+
+impl AsyncHostContext {
+    /// Gets the path of the WASM module that called the host method.
+    pub fn module_path(&self) -> &str {
+        &self.module_path
+    }
+}
+
+/// # Host Context
+/// 
+/// Context handed to host method handlers for the duration of one call. It gives
+/// access to the calling module's path, its linear memory and the per-store
+/// `HostState`. Components pass their parameters by value and expose no memory.
+pub struct HostContext<'a> {
+    /// Store of the calling instance
+    store: StoreContextMut<'a, HostState>,
+    /// Linear memory exported by the calling instance, `None` for components
+    memory: Option<Memory>,
+}
+
+impl<'a> HostContext<'a> {
+    /// Gets the path of the WASM module that called the host method.
+    pub fn module_path(&self) -> &str {
+        self.store.data().module_path()
+    }
+
+    /// Gets the linear memory exported by the calling instance, or `None` if the
+    /// caller is a component.
+    pub fn memory(&self) -> Option<Memory> {
+        self.memory
+    }
+
+    /// Reads bytes from the calling instance's memory.
+    /// 
+    /// # Errors
+    /// 
+    /// Returns `DlinkError::MemoryAccess` if the range lies outside the guest memory,
+    /// or `DlinkError::MemoryNotFound` if the caller is a component.
+    pub fn read_memory(&self, ptr: i32, len: i32) -> Result<Vec<u8>> {
+        let memory = self.memory.ok_or(DlinkError::MemoryNotFound)?;
+        read_wasm_memory(&memory, &self.store, ptr, len)
+    }
+
+    /// Writes bytes to the calling instance's memory.
+    /// 
+    /// # Errors
+    /// 
+    /// Returns `DlinkError::MemoryAccess` if the range lies outside the guest memory,
+    /// or `DlinkError::MemoryNotFound` if the caller is a component.
+    pub fn write_memory(&mut self, ptr: i32, data: &[u8]) -> Result<()> {
+        let memory = self.memory.ok_or(DlinkError::MemoryNotFound)?;
+        write_wasm_memory(&memory, &mut self.store, ptr, data)
+    }
+
+    /// Gets the per-store data of the calling instance.
+    pub fn data(&self) -> &HostState {
+        self.store.data()
+    }
+
+    /// Gets mutable access to the per-store data of the calling instance.
+    pub fn data_mut(&mut self) -> &mut HostState {
+        self.store.data_mut()
+    }
+
+    /// Gets the store context of the calling instance, for direct wasmtime access.
+    pub fn store(&mut self) -> &mut StoreContextMut<'a, HostState> {
+        &mut self.store
+    }
+}
 
 /// # Serialization Format
 /// 
@@ -43,29 +164,49 @@ pub enum SerializationFormat {
     Bincode,
     /// Protocol Buffers serialization format
     Protobuf,
-    /// FlatBuffers serialization format
+    /// FlatBuffers format, encoded as schema-less FlexBuffers (FlatBuffers tables are not supported)
     FlatBuffers,
 }
 
-/// # Host Method Registry
-/// 
-/// Global registry that stores all host functions available to WASM modules.
-/// This registry is thread-safe and can be modified at runtime.
-static HOST_METHOD_REGISTRY: LazyLock<Arc<RwLock<HashMap<String, MethodHandler>>>> = LazyLock::new(|| {
-    Arc::new(RwLock::new(HashMap::new()))
-});
+impl SerializationFormat {
+    /// Converts the `format_type` identifier passed by guests into a format.
+    /// 
+    /// # Errors
+    /// 
+    /// Returns `DlinkError::InvalidFormat` for unknown identifiers.
+    pub fn from_format_type(format_type: i32) -> Result<Self> {
+        match format_type {
+            format::JSON => Ok(SerializationFormat::Json),
+            format::BINCODE => Ok(SerializationFormat::Bincode),
+            format::PROTOBUF => Ok(SerializationFormat::Protobuf),
+            format::FLATBUFFERS => Ok(SerializationFormat::FlatBuffers),
+            _ => Err(DlinkError::InvalidFormat(format_type)),
+        }
+    }
+
+    /// Returns the `format_type` identifier guests use for this format.
+    pub fn format_type(self) -> i32 {
+        match self {
+            SerializationFormat::Json => format::JSON,
+            SerializationFormat::Bincode => format::BINCODE,
+            SerializationFormat::Protobuf => format::PROTOBUF,
+            SerializationFormat::FlatBuffers => format::FLATBUFFERS,
+        }
+    }
+}
 
 /// # Register a Host Method Dynamically
 /// 
-/// Registers a new host method that can be called by WASM modules using the
-/// universal invocation interface.
+/// Registers a new host method in the global registry
+/// (`HostMethodRegistry::global()`), which every `WasmInstanceCache` inherits
+/// from by default. WASM modules call it using the universal invocation interface.
 /// 
 /// # Parameters
 /// 
 /// - `method_name`: Name of the method to register. This is the name WASM modules
 ///   will use to call this method.
-/// - `handler`: Function pointer to the handler that will be called when the
-///   method is invoked from WASM.
+/// - `handler`: Shared handler that will be called when the method is invoked
+///   from WASM. Plain functions and capturing closures both work.
 /// 
 /// # Returns
 /// 
@@ -75,10 +216,12 @@ static HOST_METHOD_REGISTRY: LazyLock<Arc<RwLock<HashMap<String, MethodHandler>>
 /// # Example
 /// 
 /// ```rust
-/// use dlink_wm::host_import::{register_host_method, SerializationFormat};
+/// use dlink_wm::host_import::{register_host_method, HostContext, SerializationFormat};
 /// use anyhow::{anyhow, Result as AnyResult};
+/// use std::sync::Arc;
+/// use std::sync::atomic::{AtomicU64, Ordering};
 /// 
-/// fn custom_greet_handler(params: Vec<u8>, format: SerializationFormat) -> AnyResult<(bool, Vec<u8>)> {
+/// fn custom_greet_handler(_ctx: &mut HostContext<'_>, params: Vec<u8>, format: SerializationFormat) -> AnyResult<(bool, Vec<u8>)> {
 ///     match format {
 ///         SerializationFormat::Json => {
 ///             // Handle JSON parameters...
@@ -88,17 +231,142 @@ static HOST_METHOD_REGISTRY: LazyLock<Arc<RwLock<HashMap<String, MethodHandler>>
 ///     }
 /// }
 /// 
-/// // Register the method
-/// register_host_method("custom_greet", custom_greet_handler);
+/// // Register a plain function
+/// register_host_method("custom_greet", Arc::new(custom_greet_handler));
+/// 
+/// // Register a closure that captures shared state
+/// let calls = Arc::new(AtomicU64::new(0));
+/// let counter = calls.clone();
+/// register_host_method("count_calls", Arc::new(move |ctx: &mut HostContext<'_>, _params: Vec<u8>, _format: SerializationFormat| {
+///     let total = counter.fetch_add(1, Ordering::Relaxed) + 1;
+///     log::info!("{} called count_calls ({} calls so far)", ctx.module_path(), total);
+///     Ok((true, total.to_le_bytes().to_vec()))
+/// }));
 /// ```
 pub fn register_host_method(method_name: &str, handler: MethodHandler) -> bool {
-    let mut registry = HOST_METHOD_REGISTRY.write().unwrap();
-    registry.insert(method_name.to_string(), handler).is_none()
+    HostMethodRegistry::global().register(method_name, handler)
+}
+
+/// # Register a Typed Host Method
+/// 
+/// Registers a host method in the global registry whose parameters and return
+/// value are decoded and encoded automatically, using the `format_type` the guest
+/// passed to `universal_invoke`.
+/// 
+/// # Parameters
+/// 
+/// - `method_name`: Name of the method to register.
+/// - `handler`: Function or closure receiving the decoded parameters and returning
+///   the value to encode for the guest.
+/// 
+/// # Returns
+/// 
+/// `true` if the method was registered successfully, `false` if the method name
+/// is already registered.
+/// 
+/// # Errors Reported to the Guest
+/// 
+/// - Parameters that cannot be decoded, or a result that cannot be encoded, yield
+///   a format error (status `2`).
+/// - An `Err` returned by `handler` yields an execution error (status `3`).
+/// 
+/// # Example
+/// 
+/// ```rust
+/// use dlink_wm::codec::JsonParams;
+/// use dlink_wm::host_import::register_typed_host_method;
+/// use serde::Deserialize;
+/// 
+/// #[derive(Deserialize)]
+/// struct GreetParams {
+///     name: String,
+/// }
+/// 
+/// register_typed_host_method("typed_greet", |params: JsonParams<GreetParams>| {
+///     Ok(format!("Hello, {}!", params.data.name))
+/// });
+/// ```
+pub fn register_typed_host_method<P, R, F>(method_name: &str, handler: F) -> bool
+where
+    P: Decode,
+    R: Encode,
+    F: Fn(P) -> AnyResult<R> + Send + Sync + 'static,
+{
+    HostMethodRegistry::global().register_typed(method_name, handler)
+}
+
+/// # Register a Defined Host Method
+/// 
+/// Registers a host method defined with `#[dlink_host_method]` in the global
+/// registry, under the name in its schema. See `HostMethodRegistry::register_method`.
+/// 
+/// # Returns
+/// 
+/// `true` if the method was registered successfully, `false` if the method name
+/// is already registered.
+/// 
+/// # Example
+/// 
+/// ```rust
+/// use dlink_wm::dlink_host_method;
+/// use dlink_wm::host_import::register_host_method_def;
+/// use dlink_wm::registry::HostMethodRegistry;
+/// use serde::Deserialize;
+/// 
+/// #[derive(Deserialize)]
+/// struct GreetParams {
+///     name: String,
+/// }
+/// 
+/// #[dlink_host_method(name = "defined_greet")]
+/// fn greet(params: GreetParams) -> anyhow::Result<String> {
+///     Ok(format!("Hello, {}!", params.name))
+/// }
+/// 
+/// register_host_method_def::<greet>();
+/// assert!(HostMethodRegistry::global().contains("defined_greet"));
+/// ```
+pub fn register_host_method_def<M: HostMethodDef>() -> bool {
+    HostMethodRegistry::global().register_method::<M>()
+}
+
+/// # Register an Async Host Method
+/// 
+/// Registers an async host method in the global registry. Modules loaded by an
+/// `AsyncWasmInstanceCache` are suspended while the handler's future runs; calls
+/// from a synchronous `WasmInstanceCache` fail with an execution error (status `3`).
+/// 
+/// # Parameters
+/// 
+/// - `method_name`: Name of the method to register.
+/// - `handler`: Shared handler returning the future of the response.
+/// 
+/// # Returns
+/// 
+/// `true` if the method was registered successfully, `false` if the method name
+/// is already registered.
+/// 
+/// # Example
+/// 
+/// ```rust
+/// use dlink_wm::host_import::{register_async_host_method, AsyncHostContext, SerializationFormat};
+/// use std::sync::Arc;
+/// use std::time::Duration;
+/// 
+/// register_async_host_method("slow_ping", Arc::new(|_ctx: AsyncHostContext, _params: Vec<u8>, _format: SerializationFormat| {
+///     Box::pin(async move {
+///         tokio::time::sleep(Duration::from_millis(10)).await;
+///         Ok((true, b"pong".to_vec()))
+///     })
+/// }));
+/// ```
+pub fn register_async_host_method(method_name: &str, handler: AsyncMethodHandler) -> bool {
+    HostMethodRegistry::global().register_async(method_name, handler)
 }
 
 /// # Unregister a Host Method
 /// 
-/// Removes a previously registered host method from the registry.
+/// Removes a previously registered host method from the global registry.
 /// 
 /// # Parameters
 /// 
@@ -109,13 +377,12 @@ pub fn register_host_method(method_name: &str, handler: MethodHandler) -> bool {
 /// `true` if the method was unregistered successfully, `false` if the method
 /// was not found in the registry.
 pub fn unregister_host_method(method_name: &str) -> bool {
-    let mut registry = HOST_METHOD_REGISTRY.write().unwrap();
-    registry.remove(method_name).is_some()
+    HostMethodRegistry::global().unregister(method_name)
 }
 
 /// # Check if a Host Method Exists
 /// 
-/// Verifies if a host method with the given name is registered.
+/// Verifies if a host method with the given name is registered in the global registry.
 /// 
 /// # Parameters
 /// 
@@ -125,17 +392,18 @@ pub fn unregister_host_method(method_name: &str) -> bool {
 /// 
 /// `true` if the method exists in the registry, `false` otherwise.
 pub fn has_host_method(method_name: &str) -> bool {
-    let registry = HOST_METHOD_REGISTRY.read().unwrap();
-    registry.contains_key(method_name)
+    HostMethodRegistry::global().contains(method_name)
 }
 
 /// # Universal Invocation Function
 /// 
 /// Universal interface for WASM modules to call host methods. All host method
-/// calls from WASM go through this function.
+/// calls from WASM go through this function. Methods are resolved in the registry
+/// the linker was created with (see `create_dlinkwm_linker`).
 /// 
 /// # Parameters
 /// 
+/// - `registry`: Registry used to resolve the method name
 /// - `caller`: WASM caller context
 /// - `method_name_ptr`: Pointer to the method name in WASM memory
 /// - `method_name_len`: Length of the method name in bytes
@@ -146,21 +414,68 @@ pub fn has_host_method(method_name: &str) -> bool {
 /// 
 /// # Returns
 /// 
-/// Status code:
+/// A status code from the table in the `error` module, e.g.:
 /// - `0`: Success
 /// - `1`: Method not found
 /// - `2`: Format error
 /// - `3`: Execution error
+/// - `4`: Memory not found
+/// - `5`: Invalid UTF-8 method name
+/// - `6`: Out of bounds memory access
+/// - `7`: Permission denied by `host_permissions`
 /// 
 /// # Response Format
 /// 
 /// The response is written to the memory location specified by `ret_ptr` in the following format:
-/// - `0-3 bytes`: Status code (0 for success, 1 for failure)
+/// - `0-3 bytes`: Status code (1 for success, 0 for failure)
 /// - `4-7 bytes`: Response data length
 /// - `8+ bytes`: Response data
+/// 
+/// On failure the response data is the UTF-8 error message, so guests can log it,
+/// cut to `LEGACY_ERROR_MESSAGE_LIMIT` (256) bytes. Nothing is written when the
+/// module exports no memory.
+/// 
+/// The host does not know the size of the buffer at `ret_ptr` and writes the whole
+/// response of a host method, so this import is only kept for existing guests. New
+/// guests call `universal_invoke_v2`, which never writes past the buffer.
+/// 
+/// # Example
+/// 
+/// ```rust
+/// use dlink_wm::config::DlinkWMConfig;
+/// use dlink_wm::wasm_manager::WasmInstanceCache;
+/// use std::sync::{Arc, RwLock};
+/// 
+/// // A legacy guest calling a missing method with a 600 byte name, response buffer at 1024
+/// let wasm_path = std::env::temp_dir().join("dlinkwm_invoke_v1_doctest.wasm");
+/// std::fs::write(&wasm_path, format!(r#"(module
+///     (import "dlinkwm_host" "universal_invoke" (func $invoke (param i32 i32 i32 i32 i32 i32) (result i32)))
+///     (memory (export "memory") 1)
+///     (data (i32.const 0) "{}")
+///     (func (export "call") (result i32)
+///         (call $invoke (i32.const 0) (i32.const 600) (i32.const 0) (i32.const 0) (i32.const 0) (i32.const 1024))))"#,
+///     "a".repeat(600))).unwrap();
+/// let wasm_path = wasm_path.to_str().unwrap();
+/// 
+/// let mut config = DlinkWMConfig::default();
+/// config.entry_functions.insert(wasm_path.to_string(), vec!["call".to_string()]);
+/// let cache = WasmInstanceCache::with_config(Arc::new(RwLock::new(config)));
+/// assert_eq!(cache.call::<(), i32>(wasm_path, "call", ()).unwrap(), 1);
+/// 
+/// // The message is cut to 256 bytes, and nothing is written after it
+/// let memory = cache.with_instance(wasm_path, false, |instance, store| {
+///     let memory = instance.get_memory(&mut *store, "memory").unwrap();
+///     Ok(memory.data(&*store)[1024..2048].to_vec())
+/// }).unwrap();
+/// assert_eq!(u32::from_le_bytes(memory[4..8].try_into().unwrap()), 256);
+/// assert!(memory[8..264].starts_with(b"Host method 'aaa"));
+/// assert!(memory[264..].iter().all(|&byte| byte == 0));
+/// ```
 #[export_name = "universal_invoke"]
+#[allow(clippy::too_many_arguments)]
 pub fn universal_invoke(
-    mut caller: Caller<'_, WasiCtx>,
+    registry: &HostMethodRegistry,
+    mut caller: Caller<'_, HostState>,
     method_name_ptr: i32,
     method_name_len: i32,
     format_type: i32,
@@ -171,76 +486,761 @@ pub fn universal_invoke(
     // Get WASM memory instance
     let memory = match caller.get_export("memory").and_then(|e| e.into_memory()) {
         Some(mem) => mem,
-        None => return 1, // Memory not found
+        None => {
+            let err = DlinkError::MemoryNotFound;
+            log::warn!("[UniversalInvoke] {}", err);
+            return err.code();
+        }
     };
 
-    // Read method name from WASM memory
-    let method_name_bytes = match read_wasm_memory(&memory, &caller, method_name_ptr, method_name_len) {
-        Ok(bytes) => bytes,
-        Err(_) => return 1, // Failed to read method name
+    let result = invoke_host_method(
+        registry,
+        &memory,
+        &mut caller,
+        method_name_ptr,
+        method_name_len,
+        format_type,
+        params_ptr,
+        params_len,
+    );
+    finish_legacy_invoke(&memory, &mut caller, ret_ptr, result)
+}
+
+/// # Async Universal Invocation Function
+/// 
+/// Async counterpart of `universal_invoke`, linked by `create_dlinkwm_async_linker`.
+/// Async host methods are awaited while the guest is suspended; synchronous host
+/// methods run as in `universal_invoke`. Parameters, status codes and the response
+/// format are the same.
+#[allow(clippy::too_many_arguments)]
+pub async fn universal_invoke_async(
+    registry: &HostMethodRegistry,
+    mut caller: Caller<'_, HostState>,
+    method_name_ptr: i32,
+    method_name_len: i32,
+    format_type: i32,
+    params_ptr: i32,
+    params_len: i32,
+    ret_ptr: i32,
+) -> i32 {
+    let memory = match caller.get_export("memory").and_then(|e| e.into_memory()) {
+        Some(mem) => mem,
+        None => {
+            let err = DlinkError::MemoryNotFound;
+            log::warn!("[UniversalInvoke] {}", err);
+            return err.code();
+        }
     };
-    let method_name = match String::from_utf8(method_name_bytes) {
-        Ok(name) => name,
-        Err(_) => return 1, // Invalid UTF-8 encoding
+
+    let result = invoke_host_method_async(
+        registry,
+        &memory,
+        &mut caller,
+        method_name_ptr,
+        method_name_len,
+        format_type,
+        params_ptr,
+        params_len,
+    )
+    .await;
+    finish_legacy_invoke(&memory, &mut caller, ret_ptr, result)
+}
+
+/// # Bounded Universal Invocation Function
+/// 
+/// Revision of `universal_invoke` where the guest passes the capacity of its return
+/// buffer. Methods are resolved and run as in `universal_invoke`, and the response
+/// has the same format, but the host never writes more than `ret_capacity` bytes.
+/// 
+/// # Parameters
+/// 
+/// The parameters of `universal_invoke`, followed by:
+/// 
+/// - `ret_capacity`: Size of the buffer at `ret_ptr` in bytes
+/// 
+/// # Returns
+/// 
+/// A status code of `universal_invoke`, or `8` (buffer too small) if the response
+/// does not fit. The host then writes only the header, whose length is that of the
+/// response data, and keeps the response until the guest fetches it with
+/// `take_response` into a buffer of at least `8 + length` bytes. The host method is
+/// not run again. A kept response only lives within the current entry call: it is
+/// replaced by the next `universal_invoke_v2` call and dropped before the next
+/// entry call on the store.
+/// 
+/// Error messages of the host are cut to the capacity instead. Nothing is written
+/// to a buffer smaller than the 8 byte header.
+/// 
+/// # Example
+/// 
+/// ```rust
+/// use dlink_wm::config::DlinkWMConfig;
+/// use dlink_wm::registry::HostMethodRegistry;
+/// use dlink_wm::wasm_manager::WasmInstanceCache;
+/// use std::sync::{Arc, RwLock};
+/// 
+/// // A guest with a 16 byte buffer at 64, fetching what does not fit into the buffer at 128
+/// let wasm_path = std::env::temp_dir().join("dlinkwm_invoke_v2_doctest.wasm");
+/// std::fs::write(&wasm_path, r#"(module
+///     (import "dlinkwm_host" "universal_invoke_v2"
+///         (func $invoke (param i32 i32 i32 i32 i32 i32 i32) (result i32)))
+///     (import "dlinkwm_host" "take_response" (func $take (param i32 i32) (result i32)))
+///     (memory (export "memory") 1)
+///     (data (i32.const 0) "hello")
+///     (data (i32.const 8) "null")
+///     (func $hello (result i32)
+///         (call $invoke (i32.const 0) (i32.const 5) (i32.const 0) (i32.const 8) (i32.const 4)
+///             (i32.const 64) (i32.const 16)))
+///     (func (export "call") (result i32)
+///         ;; Status of the call at 32, of the first and second take at 36 and 40
+///         (i32.store (i32.const 32) (call $hello))
+///         (i32.store (i32.const 36) (call $take (i32.const 128) (i32.const 256)))
+///         (i32.store (i32.const 40) (call $take (i32.const 128) (i32.const 256)))
+///         (i32.const 0))
+///     (func (export "call_only") (result i32) (call $hello))
+///     (func (export "take") (result i32)
+///         (call $take (i32.const 128) (i32.const 256)))
+///     (data (i32.const 512) "\ff\ff\ff\ff\ff\ff\ff\ff")
+///     (func (export "tiny") (result i32)
+///         (call $invoke (i32.const 0) (i32.const 4) (i32.const 0) (i32.const 8) (i32.const 4)
+///             (i32.const 512) (i32.const 4))))"#).unwrap();
+/// let wasm_path = wasm_path.to_str().unwrap();
+/// 
+/// let registry = Arc::new(HostMethodRegistry::new());
+/// registry.register_typed("hello", |_: ()| Ok("x".repeat(100)));
+/// let mut config = DlinkWMConfig::default();
+/// let entry_functions = ["call", "call_only", "take", "tiny"].map(String::from).to_vec();
+/// config.entry_functions.insert(wasm_path.to_string(), entry_functions);
+/// let cache = WasmInstanceCache::with_registry(Arc::new(RwLock::new(config)), registry);
+/// let read = |range: std::ops::Range<usize>| cache.with_instance(wasm_path, false, |instance, store| {
+///     let memory = instance.get_memory(&mut *store, "memory").unwrap();
+///     Ok(memory.data(&*store)[range].to_vec())
+/// }).unwrap();
+/// let read_i32 = |offset: usize| i32::from_le_bytes(read(offset..offset + 4).try_into().unwrap());
+/// 
+/// // 102 bytes of JSON do not fit: status 8 and the required length in the header.
+/// // The kept response is then written once, and gone.
+/// cache.call::<(), i32>(wasm_path, "call", ()).unwrap();
+/// assert_eq!((read_i32(32), read_i32(68)), (8, 102));
+/// assert_eq!((read_i32(36), read_i32(40)), (0, 9));
+/// assert_eq!(read(136..238), format!("\"{}\"", "x".repeat(100)).into_bytes());
+/// 
+/// // A response that was not taken does not outlive the entry call
+/// assert_eq!(cache.call::<(), i32>(wasm_path, "call_only", ()).unwrap(), 8);
+/// assert_eq!(cache.call::<(), i32>(wasm_path, "take", ()).unwrap(), 9);
+/// 
+/// // The error of the unknown method "hell" does not fit a 4 byte buffer, so nothing is written
+/// assert_eq!(cache.call::<(), i32>(wasm_path, "tiny", ()).unwrap(), 1);
+/// assert_eq!(read(512..520), [0xff; 8]);
+/// ```
+#[allow(clippy::too_many_arguments)]
+pub fn universal_invoke_v2(
+    registry: &HostMethodRegistry,
+    mut caller: Caller<'_, HostState>,
+    method_name_ptr: i32,
+    method_name_len: i32,
+    format_type: i32,
+    params_ptr: i32,
+    params_len: i32,
+    ret_ptr: i32,
+    ret_capacity: i32,
+) -> i32 {
+    let memory = match caller_memory(&mut caller) {
+        Ok(memory) => memory,
+        Err(err) => return err.code(),
     };
+    caller.data_mut().pending_response = None;
 
-    // Determine serialization format from format type
-    let format = match format_type {
-        0 => SerializationFormat::Json,
-        1 => SerializationFormat::Bincode,
-        2 => SerializationFormat::Protobuf,
-        3 => SerializationFormat::FlatBuffers,
-        _ => return 2, // Invalid format type
+    let result = invoke_host_method(
+        registry,
+        &memory,
+        &mut caller,
+        method_name_ptr,
+        method_name_len,
+        format_type,
+        params_ptr,
+        params_len,
+    );
+    finish_bounded_invoke(&memory, &mut caller, ret_ptr, ret_capacity, result)
+}
+
+/// # Async Bounded Universal Invocation Function
+/// 
+/// Async counterpart of `universal_invoke_v2`, linked by
+/// `create_dlinkwm_async_linker`. Async host methods are awaited while the guest is
+/// suspended; parameters, status codes and the response format are the same.
+#[allow(clippy::too_many_arguments)]
+pub async fn universal_invoke_v2_async(
+    registry: &HostMethodRegistry,
+    mut caller: Caller<'_, HostState>,
+    method_name_ptr: i32,
+    method_name_len: i32,
+    format_type: i32,
+    params_ptr: i32,
+    params_len: i32,
+    ret_ptr: i32,
+    ret_capacity: i32,
+) -> i32 {
+    let memory = match caller_memory(&mut caller) {
+        Ok(memory) => memory,
+        Err(err) => return err.code(),
     };
+    caller.data_mut().pending_response = None;
+
+    let result = invoke_host_method_async(
+        registry,
+        &memory,
+        &mut caller,
+        method_name_ptr,
+        method_name_len,
+        format_type,
+        params_ptr,
+        params_len,
+    )
+    .await;
+    finish_bounded_invoke(&memory, &mut caller, ret_ptr, ret_capacity, result)
+}
+
+/// # Take a Pending Response
+/// 
+/// Writes the response that `universal_invoke_v2` kept because it did not fit the
+/// guest's buffer, in the same format, and forgets it.
+/// 
+/// # Parameters
+/// 
+/// - `caller`: WASM caller context
+/// - `ret_ptr`: Pointer to write the response to in WASM memory
+/// - `ret_capacity`: Size of the buffer at `ret_ptr` in bytes
+/// 
+/// # Returns
+/// 
+/// - `0`: The response was written
+/// - `8`: The buffer is still too small; the response is kept and the header holds
+///   its length
+/// - `9`: No response is pending
+/// - `4` or `6`: The memory is missing or the buffer lies outside it; the response
+///   is dropped
+pub fn take_response(mut caller: Caller<'_, HostState>, ret_ptr: i32, ret_capacity: i32) -> i32 {
+    let result = caller_memory(&mut caller).and_then(|memory| {
+        let (success, ret_bytes) = caller.data_mut().pending_response.take().ok_or(DlinkError::NoPendingResponse)?;
+        write_bounded_response(&memory, &mut caller, ret_ptr, ret_capacity, success, ret_bytes)
+    });
+
+    match result {
+        Ok(()) => STATUS_OK,
+        Err(err) => {
+            log::debug!("[UniversalInvoke] take_response: {}", err);
+            err.code()
+        }
+    }
+}
+
+/// Gets the exported memory of the calling module.
+fn caller_memory(caller: &mut Caller<'_, HostState>) -> Result<Memory> {
+    caller.get_export("memory").and_then(|e| e.into_memory()).ok_or_else(|| {
+        let err = DlinkError::MemoryNotFound;
+        log::warn!("[UniversalInvoke] {}", err);
+        err
+    })
+}
+
+/// Writes the outcome of a `universal_invoke_v2` call within the capacity of the
+/// guest's buffer and returns its status code.
+fn finish_bounded_invoke(
+    memory: &Memory,
+    caller: &mut Caller<'_, HostState>,
+    ret_ptr: i32,
+    ret_capacity: i32,
+    result: Result<(bool, Vec<u8>)>,
+) -> i32 {
+    let result =
+        result.and_then(|(success, ret_bytes)| write_bounded_response(memory, caller, ret_ptr, ret_capacity, success, ret_bytes));
+
+    match result {
+        Ok(()) => STATUS_OK,
+        Err(err @ DlinkError::BufferTooSmall { .. }) => {
+            // Not a failure: the guest fetches the response with `take_response`
+            log::debug!("[UniversalInvoke] {}", err);
+            err.code()
+        }
+        Err(err) => {
+            log::warn!("[UniversalInvoke] {}", err);
+            // Like a response, the message is only written if at least its header fits
+            let capacity = capacity(ret_capacity);
+            if capacity >= RESPONSE_HEADER_SIZE {
+                let mut message = err.to_string().into_bytes();
+                message.truncate(capacity - RESPONSE_HEADER_SIZE);
+                let _ = write_invoke_response(memory, caller, ret_ptr, false, &message);
+            }
+            err.code()
+        }
+    }
+}
+
+/// Writes a response if it fits into `ret_capacity` bytes. Otherwise writes only
+/// the header (if it fits) and keeps the response for `take_response`.
+fn write_bounded_response(
+    memory: &Memory,
+    caller: &mut Caller<'_, HostState>,
+    ret_ptr: i32,
+    ret_capacity: i32,
+    success: bool,
+    ret_bytes: Vec<u8>,
+) -> Result<()> {
+    let capacity = capacity(ret_capacity);
+    let required = RESPONSE_HEADER_SIZE + ret_bytes.len();
+    if required <= capacity {
+        return write_invoke_response(memory, caller, ret_ptr, success, &ret_bytes);
+    }
+
+    if capacity >= RESPONSE_HEADER_SIZE {
+        let header = ResponseHeader { success, len: ret_bytes.len() as u32 };
+        write_wasm_memory(memory, &mut *caller, ret_ptr, &header.encode())?;
+    }
+    caller.data_mut().pending_response = Some((success, ret_bytes));
+    Err(DlinkError::BufferTooSmall { required, capacity })
+}
+
+/// Capacity of a guest buffer; negative capacities hold nothing.
+fn capacity(ret_capacity: i32) -> usize {
+    ret_capacity.max(0) as usize
+}
+
+/// Writes the outcome of a `universal_invoke` call and returns its status code.
+/// 
+/// Legacy guests do not pass the size of their buffer, so error messages, which can
+/// hold the guest's method name or a handler's message, are cut to
+/// `LEGACY_ERROR_MESSAGE_LIMIT` bytes.
+fn finish_legacy_invoke(
+    memory: &Memory,
+    caller: &mut Caller<'_, HostState>,
+    ret_ptr: i32,
+    result: Result<(bool, Vec<u8>)>,
+) -> i32 {
+    let result = result.and_then(|(success, ret_bytes)| write_invoke_response(memory, caller, ret_ptr, success, &ret_bytes));
+
+    match result {
+        Ok(()) => STATUS_OK,
+        Err(err) => {
+            log::warn!("[UniversalInvoke] {}", err);
+            let mut message = err.to_string();
+            if message.len() > LEGACY_ERROR_MESSAGE_LIMIT {
+                let mut end = LEGACY_ERROR_MESSAGE_LIMIT;
+                while !message.is_char_boundary(end) {
+                    end -= 1;
+                }
+                message.truncate(end);
+            }
+            // Hand the message back to the guest; if the buffer itself is invalid there is nothing more to do
+            let _ = write_invoke_response(memory, caller, ret_ptr, false, message.as_bytes());
+            err.code()
+        }
+    }
+}
+
+/// A `universal_invoke` request read from guest memory.
+struct InvokeRequest {
+    /// Name of the requested host method
+    method_name: String,
+    /// Serialization format of the parameters and response
+    format: SerializationFormat,
+    /// Serialized parameters
+    params: Vec<u8>,
+}
+
+/// Reads the method name and parameters from guest memory and checks the
+/// module's permission to call the method.
+#[allow(clippy::too_many_arguments)]
+fn read_invoke_request(
+    memory: &Memory,
+    caller: &mut Caller<'_, HostState>,
+    method_name_ptr: i32,
+    method_name_len: i32,
+    format_type: i32,
+    params_ptr: i32,
+    params_len: i32,
+) -> Result<InvokeRequest> {
+    // Read method name from WASM memory
+    let method_name_bytes = read_wasm_memory(memory, &*caller, method_name_ptr, method_name_len)?;
+    let method_name = String::from_utf8(method_name_bytes).map_err(|_| DlinkError::InvalidUtf8)?;
+
+    // Check the capability table before revealing whether the method exists
+    caller.data().check_host_permission(&method_name)?;
+
+    // Determine serialization format from format type
+    let format = SerializationFormat::from_format_type(format_type)?;
 
     // Read serialized parameters from WASM memory
-    let params_bytes = match read_wasm_memory(&memory, &caller, params_ptr, params_len) {
-        Ok(bytes) => bytes,
-        Err(_) => return 2, // Failed to read parameters
-    };
+    let params = read_wasm_memory(memory, &*caller, params_ptr, params_len)?;
+    Ok(InvokeRequest { method_name, format, params })
+}
+
+/// Reads the method name and parameters from guest memory and runs the handler.
+#[allow(clippy::too_many_arguments)]
+fn invoke_host_method(
+    registry: &HostMethodRegistry,
+    memory: &Memory,
+    caller: &mut Caller<'_, HostState>,
+    method_name_ptr: i32,
+    method_name_len: i32,
+    format_type: i32,
+    params_ptr: i32,
+    params_len: i32,
+) -> Result<(bool, Vec<u8>)> {
+    let request = read_invoke_request(memory, caller, method_name_ptr, method_name_len, format_type, params_ptr, params_len)?;
 
     // Find and call the registered handler
-    match HOST_METHOD_REGISTRY.read().unwrap().get(method_name.as_str()) {
-        Some(handler) => {
-            match handler(params_bytes, format) {
-                Ok((success, ret_bytes)) => {
-                    // Write status code (4 bytes, little-endian)
-                    let status: u32 = if success { 1 } else { 0 };
-                    let status_bytes = status.to_le_bytes();
-                    if write_wasm_memory(&memory, &mut caller, ret_ptr, &status_bytes).is_err() {
-                        return 3;
-                    }
-                    
-                    // Write response length (4 bytes, little-endian)
-                    let len_bytes = (ret_bytes.len() as u32).to_le_bytes();
-                    if write_wasm_memory(&memory, &mut caller, ret_ptr + 4, &len_bytes).is_err() {
-                        return 3;
-                    }
-                    
-                    // Write response data
-                    if write_wasm_memory(&memory, &mut caller, ret_ptr + 8, &ret_bytes).is_err() {
-                        return 3;
-                    }
-                    
-                    0 // Success
-                },
-                Err(_) => 3, // Execution error
-            }
+    match registry.resolve_method(&request.method_name) {
+        Some(HostMethod::Sync(handler)) => run_handler(&handler, Some(*memory), caller.as_context_mut(), request),
+        Some(HostMethod::Async(_)) => Err(DlinkError::HandlerFailed {
+            method: request.method_name,
+            message: "async host methods can only be called from an AsyncWasmInstanceCache".to_string(),
+        }),
+        None => Err(DlinkError::MethodNotFound(request.method_name)),
+    }
+}
+
+/// Reads the method name and parameters from guest memory and runs the handler,
+/// suspending the guest while an async handler completes.
+#[allow(clippy::too_many_arguments)]
+async fn invoke_host_method_async(
+    registry: &HostMethodRegistry,
+    memory: &Memory,
+    caller: &mut Caller<'_, HostState>,
+    method_name_ptr: i32,
+    method_name_len: i32,
+    format_type: i32,
+    params_ptr: i32,
+    params_len: i32,
+) -> Result<(bool, Vec<u8>)> {
+    let request = read_invoke_request(memory, caller, method_name_ptr, method_name_len, format_type, params_ptr, params_len)?;
+
+    match registry.resolve_method(&request.method_name) {
+        Some(HostMethod::Sync(handler)) => run_handler(&handler, Some(*memory), caller.as_context_mut(), request),
+        Some(HostMethod::Async(handler)) => {
+            let ctx = AsyncHostContext {
+                module_path: caller.data().module_path().to_string(),
+            };
+            handler(ctx, request.params, request.format)
+                .await
+                .map_err(|e| handler_error(request.method_name, e))
         },
-        None => 1, // Method not found
+        None => Err(DlinkError::MethodNotFound(request.method_name)),
     }
 }
 
+/// Runs a host method called through the `invoke` import of a component.
+/// 
+/// Methods are resolved in the store's registry override, or else in `registry`,
+/// after checking the store's `host_permissions`. The handler's `HostContext` has
+/// no memory, since components pass the parameters by value.
+pub(crate) fn invoke_component_host_method(
+    registry: &Arc<HostMethodRegistry>,
+    store: StoreContextMut<'_, HostState>,
+    method_name: String,
+    format: SerializationFormat,
+    params: Vec<u8>,
+) -> Result<(bool, Vec<u8>)> {
+    store.data().check_host_permission(&method_name)?;
+    let registry = store.data().registry.clone().unwrap_or_else(|| Arc::clone(registry));
+    let request = InvokeRequest { method_name, format, params };
+
+    match registry.resolve_method(&request.method_name) {
+        Some(HostMethod::Sync(handler)) => run_handler(&handler, None, store, request),
+        Some(HostMethod::Async(_)) => Err(DlinkError::HandlerFailed {
+            method: request.method_name,
+            message: "async host methods cannot be called from components".to_string(),
+        }),
+        None => Err(DlinkError::MethodNotFound(request.method_name)),
+    }
+}
+
+/// Runs a synchronous handler on a request.
+fn run_handler(
+    handler: &MethodHandler,
+    memory: Option<Memory>,
+    store: StoreContextMut<'_, HostState>,
+    request: InvokeRequest,
+) -> Result<(bool, Vec<u8>)> {
+    let mut ctx = HostContext { store, memory };
+    handler(&mut ctx, request.params, request.format).map_err(|e| handler_error(request.method_name, e))
+}
+
+/// Converts the error returned by a handler into a `DlinkError`.
+fn handler_error(method_name: String, error: anyhow::Error) -> DlinkError {
+    match error.downcast::<DlinkError>() {
+        // Codec failures inside typed handlers keep their own status code
+        Ok(err) => err,
+        Err(e) => DlinkError::HandlerFailed {
+            method: method_name,
+            message: format!("{:#}", e),
+        },
+    }
+}
+
+/// Writes a response (status, length, data) to the guest return buffer.
+fn write_invoke_response(
+    memory: &Memory,
+    caller: &mut Caller<'_, HostState>,
+    ret_ptr: i32,
+    success: bool,
+    ret_bytes: &[u8],
+) -> Result<()> {
+    // Write the status and length header
+    let header = ResponseHeader { success, len: ret_bytes.len() as u32 };
+    write_wasm_memory(memory, &mut *caller, ret_ptr, &header.encode())?;
+
+    // Write response data
+    write_wasm_memory(memory, &mut *caller, ret_ptr.wrapping_add(RESPONSE_HEADER_SIZE as i32), ret_bytes)
+}
+
 // -------------------------- Store and Linker Configuration --------------------------
 
+/// # Host State
+/// 
+/// Per-store data shared by all host imports of an instance. It holds the WASI
+/// context, the allocator backing `host_malloc`/`host_free` (so every instance gets
+/// its own allocation accounting), the path of the module and any application data
+/// that host methods attach to the instance.
+/// 
+/// Stores of components additionally hold a WASI preview2 context and the resource
+/// table of its streams and preopens (see `create_component_store`).
+pub struct HostState {
+    /// WASI context used by the WASI imports
+    wasi: WasiCtx,
+    /// WASI preview2 context and resource table of a component store
+    preview2: Option<(Table, preview2::WasiCtx)>,
+    /// Allocator for memory handed out through `host_malloc`
+    allocator: GuestAllocator,
+    /// Path of the WASM module this store was created for
+    module_path: String,
+    /// Configuration whose `host_permissions` restrict `universal_invoke`
+    config: Option<Arc<RwLock<DlinkWMConfig>>>,
+    /// Resource limiter enforcing the module's memory and table limits
+    limiter: ModuleLimiter,
+    /// Buffers of the stdio streams configured as `capture`
+    captures: CaptureBuffers,
+    /// Deadline of the running call, checked when the engine's epoch ticks
+    call_deadline: Option<Instant>,
+    /// Registry overriding the one the linker was created with
+    registry: Option<Arc<HostMethodRegistry>>,
+    /// Response of `universal_invoke_v2` that did not fit the guest's buffer, kept
+    /// for `take_response` within the current entry call
+    pending_response: Option<(bool, Vec<u8>)>,
+    /// Application data attached to this store, keyed by type
+    extensions: HashMap<TypeId, Box<dyn Any + Send + Sync>>,
+}
+
+impl HostState {
+    /// Creates a new host state around the given WASI context.
+    /// 
+    /// # Parameters
+    /// 
+    /// - `wasi`: WASI context to expose to the instance
+    pub fn new(wasi: WasiCtx) -> Self {
+        Self {
+            wasi,
+            preview2: None,
+            allocator: GuestAllocator::new(),
+            module_path: String::new(),
+            config: None,
+            limiter: ModuleLimiter::default(),
+            captures: CaptureBuffers::default(),
+            call_deadline: None,
+            registry: None,
+            pending_response: None,
+            extensions: HashMap::new(),
+        }
+    }
+
+    /// Gets the path of the WASM module this store was created for.
+    /// 
+    /// Empty for stores that were not created by `WasmInstanceCache`.
+    pub fn module_path(&self) -> &str {
+        &self.module_path
+    }
+
+    /// Sets the path of the WASM module this store belongs to.
+    pub(crate) fn set_module_path(&mut self, module_path: &str) {
+        self.module_path = module_path.to_string();
+    }
+
+    /// Binds the store to a configuration so `universal_invoke` enforces its
+    /// `host_permissions`. Stores without a configuration may call every method.
+    pub(crate) fn set_config(&mut self, config: Arc<RwLock<DlinkWMConfig>>) {
+        self.config = Some(config);
+    }
+
+    /// Gets the configuration the store is bound to, if any.
+    pub(crate) fn config(&self) -> Option<&Arc<RwLock<DlinkWMConfig>>> {
+        self.config.as_ref()
+    }
+
+    /// Gets the resource limiter of the store.
+    pub fn limiter(&self) -> &ModuleLimiter {
+        &self.limiter
+    }
+
+    /// Gets mutable access to the resource limiter of the store.
+    pub(crate) fn limiter_mut(&mut self) -> &mut ModuleLimiter {
+        &mut self.limiter
+    }
+
+    /// Gets the deadline of the running call, if it has a timeout.
+    pub fn call_deadline(&self) -> Option<Instant> {
+        self.call_deadline
+    }
+
+    /// Sets the deadline of the next call.
+    pub(crate) fn set_call_deadline(&mut self, deadline: Option<Instant>) {
+        self.call_deadline = deadline;
+    }
+
+    /// Drops a response kept by `universal_invoke_v2` that the guest did not take,
+    /// so that it does not outlive the entry call.
+    pub(crate) fn clear_pending_response(&mut self) {
+        self.pending_response = None;
+    }
+
+    /// Makes `universal_invoke` resolve methods in `registry` instead of the
+    /// registry the linker was created with.
+    pub(crate) fn set_registry(&mut self, registry: Arc<HostMethodRegistry>) {
+        self.registry = Some(registry);
+    }
+
+    /// Takes the output the module wrote to its captured stdio streams.
+    pub fn take_captured_output(&self) -> CapturedOutput {
+        self.captures.take()
+    }
+
+    /// Checks the store's `host_permissions` for a host method.
+    fn check_host_permission(&self, method_name: &str) -> Result<()> {
+        let allowed = self
+            .config
+            .as_ref()
+            .is_none_or(|config| config.read().unwrap().is_host_method_allowed(&self.module_path, method_name));
+        if allowed {
+            Ok(())
+        } else {
+            log::warn!(
+                "[Permissions] Denied host method '{}' for WASM module '{}'",
+                method_name,
+                self.module_path
+            );
+            Err(DlinkError::PermissionDenied {
+                module: self.module_path.clone(),
+                method: method_name.to_string(),
+            })
+        }
+    }
+
+    /// Attaches application data of type `T` to this store, replacing any previous
+    /// value of the same type.
+    /// 
+    /// # Example
+    /// 
+    /// ```rust
+    /// use dlink_wm::host_import::init_store_with_wasi;
+    /// 
+    /// struct SessionCounter(u32);
+    /// 
+    /// let (mut store, _, _) = init_store_with_wasi();
+    /// store.data_mut().insert_data(SessionCounter(0));
+    /// store.data_mut().get_data_mut::<SessionCounter>().unwrap().0 += 1;
+    /// assert_eq!(store.data().get_data::<SessionCounter>().unwrap().0, 1);
+    /// ```
+    pub fn insert_data<T: Any + Send + Sync>(&mut self, value: T) -> Option<T> {
+        self.extensions
+            .insert(TypeId::of::<T>(), Box::new(value))
+            .and_then(|old| old.downcast().ok().map(|old| *old))
+    }
+
+    /// Gets the application data of type `T` attached to this store.
+    pub fn get_data<T: Any + Send + Sync>(&self) -> Option<&T> {
+        self.extensions.get(&TypeId::of::<T>()).and_then(|value| value.downcast_ref())
+    }
+
+    /// Gets mutable access to the application data of type `T` attached to this store.
+    pub fn get_data_mut<T: Any + Send + Sync>(&mut self) -> Option<&mut T> {
+        self.extensions.get_mut(&TypeId::of::<T>()).and_then(|value| value.downcast_mut())
+    }
+
+    /// Removes and returns the application data of type `T` attached to this store.
+    pub fn remove_data<T: Any + Send + Sync>(&mut self) -> Option<T> {
+        self.extensions
+            .remove(&TypeId::of::<T>())
+            .and_then(|value| value.downcast().ok().map(|value| *value))
+    }
+
+    /// Gets the WASI context of this store.
+    pub fn wasi(&self) -> &WasiCtx {
+        &self.wasi
+    }
+
+    /// Gets mutable access to the WASI context of this store.
+    pub fn wasi_mut(&mut self) -> &mut WasiCtx {
+        &mut self.wasi
+    }
+
+    /// Returns `true` if the store was created for a component and holds a WASI
+    /// preview2 context.
+    pub fn is_component(&self) -> bool {
+        self.preview2.is_some()
+    }
+
+    /// Gets the WASI preview2 context and resource table of a component store.
+    fn preview2(&self) -> &(Table, preview2::WasiCtx) {
+        self.preview2.as_ref().expect("component stores have a WASI preview2 context")
+    }
+
+    /// Gets mutable access to the WASI preview2 context and resource table of a
+    /// component store.
+    fn preview2_mut(&mut self) -> &mut (Table, preview2::WasiCtx) {
+        self.preview2.as_mut().expect("component stores have a WASI preview2 context")
+    }
+
+    /// Gets the allocator backing `host_malloc`/`host_free` for this store.
+    pub fn allocator(&self) -> &GuestAllocator {
+        &self.allocator
+    }
+
+    /// Gets a snapshot of the host allocation accounting for this store.
+    pub fn allocator_stats(&self) -> AllocatorStats {
+        self.allocator.stats()
+    }
+}
+
+// Only stores from `create_component_store` are handed to component linkers
+impl WasiView for HostState {
+    fn table(&self) -> &Table {
+        &self.preview2().0
+    }
+
+    fn table_mut(&mut self) -> &mut Table {
+        &mut self.preview2_mut().0
+    }
+
+    fn ctx(&self) -> &preview2::WasiCtx {
+        &self.preview2().1
+    }
+
+    fn ctx_mut(&mut self) -> &mut preview2::WasiCtx {
+        &mut self.preview2_mut().1
+    }
+}
+
 /// # Initialize Store and WASI Context
 /// 
 /// Creates a new WASM store with a WASI context configured to inherit stdio.
 /// 
+/// The engine has fuel consumption and epoch interruption enabled so that
+/// `ExecutionBudget` can limit individual calls. The returned store starts with
+/// an unlimited budget and a `ModuleLimiter` without limits.
+/// 
 /// # Returns
 /// 
 /// A tuple containing:
-/// - `Store<WasiCtx>`: The WASM store instance
+/// - `Store<HostState>`: The WASM store instance
 /// - `WasiCtx`: The WASI context
 /// - `Engine`: The WASM engine instance
 /// 
@@ -251,18 +1251,200 @@ pub fn universal_invoke(
 /// 
 /// let (store, wasi_ctx, engine) = init_store_with_wasi();
 /// ```
-pub fn init_store_with_wasi() -> (Store<WasiCtx>, WasiCtx, Engine) {
-    let engine = Engine::default();
-    let wasi_ctx = WasiCtxBuilder::new()
-        .inherit_stdio()
-        .build();
-    let store = Store::new(&engine, wasi_ctx.clone());
-    (store, wasi_ctx, engine)
+pub fn init_store_with_wasi() -> (Store<HostState>, WasiCtx, Engine) {
+    init_store_with_wasi_config(&WasiConfig::default()).expect("the default WASI configuration opens no files")
+}
+
+/// # Initialize Store with a WASI Configuration
+/// 
+/// Creates a new WASM store whose WASI context is built from `config`: arguments,
+/// environment variables, preopened directories and stdio. Output streams
+/// configured as `capture` can be read with `HostState::take_captured_output`.
+/// 
+/// # Parameters
+/// 
+/// - `config`: WASI configuration of the module
+/// 
+/// # Returns
+/// 
+/// The same tuple as `init_store_with_wasi`.
+/// 
+/// # Errors
+/// 
+/// Returns an error if the WASI context cannot be built (see `wasi::build_wasi_ctx`).
+/// 
+/// # Example
+/// 
+/// ```rust
+/// use dlink_wm::config::{StdioConfig, WasiConfig};
+/// use dlink_wm::host_import::init_store_with_wasi_config;
+/// 
+/// let config = WasiConfig {
+///     args: vec!["tool".to_string()],
+///     stdout: StdioConfig::Capture,
+///     ..Default::default()
+/// };
+/// let (store, _, _) = init_store_with_wasi_config(&config).unwrap();
+/// assert!(store.data().take_captured_output().stdout.is_empty());
+/// ```
+pub fn init_store_with_wasi_config(config: &WasiConfig) -> Result<(Store<HostState>, WasiCtx, Engine)> {
+    let engine = create_engine(&EngineConfig::default())?;
+    let store = create_store(&engine, config)?;
+    let wasi_ctx = store.data().wasi().clone();
+    Ok((store, wasi_ctx, engine))
+}
+
+/// # Create Engine
+/// 
+/// Creates an engine with the given compilation settings. Fuel consumption and
+/// epoch interruption are always enabled so that `ExecutionBudget` can limit calls.
+/// 
+/// # Parameters
+/// 
+/// - `config`: Compilation settings (optimisation level, SIMD, threads, bulk memory)
+///   and instance allocation strategy
+/// 
+/// # Errors
+/// 
+/// Returns `DlinkError::Wasm` if wasmtime rejects the combination of settings.
+/// 
+/// # Example
+/// 
+/// ```rust
+/// use dlink_wm::config::{EngineConfig, OptLevelConfig};
+/// use dlink_wm::host_import::create_engine;
+/// 
+/// let engine = create_engine(&EngineConfig {
+///     opt_level: OptLevelConfig::None,
+///     ..Default::default()
+/// }).unwrap();
+/// ```
+pub fn create_engine(config: &EngineConfig) -> Result<Engine> {
+    Ok(Engine::new(&engine_config(config))?)
+}
+
+/// # Create Async Engine
+/// 
+/// Creates an engine like `create_engine` with wasmtime's async support enabled.
+/// Stores of an async engine must be created with `create_async_store` and their
+/// functions called with `call_async`.
+/// 
+/// # Errors
+/// 
+/// Returns `DlinkError::Wasm` if wasmtime rejects the combination of settings.
+pub fn create_async_engine(config: &EngineConfig) -> Result<Engine> {
+    let mut engine_config = engine_config(config);
+    engine_config.async_support(true);
+    Ok(Engine::new(&engine_config)?)
+}
+
+/// Builds the wasmtime configuration of an engine.
+fn engine_config(config: &EngineConfig) -> wasmtime::Config {
+    let mut engine_config = wasmtime::Config::new();
+    engine_config
+        .consume_fuel(true)
+        .epoch_interruption(true)
+        .cranelift_opt_level(match config.opt_level {
+            OptLevelConfig::None => OptLevel::None,
+            OptLevelConfig::Speed => OptLevel::Speed,
+            OptLevelConfig::SpeedAndSize => OptLevel::SpeedAndSize,
+        })
+        .wasm_simd(config.simd)
+        .wasm_threads(config.threads)
+        .wasm_bulk_memory(config.bulk_memory)
+        .wasm_component_model(true);
+    if !config.bulk_memory {
+        // Reference types depend on bulk memory
+        engine_config.wasm_reference_types(false);
+    }
+    if let Some(pooling) = &config.pooling_allocator {
+        let mut pooling_config = PoolingAllocationConfig::default();
+        pooling_config
+            .instance_count(pooling.total_instances)
+            .instance_memory_pages(pooling.max_memory_pages);
+        engine_config.allocation_strategy(InstanceAllocationStrategy::Pooling(pooling_config));
+    }
+    engine_config
+}
+
+/// # Create Store
+/// 
+/// Creates a store on `engine` with the WASI sandbox described by `config`, an
+/// unlimited execution budget and a `ModuleLimiter` without limits.
+/// 
+/// # Parameters
+/// 
+/// - `engine`: Engine created by `create_engine`
+/// - `config`: WASI configuration of the module
+/// 
+/// # Errors
+/// 
+/// Returns an error if the WASI context cannot be built (see `wasi::build_wasi_ctx`)
+/// or the engine was not created with fuel consumption.
+pub fn create_store(engine: &Engine, config: &WasiConfig) -> Result<Store<HostState>> {
+    let (wasi_ctx, captures) = build_wasi_ctx(config)?;
+    let mut host_state = HostState::new(wasi_ctx);
+    host_state.captures = captures;
+    new_store(engine, host_state)
+}
+
+/// # Create Component Store
+/// 
+/// Creates a store for a component on `engine`, like `create_store`, with a WASI
+/// preview2 context built from `config`. Its preview1 context is empty, since
+/// components only use the preview2 imports of `component::create_component_linker`.
+/// 
+/// # Parameters
+/// 
+/// - `engine`: Engine created by `create_engine`
+/// - `config`: WASI configuration of the component
+/// 
+/// # Errors
+/// 
+/// Returns an error if the WASI context cannot be built (see
+/// `wasi::build_preview2_ctx`) or the engine was not created with fuel consumption.
+pub fn create_component_store(engine: &Engine, config: &WasiConfig) -> Result<Store<HostState>> {
+    let mut table = Table::new();
+    let (wasi_ctx, captures) = build_preview2_ctx(config, &mut table)?;
+    let mut host_state = HostState::new(WasiCtxBuilder::new().build());
+    host_state.preview2 = Some((table, wasi_ctx));
+    host_state.captures = captures;
+    new_store(engine, host_state)
+}
+
+/// Creates a store around `host_state` with an unlimited execution budget and the
+/// store's `ModuleLimiter`.
+fn new_store(engine: &Engine, host_state: HostState) -> Result<Store<HostState>> {
+    let mut store = Store::new(engine, host_state);
+    store.limiter(|state| state.limiter_mut());
+    store.epoch_deadline_callback(check_call_deadline);
+    ExecutionBudget::unlimited().apply(&mut store)?;
+    Ok(store)
+}
+
+/// # Create Async Store
+/// 
+/// Creates a store on an engine from `create_async_engine`, like `create_store`.
+/// The store yields to the executor on every epoch tick of its engine.
+/// 
+/// # Errors
+/// 
+/// Returns an error if the WASI context cannot be built (see `wasi::build_wasi_ctx`)
+/// or the engine was not created with fuel consumption.
+pub fn create_async_store(engine: &Engine, config: &WasiConfig) -> Result<Store<HostState>> {
+    let mut store = create_store(engine, config)?;
+    store.epoch_deadline_callback(check_call_deadline_async);
+    ExecutionBudget::unlimited().apply_async(&mut store)?;
+    Ok(store)
 }
 
 /// # Host Memory Allocation
 /// 
-/// Allocates memory in the host for use by WASM modules.
+/// Allocates memory inside the guest's linear memory on behalf of the WASM module.
+/// 
+/// Blocks come from the store's `GuestAllocator`. When no free block is large
+/// enough, the guest memory is grown with `memory.grow` and the new pages are
+/// added to the allocator.
 /// 
 /// # Parameters
 /// 
@@ -271,26 +1453,54 @@ pub fn init_store_with_wasi() -> (Store<WasiCtx>, WasiCtx, Engine) {
 /// 
 /// # Returns
 /// 
-/// Pointer to the allocated memory block, or `-1` if allocation failed.
-/// 
-/// # Notes
-/// 
-/// This is a simplified implementation for demonstration purposes. In a production
-/// environment, a proper memory allocator should be used.
+/// Pointer to the allocated memory block, or `0` if allocation failed (the module
+/// exports no memory, `size` is not positive, or the memory cannot grow).
 pub fn host_malloc(
-    mut caller: Caller<'_, WasiCtx>,
-    _size: i32,
+    mut caller: Caller<'_, HostState>,
+    size: i32,
 ) -> i32 {
+    if size <= 0 {
+        return 0;
+    }
+
     // Get WASM memory
-    let _memory = match caller.get_export("memory").and_then(|e| e.into_memory()) {
+    let memory = match caller.get_export("memory").and_then(|e| e.into_memory()) {
         Some(mem) => mem,
-        None => return -1,
+        None => return 0,
+    };
+
+    allocate_guest_memory(&mut caller, &memory, size as u32).map_or(0, |ptr| ptr as i32)
+}
+
+/// Allocates a block of guest memory with the store's host allocator, growing the
+/// memory if no free block is large enough.
+pub(crate) fn allocate_guest_memory(mut store: impl AsContextMut<Data = HostState>, memory: &Memory, size: u32) -> Option<u32> {
+    if let Some(ptr) = store.as_context_mut().data_mut().allocator.allocate(size) {
+        return Some(ptr);
+    }
+
+    // Out of free blocks: grow the memory and hand the new pages to the allocator
+    let pages = GuestAllocator::pages_for(size);
+    let old_pages = match memory.grow(&mut store, pages) {
+        Ok(old_pages) => old_pages,
+        Err(e) => {
+            log::warn!("[HostMalloc] Failed to grow guest memory by {} pages: {}", pages, e);
+            return None;
+        }
     };
+    let region_start = old_pages * WASM_PAGE_SIZE;
+    let region_len = (pages * WASM_PAGE_SIZE).min(u32::MAX as u64);
+    let mut context = store.as_context_mut();
+    let allocator = &mut context.data_mut().allocator;
+    allocator.add_region(region_start as u32, region_len as u32);
+    allocator.allocate(size)
+}
 
-    // Simplified allocation strategy: fixed address allocation
-    // In real applications, use a proper memory allocator
-    let alloc_ptr = 0x100000; // Start allocation from this address
-    alloc_ptr
+/// Frees a block allocated with `allocate_guest_memory` or `host_malloc`.
+pub(crate) fn free_guest_memory(mut store: impl AsContextMut<Data = HostState>, ptr: u32) {
+    if let Err(e) = store.as_context_mut().data_mut().allocator.free(ptr) {
+        log::warn!("[HostFree] {}", e);
+    }
 }
 
 /// # Host Memory Free
@@ -304,14 +1514,17 @@ pub fn host_malloc(
 /// 
 /// # Notes
 /// 
-/// This is a no-op implementation for demonstration purposes. In a production
-/// environment, a proper memory allocator should be used.
+/// Freeing a null pointer is a no-op. Double frees and frees of pointers that were
+/// not returned by `host_malloc` are refused and logged; the allocator state is
+/// left unchanged.
 pub fn host_free(
-    _caller: Caller<'_, WasiCtx>,
-    _ptr: i32,
+    mut caller: Caller<'_, HostState>,
+    ptr: i32,
 ) {
-    // Simplified implementation: no-op
-    // In real applications, use a proper memory allocator to free memory
+    if ptr == 0 {
+        return;
+    }
+    free_guest_memory(&mut caller, ptr as u32);
 }
 
 /// # Create and Configure Linker
@@ -322,6 +1535,8 @@ pub fn host_free(
 /// # Parameters
 /// 
 /// - `engine`: WASM engine instance to use for linker creation
+/// - `registry`: Host method registry that `universal_invoke` resolves methods in,
+///   unless the store's `HostState` names another one
 /// 
 /// # Returns
 /// 
@@ -331,27 +1546,163 @@ pub fn host_free(
 /// 
 /// ```rust
 /// use dlink_wm::host_import::create_dlinkwm_linker;
+/// use dlink_wm::registry::HostMethodRegistry;
 /// use wasmtime::{Engine, Store};
-/// use wasmtime_wasi::WasiCtx;
+/// use std::sync::Arc;
 /// use anyhow::Result;
 /// 
 /// fn example() -> Result<()> {
 ///     let engine = Engine::default();
-///     let linker = create_dlinkwm_linker(&engine)?;
+///     // A plugin registry that still sees the globally registered methods
+///     let registry = Arc::new(HostMethodRegistry::with_parent(HostMethodRegistry::global()));
+///     let linker = create_dlinkwm_linker(&engine, registry)?;
 ///     Ok(())
 /// }
 /// ```
-pub fn create_dlinkwm_linker(engine: &Engine) -> Result<Linker<WasiCtx>> {
+pub fn create_dlinkwm_linker(engine: &Engine, registry: Arc<HostMethodRegistry>) -> Result<Linker<HostState>> {
     // Create a new linker instance
     let mut linker = Linker::new(engine);
 
     // Register WASI imports
-    wasmtime_wasi::add_to_linker(&mut linker, |s| s)?;
+    wasmtime_wasi::add_to_linker(&mut linker, |s: &mut HostState| &mut s.wasi)?;
 
     // Register host import functions
-    linker.func_wrap("dlinkwm_host", "universal_invoke", universal_invoke)?;
-    linker.func_wrap("dlinkwm_host", "host_malloc", host_malloc)?;
-    linker.func_wrap("dlinkwm_host", "host_free", host_free)?;
+    let registry_v2 = Arc::clone(&registry);
+    linker.func_wrap(
+        IMPORT_MODULE,
+        UNIVERSAL_INVOKE,
+        move |caller: Caller<'_, HostState>,
+              method_name_ptr: i32,
+              method_name_len: i32,
+              format_type: i32,
+              params_ptr: i32,
+              params_len: i32,
+              ret_ptr: i32| {
+            let registry = caller.data().registry.clone().unwrap_or_else(|| Arc::clone(&registry));
+            universal_invoke(
+                &registry,
+                caller,
+                method_name_ptr,
+                method_name_len,
+                format_type,
+                params_ptr,
+                params_len,
+                ret_ptr,
+            )
+        },
+    )?;
+    linker.func_wrap(
+        IMPORT_MODULE,
+        UNIVERSAL_INVOKE_V2,
+        move |caller: Caller<'_, HostState>,
+              method_name_ptr: i32,
+              method_name_len: i32,
+              format_type: i32,
+              params_ptr: i32,
+              params_len: i32,
+              ret_ptr: i32,
+              ret_capacity: i32| {
+            let registry = caller.data().registry.clone().unwrap_or_else(|| Arc::clone(&registry_v2));
+            universal_invoke_v2(
+                &registry,
+                caller,
+                method_name_ptr,
+                method_name_len,
+                format_type,
+                params_ptr,
+                params_len,
+                ret_ptr,
+                ret_capacity,
+            )
+        },
+    )?;
+    linker.func_wrap(IMPORT_MODULE, TAKE_RESPONSE, take_response)?;
+    linker.func_wrap(IMPORT_MODULE, HOST_MALLOC, host_malloc)?;
+    linker.func_wrap(IMPORT_MODULE, HOST_FREE, host_free)?;
+
+    Ok(linker)
+}
+
+/// # Create and Configure Async Linker
+/// 
+/// Creates a linker for an engine from `create_async_engine`. It defines the same
+/// imports as `create_dlinkwm_linker`, with `universal_invoke` and
+/// `universal_invoke_v2` backed by `universal_invoke_async` and
+/// `universal_invoke_v2_async` so that async host methods can suspend the guest.
+/// 
+/// # Parameters
+/// 
+/// - `engine`: Async engine to use for linker creation
+/// - `registry`: Host method registry that `universal_invoke` resolves methods in,
+///   unless the store's `HostState` names another one
+/// 
+/// # Errors
+/// 
+/// Returns `DlinkError::Wasm` if an import cannot be defined.
+pub fn create_dlinkwm_async_linker(engine: &Engine, registry: Arc<HostMethodRegistry>) -> Result<Linker<HostState>> {
+    let mut linker = Linker::new(engine);
+
+    // WASI calls complete without suspending the guest
+    wasmtime_wasi::add_to_linker(&mut linker, |s: &mut HostState| &mut s.wasi)?;
+
+    let registry_v2 = Arc::clone(&registry);
+    linker.func_wrap6_async(
+        IMPORT_MODULE,
+        UNIVERSAL_INVOKE,
+        move |caller: Caller<'_, HostState>,
+              method_name_ptr: i32,
+              method_name_len: i32,
+              format_type: i32,
+              params_ptr: i32,
+              params_len: i32,
+              ret_ptr: i32| {
+            let registry = caller.data().registry.clone().unwrap_or_else(|| Arc::clone(&registry));
+            Box::new(async move {
+                universal_invoke_async(
+                    &registry,
+                    caller,
+                    method_name_ptr,
+                    method_name_len,
+                    format_type,
+                    params_ptr,
+                    params_len,
+                    ret_ptr,
+                )
+                .await
+            })
+        },
+    )?;
+    linker.func_wrap7_async(
+        IMPORT_MODULE,
+        UNIVERSAL_INVOKE_V2,
+        move |caller: Caller<'_, HostState>,
+              method_name_ptr: i32,
+              method_name_len: i32,
+              format_type: i32,
+              params_ptr: i32,
+              params_len: i32,
+              ret_ptr: i32,
+              ret_capacity: i32| {
+            let registry = caller.data().registry.clone().unwrap_or_else(|| Arc::clone(&registry_v2));
+            Box::new(async move {
+                universal_invoke_v2_async(
+                    &registry,
+                    caller,
+                    method_name_ptr,
+                    method_name_len,
+                    format_type,
+                    params_ptr,
+                    params_len,
+                    ret_ptr,
+                    ret_capacity,
+                )
+                .await
+            })
+        },
+    )?;
+    linker.func_wrap(IMPORT_MODULE, TAKE_RESPONSE, take_response)?;
+    linker.func_wrap(IMPORT_MODULE, HOST_MALLOC, host_malloc)?;
+    linker.func_wrap(IMPORT_MODULE, HOST_FREE, host_free)?;
 
     Ok(linker)
 }
@@ -13,6 +13,9 @@
 //! - **Safe & Stable**: Leverages Rust's memory safety guarantees
 //! - **Configuration Management**: Supports dynamic configuration with hot reload
 //! - **Custom Host Methods**: Allows registering custom host functions that WASM modules can call
+//! - **Components**: Loads WebAssembly components with WASI preview2 next to core modules
+//! - **Typed Bindings**: Generates host traits and guest bindings for host methods from WIT files
+//! - **Guest SDK**: `no_std` crate for plugins with safe host calls, a host-backed allocator and entry points
 //! 
 //! ## Quick Start
 //! 
@@ -54,11 +57,126 @@
 //! ## Modules
 //! 
 //! - **wasm_manager**: Core functionality for managing WASM instances and hot reload
+//! - **async_manager**: Async instance cache for tokio hosts, with async host methods
 //! - **host_import**: Host functions imported by WASM modules
+//! - **component**: Components and the WIT interface of the host methods
+//! - **registry**: Host method registries, per cache or per module
+//! - **allocator**: Per-store allocator backing `host_malloc` and `host_free`
+//! - **limits**: Per-module fuel and timeout budgets, memory and table limits
+//! - **module_cache**: Content-hash keyed cache of compiled modules with on-disk artifacts
+//! - **pool**: Per-module pools of instances for concurrent calls
+//! - **events**: Subscriptions to reload events of WASM files and the configuration
+//! - **state**: Migration of guest state across hot reloads
+//! - **lifecycle**: Guest init, shutdown and health check exports
+//! - **wasi**: Per-module WASI sandbox (arguments, environment, preopens, stdio)
 //! - **config**: Configuration management with hot reload
 //! - **utils**: Utility functions for WASM memory management and serialization
+//! - **codec**: JSON, Bincode, Protobuf and FlatBuffers encoding for host methods
+//! - **error**: `DlinkError` and the stable status code table shared with guests
 
+pub mod allocator;
+pub mod async_manager;
+pub mod codec;
+pub mod component;
+pub mod events;
 pub mod host_import;
+pub mod lifecycle;
+pub mod limits;
+pub mod module_cache;
+pub mod pool;
+pub mod registry;
+pub mod state;
 pub mod utils;
 pub mod wasm_manager;
 pub mod config;
+pub mod wasi;
+pub mod error;
+
+/// # Host Bindings
+///
+/// Generates a `Host` trait and a `register` function for every interface of a WIT
+/// world; see the `dlink-wm-bindgen` crate for the type mapping and the wire format.
+/// Guests call the methods through `dlink_wm_guest::guest_bindings!`.
+///
+/// # Example
+///
+/// ```rust
+/// use dlink_wm::codec::Encode;
+/// use dlink_wm::host_import::SerializationFormat;
+/// use dlink_wm::registry::HostMethodRegistry;
+/// use std::sync::Arc;
+///
+/// dlink_wm::host_bindings!(inline = "
+///     package example:users
+///
+///     interface users {
+///       record user { id: u64, display-name: string }
+///       get-user: func(id: u64) -> option<user>
+///     }
+///
+///     world plugin { import users }
+/// ");
+///
+/// struct Users;
+///
+/// impl users::Host for Users {
+///     fn get_user(&self, id: u64) -> anyhow::Result<Option<users::User>> {
+///         Ok(Some(users::User { id, display_name: "WASM".to_string() }))
+///     }
+/// }
+///
+/// fn main() {
+///     let registry = HostMethodRegistry::new();
+///     users::register(&registry, Arc::new(Users));
+///     assert_eq!(users::METHODS, ["users.get-user"]);
+///     assert!(registry.contains("users.get-user"));
+///
+///     // Records keep their WIT field names on the wire
+///     let user = users::User { id: 1, display_name: "WASM".to_string() };
+///     let json = user.encode(SerializationFormat::Json).unwrap();
+///     assert_eq!(json, br#"{"id":1,"display-name":"WASM"}"#);
+/// }
+/// ```
+pub use dlink_wm_bindgen::host_bindings;
+
+/// # Host Method Attribute
+///
+/// Generates the `MethodHandler` and the `MethodSchema` of a host method from a
+/// Rust function; register it with `HostMethodRegistry::register_method`. See the
+/// `dlink-wm-bindgen` crate for how parameters are decoded.
+///
+/// # Example
+///
+/// ```rust
+/// use dlink_wm::dlink_host_method;
+/// use dlink_wm::host_import::HostContext;
+/// use dlink_wm::registry::HostMethodRegistry;
+/// use serde::Deserialize;
+///
+/// #[derive(Deserialize)]
+/// struct GreetParams {
+///     name: String,
+/// }
+///
+/// /// Greets a user
+/// #[dlink_host_method]
+/// fn greet(ctx: &mut HostContext<'_>, params: GreetParams) -> anyhow::Result<String> {
+///     Ok(format!("Hello from {}, {}!", ctx.module_path(), params.name))
+/// }
+///
+/// let registry = HostMethodRegistry::new();
+/// registry.register_method::<greet>();
+///
+/// let schema = registry.schema("greet").unwrap();
+/// assert_eq!(schema.params.len(), 1);
+/// assert_eq!(schema.params[0].ty, "GreetParams");
+/// assert_eq!(schema.returns, "String");
+/// ```
+pub use dlink_wm_bindgen::dlink_host_method;
+
+#[doc(hidden)]
+pub mod __private {
+    //! Crates used by the code of `host_bindings!`.
+    pub use anyhow;
+    pub use serde;
+}
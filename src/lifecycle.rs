@@ -0,0 +1,344 @@
+//! # Module Lifecycle
+//!
+//! This module defines the exports through which a guest takes part in its own
+//! setup and teardown. `WasmInstanceCache` calls them on every instance it creates
+//! or drops; a `HealthMonitor` calls the health check on a timer.
+//!
+//! ## Guest Exports
+//!
+//! All exports are optional:
+//!
+//! | Export | Signature | Called |
+//! |--------|-----------|--------|
+//! | `dlinkwm_init` | `(config_ptr: i32, len: i32) -> i32` | After the instance is created, before it is published; returns `0` on success |
+//! | `dlinkwm_shutdown` | `()` | Before an instance is dropped because it was evicted, replaced by a reload or recycled by its pool |
+//! | `dlinkwm_health` | `() -> i32` | Every `health_interval_ms` while a `HealthMonitor` runs; returns `0` when healthy |
+//!
+//! ## Init Configuration
+//!
+//! `dlinkwm_init` receives the `[modules."<path>".init]` table of the configuration
+//! as JSON. The bytes are allocated with the host allocator and freed when the
+//! export returns, so the guest must copy what it keeps. A module without an `init`
+//! table receives a null pointer and a length of `0`.
+//!
+//! ## Failures
+//!
+//! A nonzero status or a trap in `dlinkwm_init` discards the instance: a first load
+//! fails, and a hot reload keeps the previous version published. Failures of
+//! `dlinkwm_shutdown` are logged and the instance is dropped anyway. A failed health
+//! check is logged and emitted as an `Unhealthy` event; the instance stays in use.
+//!
+//! `AsyncWasmInstanceCache` does not call the lifecycle exports.
+
+use std::collections::HashMap;
+use std::sync::mpsc;
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+use wasmtime::{Instance, Store};
+use crate::config::DlinkWMConfig;
+use crate::error::{DlinkError, Result, STATUS_OK};
+use crate::events::{ReloadEvent, ReloadEventKind, ReloadTimer};
+use crate::host_import::{allocate_guest_memory, free_guest_memory, HostState};
+use crate::utils::write_wasm_memory;
+use crate::wasm_manager::{call_export, WasmInstanceCache};
+
+/// Export initializing a new instance
+pub const INIT_EXPORT: &str = "dlinkwm_init";
+/// Export releasing the resources of an instance before it is dropped
+pub const SHUTDOWN_EXPORT: &str = "dlinkwm_shutdown";
+/// Export reporting the health of an instance
+pub const HEALTH_EXPORT: &str = "dlinkwm_health";
+
+/// How long a `HealthMonitor` waits before looking for newly configured modules
+const IDLE_POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+/// # Health Status
+///
+/// Result of calling `dlinkwm_health` on an instance.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HealthStatus {
+    /// The guest reported `0`
+    Healthy,
+    /// The guest reported a nonzero status
+    Unhealthy {
+        /// Status returned by `dlinkwm_health`
+        status: i32,
+    },
+    /// The module does not export `dlinkwm_health`
+    Unsupported,
+}
+
+/// Calls `dlinkwm_init` with the module's init configuration.
+///
+/// The configuration and budgets are read from the configuration the store is
+/// bound to; stores created outside `WasmInstanceCache` get no init configuration.
+///
+/// # Errors
+///
+/// Returns `DlinkError::InitFailed` if the guest returns a nonzero status, or an
+/// error if the export has the wrong signature, the configuration cannot be
+/// written into guest memory, or the guest traps. Modules without the export
+/// always succeed.
+pub fn init(instance: Instance, store: &mut Store<HostState>) -> Result<()> {
+    if instance.get_func(&mut *store, INIT_EXPORT).is_none() {
+        return Ok(());
+    }
+    let config = store_config(store);
+    let init_config = match config.module_config(store.data().module_path()).init {
+        Some(init_config) => serde_json::to_vec(&init_config)?,
+        None => Vec::new(),
+    };
+
+    let len = u32::try_from(init_config.len()).map_err(|_| DlinkError::MemoryAccess { ptr: 0, len: init_config.len() })?;
+    let ptr = if len == 0 {
+        0
+    } else {
+        let memory = instance.get_memory(&mut *store, "memory").ok_or(DlinkError::MemoryNotFound)?;
+        let ptr = allocate_guest_memory(&mut *store, &memory, len).ok_or(DlinkError::MemoryAccess { ptr: 0, len: init_config.len() })?;
+        if let Err(e) = write_wasm_memory(&memory, &mut *store, ptr as i32, &init_config) {
+            free_guest_memory(&mut *store, ptr);
+            return Err(e);
+        }
+        ptr
+    };
+
+    let status: Result<i32> = call_export(instance, store, &config, INIT_EXPORT, (ptr as i32, len as i32));
+    if ptr != 0 {
+        free_guest_memory(&mut *store, ptr);
+    }
+    match status? {
+        STATUS_OK => Ok(()),
+        status => Err(DlinkError::InitFailed {
+            module: store.data().module_path().to_string(),
+            status,
+        }),
+    }
+}
+
+/// Calls `dlinkwm_shutdown`, if the module exports it.
+///
+/// # Errors
+///
+/// Returns an error if the export has the wrong signature or the guest traps.
+pub fn shutdown(instance: Instance, store: &mut Store<HostState>) -> Result<()> {
+    if instance.get_func(&mut *store, SHUTDOWN_EXPORT).is_none() {
+        return Ok(());
+    }
+    let config = store_config(store);
+    call_export(instance, store, &config, SHUTDOWN_EXPORT, ())
+}
+
+/// Calls `dlinkwm_health`, if the module exports it.
+///
+/// # Errors
+///
+/// Returns an error if the export has the wrong signature or the guest traps.
+pub fn health(instance: Instance, store: &mut Store<HostState>) -> Result<HealthStatus> {
+    if instance.get_func(&mut *store, HEALTH_EXPORT).is_none() {
+        return Ok(HealthStatus::Unsupported);
+    }
+    let config = store_config(store);
+    match call_export(instance, store, &config, HEALTH_EXPORT, ())? {
+        STATUS_OK => Ok(HealthStatus::Healthy),
+        status => Ok(HealthStatus::Unhealthy { status }),
+    }
+}
+
+/// Shuts an instance down before it is dropped, logging failures.
+pub(crate) fn shutdown_instance(instance: Instance, store: &mut Store<HostState>) {
+    if let Err(e) = shutdown(instance, store) {
+        log::warn!("[Lifecycle] {} of {} failed: {}", SHUTDOWN_EXPORT, store.data().module_path(), e);
+    }
+}
+
+/// Snapshots the configuration a store is bound to, so no lock is held while the
+/// guest runs.
+fn store_config(store: &Store<HostState>) -> DlinkWMConfig {
+    store
+        .data()
+        .config()
+        .map(|config| config.read().unwrap().clone())
+        .unwrap_or_default()
+}
+
+/// # Health Monitor
+///
+/// Periodically calls `dlinkwm_health` on the loaded modules of a
+/// `WasmInstanceCache`.
+///
+/// Each module is checked every `[modules."<path>".health_interval_ms]`; modules
+/// without an interval are not checked. Pooled modules have one of their
+/// instances checked. Failed checks are logged and emitted as `Unhealthy` events
+/// on the cache's `events()`.
+///
+/// The monitor runs in a background thread until the `HealthMonitorHandle`
+/// returned by `start` is stopped or dropped.
+///
+/// # Example
+///
+/// ```rust
+/// use dlink_wm::config::{DlinkWMConfig, ModuleConfig};
+/// use dlink_wm::events::ReloadEventKind;
+/// use dlink_wm::lifecycle::HealthMonitor;
+/// use dlink_wm::wasm_manager::WasmInstanceCache;
+/// use std::sync::{Arc, RwLock};
+/// use std::time::Duration;
+///
+/// // A guest that reports status 3 once it was asked to degrade
+/// let wasm_path = std::env::temp_dir().join("dlinkwm_health_monitor_doctest.wasm");
+/// std::fs::write(&wasm_path, r#"(module
+///     (global $status (mut i32) (i32.const 0))
+///     (func (export "degrade") (global.set $status (i32.const 3)))
+///     (func (export "dlinkwm_health") (result i32) (global.get $status)))"#).unwrap();
+/// let wasm_path = wasm_path.to_str().unwrap();
+///
+/// let mut config = DlinkWMConfig::default();
+/// config.entry_functions.insert(wasm_path.to_string(), vec!["degrade".to_string()]);
+/// config.modules.insert(wasm_path.to_string(), ModuleConfig {
+///     health_interval_ms: Some(20),
+///     ..Default::default()
+/// });
+/// let cache = Arc::new(WasmInstanceCache::with_config(Arc::new(RwLock::new(config))));
+/// let events = cache.events().subscribe();
+/// let handle = HealthMonitor::new(cache.clone()).start();
+///
+/// cache.call::<(), ()>(wasm_path, "degrade", ()).unwrap();
+/// let event = events.recv_timeout(Duration::from_secs(10)).unwrap();
+/// assert!(matches!(event.kind, ReloadEventKind::Unhealthy { .. }));
+/// handle.stop();
+/// ```
+pub struct HealthMonitor {
+    /// Cache whose loaded modules are checked
+    instance_cache: Arc<WasmInstanceCache>,
+}
+
+impl HealthMonitor {
+    /// Creates a health monitor for the modules of a cache.
+    ///
+    /// # Parameters
+    ///
+    /// - `instance_cache`: Cache whose loaded modules are checked
+    pub fn new(instance_cache: Arc<WasmInstanceCache>) -> Self {
+        Self { instance_cache }
+    }
+
+    /// Starts the health check thread.
+    ///
+    /// The intervals are read from the cache's configuration on every round, so
+    /// configuration reloads apply without restarting the monitor. A module is
+    /// first checked one interval after the monitor sees it loaded.
+    ///
+    /// # Returns
+    ///
+    /// A `HealthMonitorHandle`; checking stops when it is stopped or dropped.
+    pub fn start(&self) -> HealthMonitorHandle {
+        let (tx, rx) = mpsc::channel::<()>();
+        let instance_cache = Arc::clone(&self.instance_cache);
+        let thread = thread::spawn(move || {
+            // Time of the last check of each monitored module
+            let mut last_checked: HashMap<String, Instant> = HashMap::new();
+            loop {
+                let intervals: Vec<(String, Duration)> = {
+                    let config = instance_cache.config();
+                    let config = config.read().unwrap();
+                    instance_cache
+                        .loaded_paths()
+                        .into_iter()
+                        .filter_map(|wasm_path| {
+                            let interval_ms = config.module_config(&wasm_path).health_interval_ms?;
+                            Some((wasm_path, Duration::from_millis(interval_ms.max(1))))
+                        })
+                        .collect()
+                };
+                last_checked.retain(|wasm_path, _| intervals.iter().any(|(monitored, _)| monitored == wasm_path));
+
+                let mut next_round = Instant::now() + IDLE_POLL_INTERVAL;
+                for (wasm_path, interval) in intervals {
+                    let last = *last_checked.entry(wasm_path.clone()).or_insert_with(Instant::now);
+                    let mut due = last + interval;
+                    if due <= Instant::now() {
+                        check_module(&instance_cache, &wasm_path);
+                        let now = Instant::now();
+                        last_checked.insert(wasm_path, now);
+                        due = now + interval;
+                    }
+                    next_round = next_round.min(due);
+                }
+
+                match rx.recv_timeout(next_round.saturating_duration_since(Instant::now())) {
+                    Err(mpsc::RecvTimeoutError::Timeout) => {},
+                    Ok(()) | Err(mpsc::RecvTimeoutError::Disconnected) => break,
+                }
+            }
+            log::info!("[Lifecycle] Stopped health checks");
+        });
+
+        log::info!("[Lifecycle] Started health checks");
+        HealthMonitorHandle {
+            sender: Some(tx),
+            thread: Some(thread),
+        }
+    }
+}
+
+/// Checks the health of a module and reports failures.
+fn check_module(instance_cache: &WasmInstanceCache, wasm_path: &str) {
+    let timer = ReloadTimer::start();
+    let error = match instance_cache.check_health(wasm_path) {
+        Ok(HealthStatus::Healthy) | Ok(HealthStatus::Unsupported) => return,
+        Ok(HealthStatus::Unhealthy { status }) => format!("{} returned status {}", HEALTH_EXPORT, status),
+        Err(e) => e.to_string(),
+    };
+    log::warn!("[Lifecycle] Health check of {} failed: {}", wasm_path, error);
+    let hash = instance_cache.loaded_hash(wasm_path);
+    instance_cache.events().emit(ReloadEvent {
+        kind: ReloadEventKind::Unhealthy { error },
+        path: wasm_path.to_string(),
+        old_hash: hash.clone(),
+        new_hash: hash,
+        timings: timer.finish(),
+    });
+}
+
+/// # Health Monitor Handle
+///
+/// Keeps a `HealthMonitor` running. Checking stops when the handle is stopped or
+/// dropped.
+#[must_use = "the health monitor stops when its handle is dropped"]
+pub struct HealthMonitorHandle {
+    /// Wakes the health check thread to stop it
+    sender: Option<mpsc::Sender<()>>,
+    /// The health check thread
+    thread: Option<thread::JoinHandle<()>>,
+}
+
+impl HealthMonitorHandle {
+    /// Stops the health checks and waits for the thread to exit, including a check
+    /// it is running.
+    pub fn stop(mut self) {
+        self.shutdown();
+    }
+
+    /// Checks whether the health check thread is still running.
+    pub fn is_running(&self) -> bool {
+        self.thread.as_ref().is_some_and(|thread| !thread.is_finished())
+    }
+
+    fn shutdown(&mut self) {
+        if let Some(sender) = self.sender.take() {
+            let _ = sender.send(());
+        }
+        if let Some(thread) = self.thread.take() {
+            if thread.join().is_err() {
+                log::error!("[Lifecycle] Health check thread panicked");
+            }
+        }
+    }
+}
+
+impl Drop for HealthMonitorHandle {
+    fn drop(&mut self) {
+        self.shutdown();
+    }
+}
@@ -0,0 +1,447 @@
+//! # Execution Limits
+//!
+//! This module enforces the per-module budgets configured under `[modules]`.
+//!
+//! **CPU**: Every engine created by DlinkWM has fuel consumption and epoch
+//! interruption enabled. Before each call, `ExecutionBudget` refuels the store and,
+//! when a wall-clock timeout is configured, records the call's deadline in the
+//! store and starts a watchdog thread that ticks the engine's epoch once the
+//! deadline passes. Engines are shared by many stores, so a tick only interrupts
+//! the stores whose own deadline has passed; the others keep running. A guest that
+//! runs out of budget traps and the call fails with `DlinkError::OutOfFuel` or
+//! `DlinkError::Timeout`.
+//!
+//! Async engines (see `AsyncWasmInstanceCache`) are ticked by an `EpochTicker`
+//! instead. Each tick makes a running guest yield to the executor, so long calls
+//! do not starve other tasks, and interrupts it once its deadline has passed.
+//!
+//! **Resources**: Every store has a `ModuleLimiter`, wasmtime's `ResourceLimiter`
+//! hook, that caps linear memory size, table size and the number of instances and
+//! memories. Denied growth makes `memory.grow` return `-1` as the WASM specification
+//! requires; the limiter remembers the denial so that a call failing because of it
+//! reports `DlinkError::ResourceLimitExceeded`.
+
+use std::sync::mpsc::{channel, RecvTimeoutError, Sender};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+use wasmtime::{Engine, Module, ResourceLimiter, Store, StoreContextMut, Trap, UpdateDeadline};
+use crate::allocator::WASM_PAGE_SIZE;
+use crate::config::ModuleConfig;
+use crate::error::{DlinkError, Result};
+use crate::host_import::HostState;
+
+/// Fuel given to calls without a fuel budget, enough for days of guest execution.
+///
+/// Wasmtime keeps a lifetime total of the fuel added to a store in an `i64`, so
+/// "unlimited" has to stay well below `i64::MAX`.
+pub const UNLIMITED_FUEL: u64 = 1 << 52;
+
+/// Epoch deadline of calls without a timeout, far beyond any reachable epoch.
+const NO_DEADLINE: u64 = u64::MAX / 2;
+
+/// # Execution Budget
+///
+/// CPU budget of a single call: a fuel allowance, a wall-clock timeout, both or
+/// neither.
+///
+/// # Example
+///
+/// ```rust
+/// use dlink_wm::config::ModuleConfig;
+/// use dlink_wm::limits::ExecutionBudget;
+/// use std::time::Duration;
+///
+/// let budget = ExecutionBudget::from_config(&ModuleConfig {
+///     fuel: Some(1_000_000),
+///     timeout_ms: Some(250),
+///     ..Default::default()
+/// });
+/// assert_eq!(budget.fuel, Some(1_000_000));
+/// assert_eq!(budget.timeout, Some(Duration::from_millis(250)));
+/// assert!(ExecutionBudget::unlimited().is_unlimited());
+/// ```
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ExecutionBudget {
+    /// Fuel units the call may consume, `None` for no limit
+    pub fuel: Option<u64>,
+    /// Wall-clock time the call may take, `None` for no limit
+    pub timeout: Option<Duration>,
+}
+
+impl ExecutionBudget {
+    /// Creates a budget without any limit.
+    pub fn unlimited() -> Self {
+        Self::default()
+    }
+
+    /// Creates the budget described by a module's configuration.
+    pub fn from_config(config: &ModuleConfig) -> Self {
+        Self {
+            fuel: config.fuel,
+            timeout: config.timeout_ms.map(Duration::from_millis),
+        }
+    }
+
+    /// Checks whether the budget sets no limit at all.
+    pub fn is_unlimited(&self) -> bool {
+        self.fuel.is_none() && self.timeout.is_none()
+    }
+
+    /// Prepares a store for a call under this budget.
+    ///
+    /// The store's fuel is set to the budget: fuel left over from the previous call
+    /// is discarded, fuel it consumed is replaced. When a timeout is set, a watchdog
+    /// is started that ticks the store's engine once the timeout elapses.
+    ///
+    /// # Returns
+    ///
+    /// The watchdog of the call. Keep it alive for the duration of the call; dropping
+    /// it cancels the timeout.
+    ///
+    /// # Errors
+    ///
+    /// Returns `DlinkError::Wasm` if the store's engine was created without fuel
+    /// consumption.
+    pub(crate) fn apply(&self, store: &mut Store<HostState>) -> Result<Watchdog> {
+        self.refuel(store)?;
+        match self.timeout {
+            Some(timeout) => {
+                store.data_mut().set_call_deadline(Some(Instant::now() + timeout));
+                store.set_epoch_deadline(1);
+                Ok(Watchdog::start(store.engine().clone(), timeout))
+            },
+            None => {
+                store.data_mut().set_call_deadline(None);
+                store.set_epoch_deadline(NO_DEADLINE);
+                Ok(Watchdog::default())
+            },
+        }
+    }
+
+    /// Prepares a store of an async engine for a call under this budget.
+    ///
+    /// Like `apply`, but the store yields on every epoch tick and no watchdog is
+    /// needed: the engine's `EpochTicker` lets `check_call_deadline_async` see the
+    /// deadline pass.
+    ///
+    /// # Errors
+    ///
+    /// Returns `DlinkError::Wasm` if the store's engine was created without fuel
+    /// consumption.
+    pub(crate) fn apply_async(&self, store: &mut Store<HostState>) -> Result<()> {
+        self.refuel(store)?;
+        store.data_mut().set_call_deadline(self.timeout.map(|timeout| Instant::now() + timeout));
+        store.set_epoch_deadline(1);
+        Ok(())
+    }
+
+    /// Sets the store's fuel to the budget.
+    fn refuel(&self, store: &mut Store<HostState>) -> Result<()> {
+        // Only add the fuel that is missing, wasmtime never forgets added fuel.
+        // Consuming nothing fails when the previous call overdrew its fuel.
+        let target = self.fuel.unwrap_or(UNLIMITED_FUEL);
+        let remaining = store.consume_fuel(0).unwrap_or(0);
+        if remaining > target {
+            store.consume_fuel(remaining - target)?;
+        } else if remaining < target {
+            store.add_fuel(target - remaining)?;
+        }
+        Ok(())
+    }
+
+    /// Converts the error of a call made under this budget into a `DlinkError`.
+    ///
+    /// Fuel exhaustion and epoch interruption become `DlinkError::OutOfFuel` and
+    /// `DlinkError::Timeout`; any other error becomes `DlinkError::Trap`.
+    pub fn map_call_error(&self, function: &str, error: anyhow::Error) -> DlinkError {
+        self.exceeded(function, &error).unwrap_or(DlinkError::Trap(error))
+    }
+
+    /// Gets the budget error for a call error, if the call failed by running out of budget.
+    pub fn exceeded(&self, function: &str, error: &anyhow::Error) -> Option<DlinkError> {
+        let exceeded = match error.downcast_ref::<Trap>()? {
+            Trap::OutOfFuel => DlinkError::OutOfFuel {
+                function: function.to_string(),
+                fuel: self.fuel.unwrap_or(UNLIMITED_FUEL),
+            },
+            Trap::Interrupt => DlinkError::Timeout {
+                function: function.to_string(),
+                timeout_ms: self.timeout.map_or(0, |timeout| timeout.as_millis() as u64),
+            },
+            _ => return None,
+        };
+        log::warn!("[Limits] {}", exceeded);
+        Some(exceeded)
+    }
+}
+
+/// Epoch deadline callback installed on every store.
+///
+/// Traps the store if its current call is past its deadline, otherwise waits for
+/// the next tick.
+pub(crate) fn check_call_deadline(store: StoreContextMut<'_, HostState>) -> anyhow::Result<UpdateDeadline> {
+    match store.data().call_deadline() {
+        Some(deadline) if Instant::now() >= deadline => Err(Trap::Interrupt.into()),
+        _ => Ok(UpdateDeadline::Continue(1)),
+    }
+}
+
+/// Epoch deadline callback installed on every store of an async engine.
+///
+/// Traps the store if its current call is past its deadline, otherwise yields to
+/// the executor until the next tick.
+pub(crate) fn check_call_deadline_async(store: StoreContextMut<'_, HostState>) -> anyhow::Result<UpdateDeadline> {
+    match store.data().call_deadline() {
+        Some(deadline) if Instant::now() >= deadline => Err(Trap::Interrupt.into()),
+        _ => Ok(UpdateDeadline::Yield(1)),
+    }
+}
+
+/// # Call Watchdog
+///
+/// Background thread that increments an engine's epoch when a call exceeds its
+/// timeout. Dropping the watchdog cancels it and waits for the thread to exit.
+#[derive(Default)]
+pub(crate) struct Watchdog {
+    /// Dropped to cancel the watchdog
+    cancel: Option<Sender<()>>,
+    /// Watchdog thread
+    thread: Option<JoinHandle<()>>,
+}
+
+impl Watchdog {
+    /// Starts a watchdog that interrupts `engine` after `timeout`.
+    fn start(engine: Engine, timeout: Duration) -> Self {
+        let (cancel, cancelled) = channel::<()>();
+        let thread = thread::spawn(move || {
+            if let Err(RecvTimeoutError::Timeout) = cancelled.recv_timeout(timeout) {
+                engine.increment_epoch();
+            }
+        });
+        Self {
+            cancel: Some(cancel),
+            thread: Some(thread),
+        }
+    }
+}
+
+impl Drop for Watchdog {
+    fn drop(&mut self) {
+        drop(self.cancel.take());
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+/// # Epoch Ticker
+///
+/// Background thread that increments an engine's epoch at a fixed interval.
+/// Dropping the ticker stops it and waits for the thread to exit.
+pub(crate) struct EpochTicker {
+    /// Dropped to stop the ticker
+    stop: Option<Sender<()>>,
+    /// Ticker thread
+    thread: Option<JoinHandle<()>>,
+}
+
+impl EpochTicker {
+    /// Starts ticking `engine` every `interval`.
+    pub(crate) fn start(engine: Engine, interval: Duration) -> Self {
+        let (stop, stopped) = channel::<()>();
+        let thread = thread::spawn(move || {
+            while let Err(RecvTimeoutError::Timeout) = stopped.recv_timeout(interval) {
+                engine.increment_epoch();
+            }
+        });
+        Self {
+            stop: Some(stop),
+            thread: Some(thread),
+        }
+    }
+}
+
+impl Drop for EpochTicker {
+    fn drop(&mut self) {
+        drop(self.stop.take());
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+/// # Resource Limits
+///
+/// Memory, table and instance limits of a store. `None` leaves a resource at
+/// wasmtime's default limit.
+///
+/// # Example
+///
+/// ```rust
+/// use dlink_wm::config::ModuleConfig;
+/// use dlink_wm::limits::{ModuleLimiter, ResourceLimits};
+/// use wasmtime::ResourceLimiter;
+///
+/// let limits = ResourceLimits::from_config(&ModuleConfig {
+///     max_memory_pages: Some(2),
+///     ..Default::default()
+/// });
+/// let mut limiter = ModuleLimiter::new(limits);
+///
+/// // Growing to two pages is allowed, growing to three is denied and remembered
+/// assert!(limiter.memory_growing(65536, 2 * 65536, None).unwrap());
+/// assert!(!limiter.memory_growing(2 * 65536, 3 * 65536, None).unwrap());
+/// assert!(limiter.take_violation().is_some());
+/// assert!(limiter.take_violation().is_none());
+/// ```
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ResourceLimits {
+    /// Maximum size of each linear memory in WASM pages
+    pub max_memory_pages: Option<u64>,
+    /// Maximum number of elements in each table
+    pub max_table_elements: Option<u32>,
+    /// Maximum number of instances in the store
+    pub max_instances: Option<usize>,
+    /// Maximum number of linear memories in the store
+    pub max_memories: Option<usize>,
+}
+
+impl ResourceLimits {
+    /// Creates the limits described by a module's configuration.
+    pub fn from_config(config: &ModuleConfig) -> Self {
+        Self {
+            max_memory_pages: config.max_memory_pages,
+            max_table_elements: config.max_table_elements,
+            max_instances: config.max_instances,
+            max_memories: config.max_memories,
+        }
+    }
+
+    /// Checks whether no limit is set.
+    pub fn is_unlimited(&self) -> bool {
+        *self == Self::default()
+    }
+}
+
+/// # Module Limiter
+///
+/// `ResourceLimiter` of a store, enforcing its `ResourceLimits`.
+///
+/// Instance and memory counts are fixed when the limiter is installed on a store;
+/// memory and table sizes are checked against the current limits on every growth.
+#[derive(Debug, Default)]
+pub struct ModuleLimiter {
+    /// Limits enforced by the limiter
+    limits: ResourceLimits,
+    /// Description of the last denied growth, if not yet reported
+    violation: Option<String>,
+}
+
+impl ModuleLimiter {
+    /// Creates a limiter enforcing `limits`.
+    pub fn new(limits: ResourceLimits) -> Self {
+        Self {
+            limits,
+            violation: None,
+        }
+    }
+
+    /// Gets the limits enforced by the limiter.
+    pub fn limits(&self) -> ResourceLimits {
+        self.limits
+    }
+
+    /// Replaces the memory and table size limits.
+    ///
+    /// Count limits only change when the limiter is installed on a new store.
+    pub fn set_limits(&mut self, limits: ResourceLimits) {
+        self.limits = limits;
+    }
+
+    /// Takes the description of the last denied growth.
+    pub fn take_violation(&mut self) -> Option<String> {
+        self.violation.take()
+    }
+
+    /// Checks that a module can be instantiated within the count and size limits.
+    ///
+    /// # Errors
+    ///
+    /// Returns `DlinkError::ResourceLimitExceeded` if the module defines more
+    /// memories than allowed or starts with a memory or table above the size limits.
+    pub fn check_module(&self, module_path: &str, module: &Module) -> Result<()> {
+        let required = module.resources_required();
+        let violations = [
+            (self.limits.max_instances == Some(0)).then(|| "instance limit is 0".to_string()),
+            self.limits
+                .max_memories
+                .filter(|&max| required.num_memories as usize > max)
+                .map(|max| format!("module defines {} memories, limit is {}", required.num_memories, max)),
+            required
+                .max_initial_memory_size
+                .zip(self.limits.max_memory_pages)
+                .filter(|(initial, max)| initial > max)
+                .map(|(initial, max)| format!("initial memory of {} pages exceeds the limit of {} pages", initial, max)),
+            required
+                .max_initial_table_size
+                .zip(self.limits.max_table_elements)
+                .filter(|(initial, max)| initial > max)
+                .map(|(initial, max)| format!("initial table of {} elements exceeds the limit of {} elements", initial, max)),
+        ];
+
+        match violations.into_iter().flatten().next() {
+            Some(detail) => {
+                log::warn!("[Limits] WASM module '{}' cannot be instantiated: {}", module_path, detail);
+                Err(DlinkError::ResourceLimitExceeded {
+                    module: module_path.to_string(),
+                    detail,
+                })
+            },
+            None => Ok(()),
+        }
+    }
+
+    /// Records and logs a denied growth.
+    fn deny(&mut self, detail: String) {
+        log::warn!("[Limits] {}", detail);
+        self.violation = Some(detail);
+    }
+}
+
+impl ResourceLimiter for ModuleLimiter {
+    fn memory_growing(&mut self, current: usize, desired: usize, _maximum: Option<usize>) -> anyhow::Result<bool> {
+        if let Some(max_pages) = self.limits.max_memory_pages {
+            if desired as u64 > max_pages.saturating_mul(WASM_PAGE_SIZE) {
+                self.deny(format!(
+                    "memory growth from {} to {} pages denied, limit is {} pages",
+                    current as u64 / WASM_PAGE_SIZE,
+                    (desired as u64).div_ceil(WASM_PAGE_SIZE),
+                    max_pages
+                ));
+                return Ok(false);
+            }
+        }
+        Ok(true)
+    }
+
+    fn table_growing(&mut self, current: u32, desired: u32, _maximum: Option<u32>) -> anyhow::Result<bool> {
+        if let Some(max_elements) = self.limits.max_table_elements {
+            if desired > max_elements {
+                self.deny(format!(
+                    "table growth from {} to {} elements denied, limit is {} elements",
+                    current, desired, max_elements
+                ));
+                return Ok(false);
+            }
+        }
+        Ok(true)
+    }
+
+    fn instances(&self) -> usize {
+        self.limits.max_instances.unwrap_or(wasmtime::DEFAULT_INSTANCE_LIMIT)
+    }
+
+    fn memories(&self) -> usize {
+        self.limits.max_memories.unwrap_or(wasmtime::DEFAULT_MEMORY_LIMIT)
+    }
+}
@@ -0,0 +1,505 @@
+//! # Compiled Module Cache
+//!
+//! This module caches compiled WASM modules by the SHA-256 of their bytes, so a
+//! module is compiled once no matter how many paths point at the same content.
+//!
+//! Compiled modules are kept in memory and, when a cache directory is configured,
+//! serialized to `<sha256>-<engine>.cwasm` artifacts with `Module::serialize`. The
+//! `<engine>` tag identifies the engine settings, so caches with different
+//! settings can share a directory. Artifacts survive process restarts and are
+//! evicted by age and total size, least recently used first. Modules in memory are
+//! evicted by age and count, and `forget` drops the module of a released version.
+//!
+//! Components are cached the same way through `get_or_compile_component`; the
+//! content hash tells core modules and components apart.
+
+use std::collections::HashMap;
+use std::fs::{self, File};
+use std::hash::{Hash, Hasher};
+use std::path::{Path, PathBuf};
+use std::sync::RwLock;
+use std::time::{Duration, SystemTime};
+use sha2::{Digest, Sha256};
+use wasmtime::component::Component;
+use wasmtime::{Engine, Module};
+use crate::config::ModuleCacheConfig;
+use crate::error::Result;
+
+/// File extension of serialized module artifacts.
+pub const ARTIFACT_EXTENSION: &str = "cwasm";
+
+/// Feeds `Hash` values into a SHA-256, whose output is stable across Rust releases.
+struct Sha256Hasher(Sha256);
+
+impl Hasher for Sha256Hasher {
+    fn write(&mut self, bytes: &[u8]) {
+        self.0.update(bytes);
+    }
+
+    fn finish(&self) -> u64 {
+        let digest = self.0.clone().finalize();
+        u64::from_be_bytes(digest[..8].try_into().expect("a SHA-256 digest has 32 bytes"))
+    }
+}
+
+/// # Module Cache Entry
+///
+/// Description of a cached module, as returned by `ModuleCache::entries`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ModuleCacheEntry {
+    /// SHA-256 of the WASM bytes, in lowercase hex
+    pub hash: String,
+    /// Whether the compiled module is loaded in memory
+    pub in_memory: bool,
+    /// Path of the serialized artifact, if one exists on disk
+    pub artifact: Option<PathBuf>,
+    /// Size of the artifact in bytes (0 without an artifact)
+    pub size: u64,
+    /// When the module was last compiled or loaded
+    pub last_used: SystemTime,
+}
+
+/// A compiled core module or component.
+#[derive(Clone)]
+enum CachedCode {
+    Module(Module),
+    Component(Component),
+}
+
+/// Compiled code the cache can hold.
+trait Compiled: Clone + Sized {
+    /// Compiles WASM binary or text.
+    fn compile(engine: &Engine, wasm_bytes: &[u8]) -> anyhow::Result<Self>;
+    /// Loads an artifact written by `serialize`.
+    ///
+    /// # Safety
+    ///
+    /// The file must have been written by `serialize` for a compatible engine.
+    unsafe fn deserialize_file(engine: &Engine, path: &Path) -> anyhow::Result<Self>;
+    /// Serializes the compiled code into an artifact.
+    fn serialize(&self) -> anyhow::Result<Vec<u8>>;
+    /// Wraps the code for the cache.
+    fn cached(self) -> CachedCode;
+    /// Gets the code from a cache entry, if it has this kind.
+    fn from_cached(cached: &CachedCode) -> Option<Self>;
+}
+
+impl Compiled for Module {
+    fn compile(engine: &Engine, wasm_bytes: &[u8]) -> anyhow::Result<Self> {
+        Module::new(engine, wasm_bytes)
+    }
+
+    unsafe fn deserialize_file(engine: &Engine, path: &Path) -> anyhow::Result<Self> {
+        Module::deserialize_file(engine, path)
+    }
+
+    fn serialize(&self) -> anyhow::Result<Vec<u8>> {
+        Module::serialize(self)
+    }
+
+    fn cached(self) -> CachedCode {
+        CachedCode::Module(self)
+    }
+
+    fn from_cached(cached: &CachedCode) -> Option<Self> {
+        match cached {
+            CachedCode::Module(module) => Some(module.clone()),
+            CachedCode::Component(_) => None,
+        }
+    }
+}
+
+impl Compiled for Component {
+    fn compile(engine: &Engine, wasm_bytes: &[u8]) -> anyhow::Result<Self> {
+        Component::new(engine, wasm_bytes)
+    }
+
+    unsafe fn deserialize_file(engine: &Engine, path: &Path) -> anyhow::Result<Self> {
+        Component::deserialize_file(engine, path)
+    }
+
+    fn serialize(&self) -> anyhow::Result<Vec<u8>> {
+        Component::serialize(self)
+    }
+
+    fn cached(self) -> CachedCode {
+        CachedCode::Component(self)
+    }
+
+    fn from_cached(cached: &CachedCode) -> Option<Self> {
+        match cached {
+            CachedCode::Component(component) => Some(component.clone()),
+            CachedCode::Module(_) => None,
+        }
+    }
+}
+
+/// # Module Cache
+///
+/// Thread-safe cache of compiled modules for one engine, keyed by content hash.
+///
+/// # Example
+///
+/// ```rust
+/// use dlink_wm::config::ModuleCacheConfig;
+/// use dlink_wm::module_cache::ModuleCache;
+/// use wasmtime::Engine;
+///
+/// let dir = std::env::temp_dir().join("dlinkwm_module_cache_doctest");
+/// let config = ModuleCacheConfig {
+///     dir: Some(dir.to_string_lossy().to_string()),
+///     ..Default::default()
+/// };
+/// let wasm = br#"(module (func (export "answer") (result i32) i32.const 42))"#;
+///
+/// let cache = ModuleCache::new(Engine::default(), &config);
+/// let (hash, _module) = cache.get_or_compile(wasm).unwrap();
+/// assert_eq!(hash, ModuleCache::content_hash(wasm));
+///
+/// // A new cache on the same directory loads the artifact instead of compiling
+/// let restarted = ModuleCache::new(Engine::default(), &config);
+/// restarted.get_or_compile(wasm).unwrap();
+/// let entry = restarted.entries().unwrap().into_iter().find(|e| e.hash == hash).unwrap();
+/// assert!(entry.in_memory && entry.artifact.is_some());
+///
+/// restarted.clear().unwrap();
+/// assert!(restarted.entries().unwrap().is_empty());
+/// ```
+pub struct ModuleCache {
+    /// Engine the modules are compiled for
+    engine: Engine,
+    /// Directory of serialized artifacts, `None` for a memory-only cache
+    dir: Option<PathBuf>,
+    /// Tag of the engine settings, part of every artifact name
+    engine_tag: String,
+    /// Maximum total size of the artifacts
+    max_bytes: Option<u64>,
+    /// Maximum time since a module was last used
+    max_age: Option<Duration>,
+    /// Maximum number of compiled modules in memory
+    max_in_memory: Option<usize>,
+    /// Compiled modules and components in memory, with the time they were last used
+    modules: RwLock<HashMap<String, (CachedCode, SystemTime)>>,
+}
+
+impl ModuleCache {
+    /// Creates a module cache for `engine`.
+    ///
+    /// If the cache directory cannot be created, the error is logged and the cache
+    /// works in memory only.
+    ///
+    /// # Parameters
+    ///
+    /// - `engine`: Engine the modules are compiled for
+    /// - `config`: Cache directory and eviction settings
+    pub fn new(engine: Engine, config: &ModuleCacheConfig) -> Self {
+        let dir = config.dir.as_ref().map(PathBuf::from).filter(|dir| match fs::create_dir_all(dir) {
+            Ok(()) => true,
+            Err(e) => {
+                log::error!("[ModuleCache] Cannot create cache directory {}: {}", dir.display(), e);
+                false
+            },
+        });
+        // `DefaultHasher` may change between Rust releases, which would orphan the artifacts
+        let mut hasher = Sha256Hasher(Sha256::new());
+        engine.precompile_compatibility_hash().hash(&mut hasher);
+        let engine_tag = hasher.0.finalize()[..8].iter().map(|byte| format!("{:02x}", byte)).collect();
+        Self {
+            engine,
+            dir,
+            engine_tag,
+            max_bytes: config.max_bytes,
+            max_age: config.max_age_secs.map(Duration::from_secs),
+            max_in_memory: config.max_in_memory,
+            modules: RwLock::new(HashMap::new()),
+        }
+    }
+
+    /// Computes the cache key of WASM bytes: their SHA-256 in lowercase hex.
+    pub fn content_hash(wasm_bytes: &[u8]) -> String {
+        Sha256::digest(wasm_bytes).iter().map(|byte| format!("{:02x}", byte)).collect()
+    }
+
+    /// Gets the directory of serialized artifacts, if any.
+    pub fn dir(&self) -> Option<&Path> {
+        self.dir.as_deref()
+    }
+
+    /// Gets the compiled module for WASM bytes, compiling it only if neither the
+    /// memory cache nor the artifact directory has it.
+    ///
+    /// # Parameters
+    ///
+    /// - `wasm_bytes`: WASM binary or text
+    ///
+    /// # Returns
+    ///
+    /// The content hash of the bytes and the compiled module.
+    ///
+    /// # Errors
+    ///
+    /// Returns `DlinkError::Wasm` if the module cannot be compiled. Failures to read
+    /// or write artifacts are logged and fall back to compiling.
+    pub fn get_or_compile(&self, wasm_bytes: &[u8]) -> Result<(String, Module)> {
+        self.get_or_compile_code(wasm_bytes)
+    }
+
+    /// Gets the compiled component for WASM bytes, like `get_or_compile` does for
+    /// core modules.
+    ///
+    /// # Parameters
+    ///
+    /// - `wasm_bytes`: Component binary or text
+    ///
+    /// # Returns
+    ///
+    /// The content hash of the bytes and the compiled component.
+    ///
+    /// # Errors
+    ///
+    /// Returns `DlinkError::Wasm` if the component cannot be compiled.
+    pub fn get_or_compile_component(&self, wasm_bytes: &[u8]) -> Result<(String, Component)> {
+        self.get_or_compile_code(wasm_bytes)
+    }
+
+    /// Gets compiled code from memory, from its artifact or by compiling it.
+    fn get_or_compile_code<C: Compiled>(&self, wasm_bytes: &[u8]) -> Result<(String, C)> {
+        let hash = Self::content_hash(wasm_bytes);
+        if let Some((cached, last_used)) = self.modules.write().unwrap().get_mut(&hash) {
+            if let Some(code) = C::from_cached(cached) {
+                *last_used = SystemTime::now();
+                return Ok((hash, code));
+            }
+        }
+
+        let code = match self.load_artifact::<C>(&hash) {
+            Some(code) => code,
+            None => {
+                log::info!("[ModuleCache] Compiling module {}", hash);
+                let code = C::compile(&self.engine, wasm_bytes)?;
+                self.store_artifact(&hash, &code);
+                code
+            },
+        };
+        self.modules.write().unwrap().insert(hash.clone(), (code.clone().cached(), SystemTime::now()));
+        if let Err(e) = self.evict() {
+            log::warn!("[ModuleCache] Eviction failed: {}", e);
+        }
+        Ok((hash, code))
+    }
+
+    /// Lists the cached modules, in memory and on disk.
+    ///
+    /// # Errors
+    ///
+    /// Returns `DlinkError::Io` if the artifact directory cannot be read.
+    pub fn entries(&self) -> Result<Vec<ModuleCacheEntry>> {
+        let mut entries: HashMap<String, ModuleCacheEntry> = self
+            .modules
+            .read()
+            .unwrap()
+            .iter()
+            .map(|(hash, (_, last_used))| {
+                (hash.clone(), ModuleCacheEntry {
+                    hash: hash.clone(),
+                    in_memory: true,
+                    artifact: None,
+                    size: 0,
+                    last_used: *last_used,
+                })
+            })
+            .collect();
+
+        for (hash, path, metadata) in self.artifacts()? {
+            let last_used = metadata.modified()?;
+            let entry = entries.entry(hash.clone()).or_insert_with(|| ModuleCacheEntry {
+                hash,
+                in_memory: false,
+                artifact: None,
+                size: 0,
+                last_used,
+            });
+            entry.artifact = Some(path);
+            entry.size = metadata.len();
+            entry.last_used = entry.last_used.max(last_used);
+        }
+
+        let mut entries: Vec<_> = entries.into_values().collect();
+        // Most recently used first
+        entries.sort_by_key(|entry| std::cmp::Reverse(entry.last_used));
+        Ok(entries)
+    }
+
+    /// Evicts modules unused for longer than `max_age_secs`, then the least
+    /// recently used modules in memory until at most `max_in_memory` remain, and
+    /// the least recently used artifacts until their total size is within
+    /// `max_bytes`.
+    ///
+    /// # Returns
+    ///
+    /// The number of evicted modules and artifacts.
+    ///
+    /// # Errors
+    ///
+    /// Returns `DlinkError::Io` if the artifact directory cannot be read or an
+    /// artifact cannot be removed.
+    pub fn evict(&self) -> Result<usize> {
+        let now = SystemTime::now();
+        let expired = |last_used: SystemTime| {
+            self.max_age
+                .is_some_and(|max_age| now.duration_since(last_used).unwrap_or_default() > max_age)
+        };
+        let mut evicted = 0;
+
+        self.modules.write().unwrap().retain(|hash, (_, last_used)| {
+            let keep = !expired(*last_used);
+            if !keep {
+                log::info!("[ModuleCache] Evicting expired module {} from memory", hash);
+                evicted += 1;
+            }
+            keep
+        });
+
+        if let Some(max_in_memory) = self.max_in_memory {
+            let mut modules = self.modules.write().unwrap();
+            if modules.len() > max_in_memory {
+                let mut by_age: Vec<_> = modules.iter().map(|(hash, (_, last_used))| (*last_used, hash.clone())).collect();
+                by_age.sort();
+                for (_, hash) in by_age.into_iter().take(modules.len() - max_in_memory) {
+                    log::info!("[ModuleCache] Evicting module {} from memory", hash);
+                    modules.remove(&hash);
+                    evicted += 1;
+                }
+            }
+        }
+
+        // Oldest first
+        let mut artifacts = Vec::new();
+        for (hash, path, metadata) in self.artifacts()? {
+            artifacts.push((metadata.modified()?, metadata.len(), hash, path));
+        }
+        artifacts.sort();
+        let mut total_bytes: u64 = artifacts.iter().map(|(_, size, _, _)| size).sum();
+        for (last_used, size, hash, path) in artifacts {
+            let over_size = self.max_bytes.is_some_and(|max_bytes| total_bytes > max_bytes);
+            if !over_size && !expired(last_used) {
+                continue;
+            }
+            log::info!("[ModuleCache] Evicting artifact of module {} ({} bytes)", hash, size);
+            fs::remove_file(&path)?;
+            total_bytes -= size;
+            evicted += 1;
+        }
+        Ok(evicted)
+    }
+
+    /// Drops a module from memory, keeping its artifact on disk.
+    ///
+    /// Instances created from the module keep it alive; the next load of the same
+    /// content reads the artifact or compiles it again.
+    ///
+    /// # Returns
+    ///
+    /// `true` if the module was in memory.
+    pub fn forget(&self, hash: &str) -> bool {
+        self.modules.write().unwrap().remove(hash).is_some()
+    }
+
+    /// Removes a module from memory and disk.
+    ///
+    /// # Returns
+    ///
+    /// `true` if the module was cached.
+    ///
+    /// # Errors
+    ///
+    /// Returns `DlinkError::Io` if the artifact cannot be removed.
+    pub fn remove(&self, hash: &str) -> Result<bool> {
+        let mut removed = self.modules.write().unwrap().remove(hash).is_some();
+        if let Some(path) = self.artifact_path(hash) {
+            if path.exists() {
+                fs::remove_file(path)?;
+                removed = true;
+            }
+        }
+        Ok(removed)
+    }
+
+    /// Removes every module of this engine from memory and disk.
+    ///
+    /// # Errors
+    ///
+    /// Returns `DlinkError::Io` if the artifact directory cannot be read or an
+    /// artifact cannot be removed.
+    pub fn clear(&self) -> Result<()> {
+        self.modules.write().unwrap().clear();
+        for (_, path, _) in self.artifacts()? {
+            fs::remove_file(path)?;
+        }
+        Ok(())
+    }
+
+    /// Gets the artifact path of a module, if the cache has a directory.
+    fn artifact_path(&self, hash: &str) -> Option<PathBuf> {
+        self.dir
+            .as_ref()
+            .map(|dir| dir.join(format!("{}-{}.{}", hash, self.engine_tag, ARTIFACT_EXTENSION)))
+    }
+
+    /// Lists the artifacts of this engine as `(hash, path, metadata)`.
+    fn artifacts(&self) -> Result<Vec<(String, PathBuf, fs::Metadata)>> {
+        let Some(dir) = &self.dir else {
+            return Ok(Vec::new());
+        };
+        let suffix = format!("-{}.{}", self.engine_tag, ARTIFACT_EXTENSION);
+        let mut artifacts = Vec::new();
+        for entry in fs::read_dir(dir)? {
+            let entry = entry?;
+            let file_name = entry.file_name().to_string_lossy().to_string();
+            if let Some(hash) = file_name.strip_suffix(&suffix) {
+                artifacts.push((hash.to_string(), entry.path(), entry.metadata()?));
+            }
+        }
+        Ok(artifacts)
+    }
+
+    /// Loads a module from its artifact, refreshing the artifact's last use time.
+    fn load_artifact<C: Compiled>(&self, hash: &str) -> Option<C> {
+        let path = self.artifact_path(hash).filter(|path| path.exists())?;
+        // SAFETY: artifacts are only written by `store_artifact` into the cache
+        // directory, which must not be writable by untrusted parties. Artifacts of
+        // other wasmtime versions or engine settings are rejected by wasmtime.
+        match unsafe { C::deserialize_file(&self.engine, &path) } {
+            Ok(module) => {
+                log::info!("[ModuleCache] Loaded module {} from {}", hash, path.display());
+                if let Err(e) = File::options().write(true).open(&path).and_then(|f| f.set_modified(SystemTime::now())) {
+                    log::warn!("[ModuleCache] Cannot update last use of {}: {}", path.display(), e);
+                }
+                Some(module)
+            },
+            Err(e) => {
+                log::warn!("[ModuleCache] Discarding unusable artifact {}: {:#}", path.display(), e);
+                let _ = fs::remove_file(&path);
+                None
+            },
+        }
+    }
+
+    /// Serializes a module to its artifact. Failures are logged, not returned.
+    fn store_artifact<C: Compiled>(&self, hash: &str, code: &C) {
+        let Some(path) = self.artifact_path(hash) else {
+            return;
+        };
+        // Write to a temporary file first so that readers never see a partial artifact
+        let temp_path = path.with_extension(format!("{}.tmp", std::process::id()));
+        let result = code
+            .serialize()
+            .map_err(|e| std::io::Error::other(format!("{:#}", e)))
+            .and_then(|bytes| fs::write(&temp_path, bytes))
+            .and_then(|()| fs::rename(&temp_path, &path));
+        if let Err(e) = result {
+            log::warn!("[ModuleCache] Cannot write artifact {}: {}", path.display(), e);
+            let _ = fs::remove_file(&temp_path);
+        }
+    }
+}
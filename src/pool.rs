@@ -0,0 +1,327 @@
+//! # Instance Pool
+//!
+//! This module keeps a pool of pre-instantiated stores per module so that calls to
+//! the same module can run concurrently. Each pooled instance has its own store,
+//! WASI context and host allocator; guest state is not shared between them.
+//!
+//! Instances are checked out with `InstancePool::checkout` and return to the pool
+//! when the `PooledInstance` is dropped. The pool keeps between `min_idle` and
+//! `max_idle` idle instances, never holds more than `max_size` instances in total
+//! and drops instances after `recycle_after` checkouts. Instances the pool drops
+//! are shut down first (see the `lifecycle` module).
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Condvar, Mutex};
+use wasmtime::{Instance, Store};
+use crate::config::PoolConfig;
+use crate::error::Result;
+use crate::host_import::HostState;
+use crate::lifecycle::shutdown_instance;
+
+/// Creates a new instance of the pooled module with its own store.
+pub type InstanceFactory = Box<dyn Fn() -> Result<(Instance, Store<HostState>)> + Send + Sync>;
+
+/// # Pool Statistics
+///
+/// Snapshot of the state and history of an `InstancePool`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct PoolStats {
+    /// Instances ready to be checked out
+    pub idle: usize,
+    /// Instances currently checked out
+    pub in_use: usize,
+    /// Instances created since the pool was created
+    pub created: u64,
+    /// Instances dropped after reaching `recycle_after` checkouts
+    pub recycled: u64,
+}
+
+/// An instance owned by the pool, with the number of times it was checked out.
+struct PoolEntry {
+    instance: Instance,
+    store: Store<HostState>,
+    uses: u64,
+}
+
+impl PoolEntry {
+    /// Runs the guest's shutdown export and drops the instance.
+    fn shutdown(mut self) {
+        shutdown_instance(self.instance, &mut self.store);
+    }
+}
+
+/// Mutable state of the pool, guarded by its mutex.
+#[derive(Default)]
+struct PoolState {
+    /// Instances ready to be checked out
+    idle: Vec<PoolEntry>,
+    /// Instances in existence or being created, idle and checked out
+    total: usize,
+    /// Instances created since the pool was created
+    created: u64,
+    /// Instances recycled since the pool was created
+    recycled: u64,
+}
+
+/// # Instance Pool
+///
+/// Thread-safe pool of instances of one module.
+///
+/// # Example
+///
+/// ```rust
+/// use dlink_wm::config::{DlinkWMConfig, ModuleConfig, PoolConfig};
+/// use dlink_wm::wasm_manager::WasmInstanceCache;
+/// use std::sync::{Arc, RwLock};
+///
+/// let mut config = DlinkWMConfig::default();
+/// config.modules.insert("wasm/wasm_test.wasm".to_string(), ModuleConfig {
+///     pool: Some(PoolConfig { max_size: 2, min_idle: 1, ..Default::default() }),
+///     ..Default::default()
+/// });
+/// let cache = WasmInstanceCache::with_config(Arc::new(RwLock::new(config)));
+///
+/// let pool = cache.pool("wasm/wasm_test.wasm").unwrap().expect("pooling is configured");
+/// assert_eq!(pool.stats().idle, 1);
+///
+/// // Two instances can be used at the same time
+/// let first = pool.checkout(false).unwrap();
+/// let second = pool.checkout(false).unwrap();
+/// assert_eq!(pool.stats().in_use, 2);
+///
+/// drop((first, second));
+/// assert_eq!(pool.stats().idle, 2);
+/// ```
+pub struct InstancePool {
+    /// Path of the pooled module
+    wasm_path: String,
+    /// Sizes of the pool
+    config: PoolConfig,
+    /// Creates new instances of the module
+    factory: InstanceFactory,
+    /// Idle instances and counters
+    state: Mutex<PoolState>,
+    /// Signalled when an instance is returned or a slot becomes free
+    available: Condvar,
+    /// Set when the pool is replaced; returned instances are dropped
+    retired: AtomicBool,
+}
+
+impl InstancePool {
+    /// Creates an empty pool. Call `fill` to create the `min_idle` instances.
+    ///
+    /// # Parameters
+    ///
+    /// - `wasm_path`: Path of the pooled module, used in log messages
+    /// - `config`: Sizes of the pool
+    /// - `factory`: Creates new instances of the module
+    pub fn new(wasm_path: &str, config: PoolConfig, factory: InstanceFactory) -> Arc<Self> {
+        Arc::new(Self {
+            wasm_path: wasm_path.to_string(),
+            config,
+            factory,
+            state: Mutex::new(PoolState::default()),
+            available: Condvar::new(),
+            retired: AtomicBool::new(false),
+        })
+    }
+
+    /// Gets the sizes of the pool.
+    pub fn config(&self) -> &PoolConfig {
+        &self.config
+    }
+
+    /// Gets a snapshot of the pool's state.
+    pub fn stats(&self) -> PoolStats {
+        let state = self.state.lock().unwrap();
+        PoolStats {
+            idle: state.idle.len(),
+            in_use: state.total - state.idle.len(),
+            created: state.created,
+            recycled: state.recycled,
+        }
+    }
+
+    /// Checks an instance out of the pool.
+    ///
+    /// Takes an idle instance if there is one, otherwise creates a new one if the
+    /// pool has fewer than `max_size` instances, otherwise waits until an instance
+    /// is returned.
+    ///
+    /// # Parameters
+    ///
+    /// - `fresh`: Drop the idle instance that would be reused and create a new one
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if a new instance cannot be created.
+    pub fn checkout(self: &Arc<Self>, fresh: bool) -> Result<PooledInstance> {
+        let max_size = self.config.max_size.max(1);
+        let mut state = self.state.lock().unwrap();
+        loop {
+            if let Some(entry) = state.idle.pop() {
+                if !fresh {
+                    return Ok(PooledInstance { pool: Arc::clone(self), entry: Some(entry) });
+                }
+                // Free the slot of the stale instance for a new one
+                state.total -= 1;
+                drop(state);
+                entry.shutdown();
+                state = self.state.lock().unwrap();
+            }
+            if state.total < max_size {
+                state.total += 1;
+                drop(state);
+                let entry = self.create()?;
+                return Ok(PooledInstance { pool: Arc::clone(self), entry: Some(entry) });
+            }
+            state = self.available.wait(state).unwrap();
+        }
+    }
+
+    /// Creates idle instances until the pool has `min_idle` of them or `max_size`
+    /// instances in total.
+    ///
+    /// # Errors
+    ///
+    /// Returns the error of the first instance that cannot be created.
+    pub fn fill(&self) -> Result<()> {
+        loop {
+            {
+                let mut state = self.state.lock().unwrap();
+                if self.is_retired()
+                    || state.idle.len() >= self.config.min_idle
+                    || state.total >= self.config.max_size.max(1)
+                {
+                    return Ok(());
+                }
+                state.total += 1;
+            }
+            let entry = self.create()?;
+            self.state.lock().unwrap().idle.push(entry);
+            self.available.notify_one();
+        }
+    }
+
+    /// Retires the pool: idle instances are dropped now, checked out instances when
+    /// they are returned.
+    pub fn retire(&self) {
+        self.retired.store(true, Ordering::SeqCst);
+        let idle = {
+            let mut state = self.state.lock().unwrap();
+            state.total -= state.idle.len();
+            std::mem::take(&mut state.idle)
+        };
+        for entry in idle {
+            entry.shutdown();
+        }
+        self.available.notify_all();
+    }
+
+    /// Checks whether the pool was retired.
+    pub fn is_retired(&self) -> bool {
+        self.retired.load(Ordering::SeqCst)
+    }
+
+    /// Creates an instance for a slot already counted in `total`, releasing the
+    /// slot if creation fails.
+    fn create(&self) -> Result<PoolEntry> {
+        match (self.factory)() {
+            Ok((instance, store)) => {
+                self.state.lock().unwrap().created += 1;
+                Ok(PoolEntry { instance, store, uses: 0 })
+            },
+            Err(e) => {
+                self.state.lock().unwrap().total -= 1;
+                self.available.notify_one();
+                Err(e)
+            },
+        }
+    }
+
+    /// Returns a checked out instance, recycling or dropping it if needed.
+    fn checkin(&self, mut entry: PoolEntry) {
+        entry.uses += 1;
+        // Idle instances hold no response of the call they ran
+        entry.store.data_mut().clear_pending_response();
+        let recycle = self.config.recycle_after.is_some_and(|limit| entry.uses >= limit);
+        let max_idle = self.config.max_idle.unwrap_or(self.config.max_size);
+        let dropped = {
+            let mut state = self.state.lock().unwrap();
+            if recycle || self.is_retired() || state.idle.len() >= max_idle {
+                state.total -= 1;
+                if recycle {
+                    state.recycled += 1;
+                }
+                Some(entry)
+            } else {
+                state.idle.push(entry);
+                None
+            }
+        };
+        self.available.notify_one();
+
+        if let Some(entry) = dropped {
+            if recycle {
+                log::debug!("[Pool] Recycling instance of {} after {} uses", self.wasm_path, entry.uses);
+            }
+            entry.shutdown();
+            if let Err(e) = self.fill() {
+                log::error!("[Pool] Failed to refill pool of {}: {}", self.wasm_path, e);
+            }
+        }
+    }
+}
+
+/// # Pooled Instance
+///
+/// An instance checked out of an `InstancePool`. It returns to the pool when
+/// dropped, unless it is discarded.
+pub struct PooledInstance {
+    /// Pool the instance returns to
+    pool: Arc<InstancePool>,
+    /// The instance, `None` once returned or discarded
+    entry: Option<PoolEntry>,
+}
+
+impl PooledInstance {
+    /// Gets the instance.
+    pub fn instance(&self) -> Instance {
+        self.entry().instance
+    }
+
+    /// Gets the store of the instance.
+    pub fn store(&self) -> &Store<HostState> {
+        &self.entry().store
+    }
+
+    /// Gets the store of the instance mutably.
+    pub fn store_mut(&mut self) -> &mut Store<HostState> {
+        &mut self.entry.as_mut().expect("pooled instance is present until dropped").store
+    }
+
+    /// Drops the instance instead of returning it to the pool, e.g. after a trap
+    /// left the guest in an unknown state.
+    pub fn discard(mut self) {
+        if let Some(entry) = self.entry.take() {
+            self.pool.state.lock().unwrap().total -= 1;
+            self.pool.available.notify_one();
+            entry.shutdown();
+            if let Err(e) = self.pool.fill() {
+                log::error!("[Pool] Failed to refill pool of {}: {}", self.pool.wasm_path, e);
+            }
+        }
+    }
+
+    fn entry(&self) -> &PoolEntry {
+        self.entry.as_ref().expect("pooled instance is present until dropped")
+    }
+}
+
+impl Drop for PooledInstance {
+    fn drop(&mut self) {
+        if let Some(entry) = self.entry.take() {
+            self.pool.checkin(entry);
+        }
+    }
+}
@@ -0,0 +1,334 @@
+//! # Host Method Registries
+//!
+//! This module defines `HostMethodRegistry`, the table of host methods a linker
+//! exposes through `universal_invoke`. Registries form a tree: a registry can have a
+//! parent, and lookups of un-namespaced method names fall back to the parent when
+//! the method is not registered locally.
+//!
+//! A method name is *namespaced* when it contains a `.` (e.g. `db.query`).
+//! Namespaced methods are never inherited; they must be registered in the registry
+//! the module is linked against. This lets a trusted plugin see `db.query` while an
+//! untrusted plugin sharing the same parent does not.
+//!
+//! A method is either synchronous (`MethodHandler`) or async (`AsyncMethodHandler`);
+//! registering one replaces the other under the same name.
+//!
+//! The process-wide registry used by `register_host_method` and friends is
+//! available through `HostMethodRegistry::global()`.
+//!
+//! Methods defined with `#[dlink_host_method]` implement `HostMethodDef` and are
+//! registered with `register_method`, which also records their `MethodSchema` for
+//! introspection.
+
+use std::collections::HashMap;
+use std::future::Future;
+use std::sync::{Arc, LazyLock, RwLock};
+use anyhow::Result as AnyResult;
+use serde::Serialize;
+use crate::codec::{Decode, Encode};
+use crate::host_import::{AsyncHostContext, AsyncMethodHandler, HostContext, HostFuture, MethodHandler, SerializationFormat};
+
+/// Separator between a namespace and a method name.
+pub const NAMESPACE_SEPARATOR: char = '.';
+
+/// Process-wide registry backing the free `register_host_method` functions.
+static GLOBAL_REGISTRY: LazyLock<Arc<HostMethodRegistry>> = LazyLock::new(|| Arc::new(HostMethodRegistry::new()));
+
+/// # Host Method
+///
+/// Handler registered under a method name.
+#[derive(Clone)]
+pub enum HostMethod {
+    /// Handler running to completion while the guest waits
+    Sync(MethodHandler),
+    /// Handler whose future is awaited while the guest is suspended
+    Async(AsyncMethodHandler),
+}
+
+/// # Method Schema
+///
+/// Parameter and return types of a host method, as written in its Rust signature.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct MethodSchema {
+    /// Name the method is registered under
+    pub name: &'static str,
+    /// Parameters decoded from the guest's payload
+    pub params: &'static [ParamSchema],
+    /// Type of the value encoded for the guest (the `Ok` type of a `Result`)
+    pub returns: &'static str,
+    /// Documentation of the method
+    pub docs: &'static str,
+}
+
+/// # Parameter Schema
+///
+/// Name and type of a host method parameter.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct ParamSchema {
+    /// Parameter name
+    pub name: &'static str,
+    /// Parameter type
+    pub ty: &'static str,
+}
+
+/// # Host Method Definition
+///
+/// A host method with its schema, implemented by `#[dlink_host_method]`.
+pub trait HostMethodDef {
+    /// Name, parameters and return type of the method
+    const SCHEMA: MethodSchema;
+
+    /// Creates the handler decoding the parameters and encoding the result.
+    fn handler() -> MethodHandler;
+}
+
+/// # Host Method Registry
+///
+/// Thread-safe table of host methods, optionally inheriting un-namespaced methods
+/// from a parent registry.
+///
+/// # Example
+///
+/// ```rust
+/// use dlink_wm::host_import::{HostContext, SerializationFormat};
+/// use dlink_wm::registry::HostMethodRegistry;
+/// use std::sync::Arc;
+///
+/// fn ok_handler(_ctx: &mut HostContext<'_>, _params: Vec<u8>, _format: SerializationFormat) -> anyhow::Result<(bool, Vec<u8>)> {
+///     Ok((true, Vec::new()))
+/// }
+///
+/// let parent = Arc::new(HostMethodRegistry::new());
+/// parent.register("log", Arc::new(ok_handler));
+/// parent.register("db.query", Arc::new(ok_handler));
+///
+/// let plugin = HostMethodRegistry::with_parent(parent.clone());
+/// assert!(plugin.contains("log"));       // Inherited from the parent
+/// assert!(!plugin.contains("db.query")); // Namespaced methods are not inherited
+///
+/// // Isolated registries never see each other's methods
+/// let a = HostMethodRegistry::new();
+/// let b = HostMethodRegistry::new();
+/// a.register("only_in_a", Arc::new(ok_handler));
+/// assert!(!b.contains("only_in_a"));
+/// ```
+#[derive(Default)]
+pub struct HostMethodRegistry {
+    /// Methods registered directly in this registry
+    methods: RwLock<HashMap<String, HostMethod>>,
+    /// Schemas of the methods registered with `register_method`
+    schemas: RwLock<HashMap<String, MethodSchema>>,
+    /// Registry un-namespaced lookups fall back to
+    parent: Option<Arc<HostMethodRegistry>>,
+}
+
+impl HostMethodRegistry {
+    /// Creates an empty, isolated registry.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Creates an empty registry that inherits un-namespaced methods from `parent`.
+    ///
+    /// # Parameters
+    ///
+    /// - `parent`: Registry to fall back to for un-namespaced method names
+    pub fn with_parent(parent: Arc<HostMethodRegistry>) -> Self {
+        Self {
+            methods: RwLock::new(HashMap::new()),
+            schemas: RwLock::new(HashMap::new()),
+            parent: Some(parent),
+        }
+    }
+
+    /// Gets the process-wide registry used by `register_host_method`.
+    pub fn global() -> Arc<HostMethodRegistry> {
+        Arc::clone(&GLOBAL_REGISTRY)
+    }
+
+    /// Gets the parent registry, if any.
+    pub fn parent(&self) -> Option<&Arc<HostMethodRegistry>> {
+        self.parent.as_ref()
+    }
+
+    /// Registers a host method in this registry.
+    ///
+    /// # Returns
+    ///
+    /// `true` if the method was registered successfully, `false` if the method name
+    /// was already registered in this registry (the handler is replaced).
+    pub fn register(&self, method_name: &str, handler: MethodHandler) -> bool {
+        self.schemas.write().unwrap().remove(method_name);
+        let mut methods = self.methods.write().unwrap();
+        methods.insert(method_name.to_string(), HostMethod::Sync(handler)).is_none()
+    }
+
+    /// Registers an async host method in this registry.
+    ///
+    /// See `host_import::register_async_host_method` for details.
+    ///
+    /// # Returns
+    ///
+    /// `true` if the method was registered successfully, `false` if the method name
+    /// was already registered in this registry (the handler is replaced).
+    pub fn register_async(&self, method_name: &str, handler: AsyncMethodHandler) -> bool {
+        self.schemas.write().unwrap().remove(method_name);
+        let mut methods = self.methods.write().unwrap();
+        methods.insert(method_name.to_string(), HostMethod::Async(handler)).is_none()
+    }
+
+    /// Registers a typed host method in this registry.
+    ///
+    /// See `host_import::register_typed_host_method` for details.
+    pub fn register_typed<P, R, F>(&self, method_name: &str, handler: F) -> bool
+    where
+        P: Decode,
+        R: Encode,
+        F: Fn(P) -> AnyResult<R> + Send + Sync + 'static,
+    {
+        let typed_handler = move |_ctx: &mut HostContext<'_>, params_bytes: Vec<u8>, format: SerializationFormat| -> AnyResult<(bool, Vec<u8>)> {
+            let params = P::decode(&params_bytes, format)?;
+            let result = handler(params)?;
+            Ok((true, result.encode(format)?))
+        };
+        self.register(method_name, Arc::new(typed_handler))
+    }
+
+    /// Registers a typed async host method in this registry.
+    ///
+    /// Parameters and results are decoded and encoded as for `register_typed`.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use dlink_wm::codec::JsonParams;
+    /// use dlink_wm::registry::HostMethodRegistry;
+    ///
+    /// let registry = HostMethodRegistry::new();
+    /// registry.register_typed_async("double", |params: JsonParams<i64>| async move {
+    ///     Ok(params.data * 2)
+    /// });
+    /// assert!(registry.contains("double"));
+    /// ```
+    pub fn register_typed_async<P, R, F, Fut>(&self, method_name: &str, handler: F) -> bool
+    where
+        P: Decode + Send + 'static,
+        R: Encode + 'static,
+        F: Fn(P) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = AnyResult<R>> + Send + 'static,
+    {
+        let handler = Arc::new(handler);
+        let typed_handler = move |_ctx: AsyncHostContext, params_bytes: Vec<u8>, format: SerializationFormat| -> HostFuture {
+            let handler = Arc::clone(&handler);
+            Box::pin(async move {
+                let params = P::decode(&params_bytes, format)?;
+                let result = handler(params).await?;
+                Ok((true, result.encode(format)?))
+            })
+        };
+        self.register_async(method_name, Arc::new(typed_handler))
+    }
+
+    /// Registers a host method defined with `#[dlink_host_method]` under the name in
+    /// its schema, and records the schema.
+    ///
+    /// # Returns
+    ///
+    /// `true` if the method was registered successfully, `false` if the method name
+    /// was already registered in this registry (the handler is replaced).
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use dlink_wm::dlink_host_method;
+    /// use dlink_wm::registry::HostMethodRegistry;
+    ///
+    /// /// Adds two numbers
+    /// #[dlink_host_method]
+    /// fn add(a: i64, b: i64) -> i64 {
+    ///     a + b
+    /// }
+    ///
+    /// let registry = HostMethodRegistry::new();
+    /// registry.register_method::<add>();
+    ///
+    /// let schema = registry.schema("add").unwrap();
+    /// assert_eq!(schema.params[1].name, "b");
+    /// assert_eq!(schema.returns, "i64");
+    /// assert_eq!(schema.docs, "Adds two numbers");
+    /// ```
+    pub fn register_method<M: HostMethodDef>(&self) -> bool {
+        let registered = self.register(M::SCHEMA.name, M::handler());
+        self.schemas.write().unwrap().insert(M::SCHEMA.name.to_string(), M::SCHEMA);
+        registered
+    }
+
+    /// Removes a host method from this registry. Inherited methods are not affected.
+    ///
+    /// # Returns
+    ///
+    /// `true` if the method was removed, `false` if it was not registered here.
+    pub fn unregister(&self, method_name: &str) -> bool {
+        self.schemas.write().unwrap().remove(method_name);
+        let mut methods = self.methods.write().unwrap();
+        methods.remove(method_name).is_some()
+    }
+
+    /// Checks whether a method resolves in this registry, including inherited methods.
+    pub fn contains(&self, method_name: &str) -> bool {
+        self.resolve_method(method_name).is_some()
+    }
+
+    /// Looks up the synchronous handler for a method.
+    ///
+    /// Returns `None` if the method is not registered or is async; see
+    /// `resolve_method`.
+    pub fn resolve(&self, method_name: &str) -> Option<MethodHandler> {
+        match self.resolve_method(method_name)? {
+            HostMethod::Sync(handler) => Some(handler),
+            HostMethod::Async(_) => None,
+        }
+    }
+
+    /// Looks up the handler for a method, synchronous or async.
+    ///
+    /// Namespaced names are looked up only in this registry; un-namespaced names
+    /// fall back to the parent chain.
+    pub fn resolve_method(&self, method_name: &str) -> Option<HostMethod> {
+        if let Some(method) = self.methods.read().unwrap().get(method_name) {
+            return Some(method.clone());
+        }
+        if is_namespaced(method_name) {
+            return None;
+        }
+        self.parent.as_ref().and_then(|parent| parent.resolve_method(method_name))
+    }
+
+    /// Looks up the schema of a method registered with `register_method`, following
+    /// the same inheritance rules as `resolve_method`.
+    pub fn schema(&self, method_name: &str) -> Option<MethodSchema> {
+        if self.methods.read().unwrap().contains_key(method_name) {
+            return self.schemas.read().unwrap().get(method_name).copied();
+        }
+        if is_namespaced(method_name) {
+            return None;
+        }
+        self.parent.as_ref().and_then(|parent| parent.schema(method_name))
+    }
+
+    /// Gets the schemas of the methods registered directly in this registry with
+    /// `register_method`.
+    pub fn schemas(&self) -> Vec<MethodSchema> {
+        self.schemas.read().unwrap().values().copied().collect()
+    }
+
+    /// Gets the names of the methods registered directly in this registry.
+    pub fn method_names(&self) -> Vec<String> {
+        self.methods.read().unwrap().keys().cloned().collect()
+    }
+}
+
+/// Checks whether a method name carries a namespace (e.g. `db.query`).
+pub fn is_namespaced(method_name: &str) -> bool {
+    method_name.contains(NAMESPACE_SEPARATOR)
+}
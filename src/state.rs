@@ -0,0 +1,231 @@
+//! # State Migration
+//!
+//! This module carries guest state across hot reloads. When a shared instance is
+//! replaced, the host asks the outgoing instance to save its state and hands the
+//! bytes to the incoming instance before the new version is published. Pooled
+//! modules keep no shared state and are not migrated.
+//!
+//! ## Guest Exports
+//!
+//! All exports are optional:
+//!
+//! | Export | Signature | Meaning |
+//! |--------|-----------|---------|
+//! | `dlinkwm_state_version` | `() -> i32` | Version of the state format the module saves and prefers to restore; `0` when missing |
+//! | `dlinkwm_state_save` | `() -> i64` | Serializes the state and returns `(ptr << 32) \| len` of the bytes in guest memory |
+//! | `dlinkwm_state_restore` | `(version: i32, ptr: i32, len: i32) -> i32` | Restores state saved in format `version`; returns a status below |
+//!
+//! The bytes passed to `dlinkwm_state_restore` are allocated with the host
+//! allocator and freed when it returns, so the guest must copy what it keeps.
+//!
+//! ## Version Negotiation
+//!
+//! The host reads the version of both sides and passes the saved version to
+//! `dlinkwm_state_restore`. The incoming module migrates older formats itself, or
+//! returns `STATE_UNSUPPORTED_VERSION` to start with fresh state.
+//!
+//! ## Fallbacks
+//!
+//! Migration is skipped, and the new instance starts with fresh state, when the
+//! outgoing module does not export `dlinkwm_state_save`, the incoming module does
+//! not export `dlinkwm_state_restore`, saving fails, or the incoming module refuses
+//! the state. A trap in `dlinkwm_state_restore` fails the reload, because the new
+//! instance may be left half-restored; the previous version stays published.
+
+use wasmtime::{Instance, Store};
+use crate::config::DlinkWMConfig;
+use crate::error::{DlinkError, Result};
+use crate::host_import::{allocate_guest_memory, free_guest_memory, HostState};
+use crate::utils::{read_wasm_memory, write_wasm_memory};
+use crate::wasm_manager::call_export;
+
+/// Export returning the version of the guest's state format
+pub const STATE_VERSION_EXPORT: &str = "dlinkwm_state_version";
+/// Export serializing the guest's state
+pub const STATE_SAVE_EXPORT: &str = "dlinkwm_state_save";
+/// Export restoring state saved by a previous version
+pub const STATE_RESTORE_EXPORT: &str = "dlinkwm_state_restore";
+
+/// The state was restored
+pub const STATE_RESTORED: i32 = 0;
+/// The guest cannot read the version of the saved state and starts fresh
+pub const STATE_UNSUPPORTED_VERSION: i32 = 1;
+
+/// # Saved State
+///
+/// State saved by an outgoing instance.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SavedState {
+    /// Version of the state format
+    pub version: i32,
+    /// Serialized state
+    pub data: Vec<u8>,
+}
+
+/// # Migration Outcome
+///
+/// Result of moving state from an outgoing to an incoming instance.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MigrationOutcome {
+    /// The incoming instance restored the state
+    Restored {
+        /// Version of the saved state
+        from_version: i32,
+        /// Version of the incoming module's state format
+        to_version: i32,
+        /// Size of the state in bytes
+        bytes: usize,
+    },
+    /// No state was moved and the incoming instance starts fresh
+    Skipped {
+        /// Why the state was not moved
+        reason: String,
+    },
+}
+
+/// Moves the state of an outgoing instance into an incoming one.
+///
+/// # Parameters
+///
+/// - `outgoing`: Instance being replaced, and its store
+/// - `incoming`: New instance, not yet published, and its store
+/// - `config`: Configuration providing the budgets of the state calls
+///
+/// # Errors
+///
+/// Returns an error if `dlinkwm_state_restore` traps or the state cannot be
+/// written into the incoming instance's memory. Failures on the outgoing side
+/// skip the migration instead.
+///
+/// # Example
+///
+/// ```rust
+/// use dlink_wm::config::DlinkWMConfig;
+/// use dlink_wm::wasm_manager::WasmInstanceCache;
+/// use std::sync::{Arc, RwLock};
+///
+/// // A counter that saves its value as 4 bytes at address 0
+/// let counter = |step: i32, restore: bool| format!(r#"(module
+///     (memory (export "memory") 1)
+///     (global $count (mut i32) (i32.const 0))
+///     (func (export "incr") (result i32)
+///         (global.set $count (i32.add (global.get $count) (i32.const {step})))
+///         (global.get $count))
+///     (func (export "dlinkwm_state_version") (result i32) i32.const 1)
+///     (func (export "dlinkwm_state_save") (result i64)
+///         (i32.store (i32.const 0) (global.get $count))
+///         i64.const 4)
+///     {})"#, if restore { r#"(func (export "dlinkwm_state_restore") (param $version i32) (param $ptr i32) (param $len i32) (result i32)
+///         (if (i32.ne (local.get $version) (i32.const 1)) (then (return (i32.const 1))))
+///         (global.set $count (i32.load (local.get $ptr)))
+///         i32.const 0)"# } else { "" });
+///
+/// let wasm_path = std::env::temp_dir().join("dlinkwm_state_doctest.wasm");
+/// std::fs::write(&wasm_path, counter(1, true)).unwrap();
+/// let wasm_path = wasm_path.to_str().unwrap();
+/// let mut config = DlinkWMConfig::default();
+/// config.entry_functions.insert(wasm_path.to_string(), vec!["incr".to_string()]);
+/// let cache = WasmInstanceCache::with_config(Arc::new(RwLock::new(config)));
+/// cache.call::<(), i32>(wasm_path, "incr", ()).unwrap();
+/// assert_eq!(cache.call::<(), i32>(wasm_path, "incr", ()).unwrap(), 2);
+///
+/// // The new version continues from the saved count
+/// std::fs::write(wasm_path, counter(10, true)).unwrap();
+/// cache.hot_reload(wasm_path).unwrap();
+/// assert_eq!(cache.call::<(), i32>(wasm_path, "incr", ()).unwrap(), 12);
+///
+/// // Without dlinkwm_state_restore, the new version starts fresh
+/// std::fs::write(wasm_path, counter(100, false)).unwrap();
+/// cache.hot_reload(wasm_path).unwrap();
+/// assert_eq!(cache.call::<(), i32>(wasm_path, "incr", ()).unwrap(), 100);
+/// ```
+pub fn migrate_state(
+    outgoing: (Instance, &mut Store<HostState>),
+    incoming: (Instance, &mut Store<HostState>),
+    config: &DlinkWMConfig,
+) -> Result<MigrationOutcome> {
+    let (old_instance, old_store) = outgoing;
+    let (new_instance, new_store) = incoming;
+    if new_instance.get_func(&mut *new_store, STATE_RESTORE_EXPORT).is_none() {
+        return Ok(skipped(format!("the new version does not export {}", STATE_RESTORE_EXPORT)));
+    }
+    if old_instance.get_func(&mut *old_store, STATE_SAVE_EXPORT).is_none() {
+        return Ok(skipped(format!("the previous version does not export {}", STATE_SAVE_EXPORT)));
+    }
+
+    let state = match save_state(old_instance, old_store, config) {
+        Ok(state) => state,
+        Err(e) => {
+            log::warn!("[State] {} failed, starting with fresh state: {}", STATE_SAVE_EXPORT, e);
+            return Ok(skipped(format!("saving failed: {}", e)));
+        },
+    };
+    let to_version = state_version(new_instance, new_store, config)?;
+    let bytes = state.data.len();
+    match restore_state(new_instance, new_store, &state, config)? {
+        STATE_RESTORED => Ok(MigrationOutcome::Restored { from_version: state.version, to_version, bytes }),
+        status => {
+            log::warn!(
+                "[State] New version refused state version {} with status {}, starting with fresh state",
+                state.version,
+                status
+            );
+            Ok(skipped(format!("the new version refused state version {} with status {}", state.version, status)))
+        },
+    }
+}
+
+/// Calls `dlinkwm_state_save` and copies the state out of guest memory.
+///
+/// # Errors
+///
+/// Returns an error if the export is missing or has the wrong signature, the
+/// guest traps, or the returned bytes lie outside its memory.
+pub fn save_state(instance: Instance, store: &mut Store<HostState>, config: &DlinkWMConfig) -> Result<SavedState> {
+    let version = state_version(instance, store, config)?;
+    let packed: i64 = call_export(instance, store, config, STATE_SAVE_EXPORT, ())?;
+    let (ptr, len) = ((packed as u64 >> 32) as i32, packed as u32 as i32);
+    let memory = instance.get_memory(&mut *store, "memory").ok_or(DlinkError::MemoryNotFound)?;
+    let data = read_wasm_memory(&memory, &*store, ptr, len)?;
+    Ok(SavedState { version, data })
+}
+
+/// Copies state into guest memory and calls `dlinkwm_state_restore`.
+///
+/// # Returns
+///
+/// The status returned by the guest, e.g. `STATE_RESTORED`.
+///
+/// # Errors
+///
+/// Returns an error if the export is missing or has the wrong signature, the
+/// state cannot be written into guest memory, or the guest traps.
+pub fn restore_state(instance: Instance, store: &mut Store<HostState>, state: &SavedState, config: &DlinkWMConfig) -> Result<i32> {
+    let memory = instance.get_memory(&mut *store, "memory").ok_or(DlinkError::MemoryNotFound)?;
+    let len = u32::try_from(state.data.len()).map_err(|_| DlinkError::MemoryAccess { ptr: 0, len: state.data.len() })?;
+    let ptr = if len == 0 {
+        0
+    } else {
+        allocate_guest_memory(&mut *store, &memory, len).ok_or(DlinkError::MemoryAccess { ptr: 0, len: state.data.len() })?
+    };
+
+    let status = write_wasm_memory(&memory, &mut *store, ptr as i32, &state.data).and_then(|()| {
+        call_export(instance, store, config, STATE_RESTORE_EXPORT, (state.version, ptr as i32, len as i32))
+    });
+    if ptr != 0 {
+        free_guest_memory(&mut *store, ptr);
+    }
+    status
+}
+
+/// Gets the state format version of an instance, `0` if it does not export one.
+fn state_version(instance: Instance, store: &mut Store<HostState>, config: &DlinkWMConfig) -> Result<i32> {
+    if instance.get_func(&mut *store, STATE_VERSION_EXPORT).is_none() {
+        return Ok(0);
+    }
+    call_export(instance, store, config, STATE_VERSION_EXPORT, ())
+}
+
+fn skipped(reason: String) -> MigrationOutcome {
+    MigrationOutcome::Skipped { reason }
+}
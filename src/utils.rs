@@ -2,11 +2,13 @@
 //! 
 //! This module provides utility functions for working with WASM memory and serialization.
 //! It includes functions for reading and writing to WASM linear memory, as well as
-//! serialization and deserialization helpers.
+//! serialization and deserialization helpers for every `SerializationFormat`.
 
 use serde::{Serialize, Deserialize};
 use wasmtime::{Memory, AsContext, AsContextMut};
-use anyhow::Result;
+use crate::codec::{Decode, Encode};
+use crate::error::{DlinkError, Result};
+use crate::host_import::SerializationFormat;
 
 /// # Read from WASM Memory
 /// 
@@ -21,7 +23,8 @@ use anyhow::Result;
 /// 
 /// # Returns
 /// 
-/// A `Result` containing the read byte array, or an error if the read operation fails.
+/// A `Result` containing the read byte array, or `DlinkError::MemoryAccess` if the
+/// range lies outside the guest memory.
 /// 
 /// # Example
 /// 
@@ -29,17 +32,22 @@ use anyhow::Result;
 /// use wasmtime::{Memory, Store};
 /// use dlink_wm::utils::read_wasm_memory;
 /// 
-/// fn example(memory: &Memory, store: &Store<()>, ptr: i32, len: i32) -> anyhow::Result<()> {
+/// fn example(memory: &Memory, store: &Store<()>, ptr: i32, len: i32) -> dlink_wm::error::Result<()> {
 ///     let data = read_wasm_memory(memory, store, ptr, len)?;
 ///     println!("Read {} bytes from WASM memory", data.len());
 ///     Ok(())
 /// }
 /// ```
 pub fn read_wasm_memory(memory: &Memory, store: impl AsContext, ptr: i32, len: i32) -> Result<Vec<u8>> {
-    let ptr = ptr as usize;
-    let len = len as usize;
+    let len = len as u32 as usize;
+    // Validate the range before allocating the buffer
+    if (ptr as u32 as usize).saturating_add(len) > memory.data_size(&store) {
+        return Err(DlinkError::MemoryAccess { ptr, len });
+    }
     let mut buffer = vec![0u8; len];
-    memory.read(store, ptr, &mut buffer)?;
+    memory
+        .read(store, ptr as u32 as usize, &mut buffer)
+        .map_err(|_| DlinkError::MemoryAccess { ptr, len })?;
     Ok(buffer)
 }
 
@@ -56,7 +64,8 @@ pub fn read_wasm_memory(memory: &Memory, store: impl AsContext, ptr: i32, len: i
 /// 
 /// # Returns
 /// 
-/// A `Result` indicating success or failure of the write operation.
+/// A `Result` indicating success, or `DlinkError::MemoryAccess` if the range lies
+/// outside the guest memory.
 /// 
 /// # Example
 /// 
@@ -64,15 +73,16 @@ pub fn read_wasm_memory(memory: &Memory, store: impl AsContext, ptr: i32, len: i
 /// use wasmtime::{Memory, Store};
 /// use dlink_wm::utils::write_wasm_memory;
 /// 
-/// fn example(memory: &Memory, store: &mut Store<()>, ptr: i32, data: &[u8]) -> anyhow::Result<()> {
+/// fn example(memory: &Memory, store: &mut Store<()>, ptr: i32, data: &[u8]) -> dlink_wm::error::Result<()> {
 ///     write_wasm_memory(memory, store, ptr, data)?;
 ///     println!("Wrote {} bytes to WASM memory", data.len());
 ///     Ok(())
 /// }
 /// ```
 pub fn write_wasm_memory(memory: &Memory, store: impl AsContextMut, ptr: i32, data: &[u8]) -> Result<()> {
-    let ptr = ptr as usize;
-    memory.write(store, ptr, data)?;
+    memory
+        .write(store, ptr as u32 as usize, data)
+        .map_err(|_| DlinkError::MemoryAccess { ptr, len: data.len() })?;
     Ok(())
 }
 
@@ -108,7 +118,7 @@ pub fn write_wasm_memory(memory: &Memory, store: impl AsContextMut, ptr: i32, da
 ///     age: u32,
 /// }
 /// 
-/// fn example(memory: &Memory, store: &Store<()>, ptr: i32, len: i32) -> anyhow::Result<()> {
+/// fn example(memory: &Memory, store: &Store<()>, ptr: i32, len: i32) -> dlink_wm::error::Result<()> {
 ///     let person: Person = deserialize_from_wasm(memory, store, ptr, len)?;
 ///     println!("Deserialized: {:?}", person);
 ///     Ok(())
@@ -120,9 +130,7 @@ pub fn deserialize_from_wasm<T: for<'a> Deserialize<'a>>(
     ptr: i32,
     len: i32
 ) -> Result<T> {
-    let buffer = read_wasm_memory(memory, store, ptr, len)?;
-    let result = serde_json::from_slice(&buffer)?;
-    Ok(result)
+    decode_from_wasm(memory, store, ptr, len, SerializationFormat::Json)
 }
 
 /// # Serialize to WASM Memory
@@ -157,22 +165,159 @@ pub fn deserialize_from_wasm<T: for<'a> Deserialize<'a>>(
 ///     age: u32,
 /// }
 /// 
-/// fn example(memory: &Memory, store: &mut Store<()>, ptr: i32) -> anyhow::Result<()> {
+/// fn example(memory: &Memory, store: &mut Store<()>, ptr: i32) -> dlink_wm::error::Result<()> {
 ///     let person = Person { name: "Alice".to_string(), age: 30 };
 ///     let bytes_written = serialize_to_wasm(memory, store, ptr, &person)?;
 ///     println!("Serialized and wrote {} bytes to WASM memory", bytes_written);
 ///     Ok(())
 /// }
 /// ```
-pub fn serialize_to_wasm<T: Serialize>(
+pub fn serialize_to_wasm<T: Serialize + ?Sized>(
     memory: &Memory,
     store: impl AsContextMut,
     ptr: i32,
     data: &T
 ) -> Result<usize> {
-    let buffer = serde_json::to_vec(data)?;
+    encode_to_wasm(memory, store, ptr, data, SerializationFormat::Json)
+}
+
+/// # Decode from WASM Memory
+/// 
+/// Format-aware counterpart of `deserialize_from_wasm`: reads bytes from WASM linear
+/// memory and decodes them in the given serialization format.
+/// 
+/// # Parameters
+/// 
+/// - `memory`: Reference to the WASM memory instance
+/// - `store`: WASM context used to access memory
+/// - `ptr`: Pointer to the start of the encoded data in WASM memory
+/// - `len`: Length of the encoded data in bytes
+/// - `format`: Serialization format of the data
+/// 
+/// # Returns
+/// 
+/// A `Result` containing the decoded value, or an error if the memory access or
+/// decoding fails.
+/// 
+/// # Example
+/// 
+/// ```rust
+/// use serde::Deserialize;
+/// use wasmtime::{Memory, Store};
+/// use dlink_wm::host_import::SerializationFormat;
+/// use dlink_wm::utils::decode_from_wasm;
+/// 
+/// #[derive(Debug, Deserialize)]
+/// struct Person {
+///     name: String,
+///     age: u32,
+/// }
+/// 
+/// fn example(memory: &Memory, store: &Store<()>, ptr: i32, len: i32) -> dlink_wm::error::Result<()> {
+///     let person: Person = decode_from_wasm(memory, store, ptr, len, SerializationFormat::Bincode)?;
+///     println!("Decoded: {:?}", person);
+///     Ok(())
+/// }
+/// ```
+pub fn decode_from_wasm<T: Decode>(
+    memory: &Memory,
+    store: impl AsContext,
+    ptr: i32,
+    len: i32,
+    format: SerializationFormat
+) -> Result<T> {
+    let buffer = read_wasm_memory(memory, store, ptr, len)?;
+    T::decode(&buffer, format)
+}
+
+/// # Encode to WASM Memory
+/// 
+/// Format-aware counterpart of `serialize_to_wasm`: encodes a value in the given
+/// serialization format and writes it to WASM linear memory.
+/// 
+/// # Parameters
+/// 
+/// - `memory`: Reference to the WASM memory instance
+/// - `store`: Mutable WASM context used to access memory
+/// - `ptr`: Pointer to write the encoded data to in WASM memory
+/// - `data`: Value to encode and write
+/// - `format`: Serialization format to use
+/// 
+/// # Returns
+/// 
+/// A `Result` containing the number of bytes written to WASM memory, or an error if
+/// the encoding or memory access fails.
+/// 
+/// # Example
+/// 
+/// ```rust
+/// use serde::Serialize;
+/// use wasmtime::{Memory, Store};
+/// use dlink_wm::host_import::SerializationFormat;
+/// use dlink_wm::utils::encode_to_wasm;
+/// 
+/// #[derive(Debug, Serialize)]
+/// struct Person {
+///     name: String,
+///     age: u32,
+/// }
+/// 
+/// fn example(memory: &Memory, store: &mut Store<()>, ptr: i32) -> dlink_wm::error::Result<()> {
+///     let person = Person { name: "Alice".to_string(), age: 30 };
+///     let bytes_written = encode_to_wasm(memory, store, ptr, &person, SerializationFormat::FlatBuffers)?;
+///     println!("Encoded and wrote {} bytes to WASM memory", bytes_written);
+///     Ok(())
+/// }
+/// ```
+pub fn encode_to_wasm<T: Encode + ?Sized>(
+    memory: &Memory,
+    store: impl AsContextMut,
+    ptr: i32,
+    data: &T,
+    format: SerializationFormat
+) -> Result<usize> {
+    let buffer = data.encode(format)?;
     write_wasm_memory(memory, store, ptr, &buffer)?;
     Ok(buffer.len())
 }
 
+/// # Glob Match
+/// 
+/// Matches `text` against a glob `pattern`, where `*` matches any sequence of
+/// characters (including `/`) and `?` matches exactly one character. A pattern
+/// without wildcards must equal the text.
+/// 
+/// # Example
+/// 
+/// ```rust
+/// use dlink_wm::utils::glob_match;
+/// 
+/// assert!(glob_match("plugins/*.wasm", "plugins/auth.wasm"));
+/// assert!(glob_match("db.?et", "db.get"));
+/// assert!(!glob_match("db.*", "fs.read"));
+/// ```
+pub fn glob_match(pattern: &str, text: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let text: Vec<char> = text.chars().collect();
+    let (mut p, mut t) = (0, 0);
+    // Position of the last `*` in the pattern and the text position it matched up to
+    let mut backtrack: Option<(usize, usize)> = None;
 
+    while t < text.len() {
+        if p < pattern.len() && (pattern[p] == '?' || pattern[p] == text[t]) {
+            p += 1;
+            t += 1;
+        } else if p < pattern.len() && pattern[p] == '*' {
+            backtrack = Some((p, t));
+            p += 1;
+        } else if let Some((star_p, star_t)) = backtrack {
+            // Let the last `*` absorb one more character
+            p = star_p + 1;
+            t = star_t + 1;
+            backtrack = Some((star_p, star_t + 1));
+        } else {
+            return false;
+        }
+    }
+    pattern[p..].iter().all(|&c| c == '*')
+}
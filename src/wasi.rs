@@ -0,0 +1,395 @@
+//! # WASI Sandbox
+//!
+//! This module builds the WASI context of a store from a module's `WasiConfig`:
+//! command-line arguments, environment variables, preopened directories and stdio.
+//!
+//! Read-only preopens are wrapped in `ReadOnlyDir`, which refuses every operation
+//! that would create, modify, rename or remove a file. Captured output streams are
+//! collected in `CaptureBuffers` owned by the store's `HostState`.
+//!
+//! Core modules get a WASI preview1 context (`build_wasi_ctx`), components a
+//! preview2 context (`build_preview2_ctx`) built from the same configuration.
+
+use std::any::Any;
+use std::fs::{File, OpenOptions};
+use std::io::{Read, Write};
+use std::path::PathBuf;
+use std::sync::{Arc, RwLock};
+use wasi_common::dir::{OpenResult, ReaddirCursor, ReaddirEntity};
+use wasi_common::file::{FdFlags, Filestat, OFlags};
+use wasi_common::pipe::{ReadPipe, WritePipe};
+use wasi_common::{ErrorExt, SystemTimeSpec, WasiDir, WasiFile};
+use bytes::Bytes;
+use wasmtime_wasi::preview2::{self, DirPerms, FilePerms, HostInputStream, HostOutputStream, StreamState, Table};
+use wasmtime_wasi::sync::{ambient_authority, Dir};
+use wasmtime_wasi::{WasiCtx, WasiCtxBuilder};
+use crate::config::{StdioConfig, WasiConfig};
+use crate::error::{DlinkError, Result};
+
+/// Shared buffer collecting a captured output stream.
+pub type CaptureBuffer = Arc<RwLock<Vec<u8>>>;
+
+/// # Captured Output
+///
+/// Output a module wrote to its captured stdout and stderr.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CapturedOutput {
+    /// Bytes written to stdout
+    pub stdout: Vec<u8>,
+    /// Bytes written to stderr
+    pub stderr: Vec<u8>,
+}
+
+/// # Capture Buffers
+///
+/// Buffers of the output streams configured as `capture`.
+#[derive(Debug, Clone, Default)]
+pub struct CaptureBuffers {
+    /// Buffer of stdout, if captured
+    stdout: Option<CaptureBuffer>,
+    /// Buffer of stderr, if captured
+    stderr: Option<CaptureBuffer>,
+}
+
+impl CaptureBuffers {
+    /// Checks whether any output stream is captured.
+    pub fn is_capturing(&self) -> bool {
+        self.stdout.is_some() || self.stderr.is_some()
+    }
+
+    /// Takes the output captured so far, leaving the buffers empty.
+    pub fn take(&self) -> CapturedOutput {
+        let take = |buffer: &Option<CaptureBuffer>| {
+            buffer
+                .as_ref()
+                .map(|buffer| std::mem::take(&mut *buffer.write().unwrap()))
+                .unwrap_or_default()
+        };
+        CapturedOutput {
+            stdout: take(&self.stdout),
+            stderr: take(&self.stderr),
+        }
+    }
+}
+
+/// Builds a WASI context from a module's configuration.
+///
+/// # Parameters
+///
+/// - `config`: WASI configuration of the module
+///
+/// # Returns
+///
+/// The WASI context and the buffers of the captured output streams.
+///
+/// # Errors
+///
+/// Returns an error if:
+/// - An argument or environment variable contains a NUL byte (`DlinkError::Config`)
+/// - Stdin is configured as `capture` (`DlinkError::Config`)
+/// - A stdio file or preopened directory cannot be opened (`DlinkError::Io`)
+///
+/// # Example
+///
+/// ```rust
+/// use dlink_wm::config::{PreopenConfig, StdioConfig, WasiConfig};
+/// use dlink_wm::wasi::build_wasi_ctx;
+///
+/// let config = WasiConfig {
+///     args: vec!["tool".to_string(), "--verbose".to_string()],
+///     stdout: StdioConfig::Capture,
+///     stderr: StdioConfig::Null,
+///     preopens: vec![PreopenConfig {
+///         host: ".".to_string(),
+///         guest: "/data".to_string(),
+///         read_only: true,
+///     }],
+///     ..Default::default()
+/// };
+/// let (_wasi_ctx, captures) = build_wasi_ctx(&config).unwrap();
+/// assert!(captures.is_capturing());
+///
+/// // Input cannot be captured
+/// let config = WasiConfig { stdin: StdioConfig::Capture, ..Default::default() };
+/// assert!(build_wasi_ctx(&config).is_err());
+/// ```
+pub fn build_wasi_ctx(config: &WasiConfig) -> Result<(WasiCtx, CaptureBuffers)> {
+    let env: Vec<(String, String)> = config.env.iter().map(|(k, v)| (k.clone(), v.clone())).collect();
+    let mut builder = WasiCtxBuilder::new()
+        .args(&config.args)
+        .map_err(|e| DlinkError::Config(format!("Invalid WASI argument: {}", e)))?
+        .envs(&env)
+        .map_err(|e| DlinkError::Config(format!("Invalid WASI environment variable: {}", e)))?;
+
+    builder = match &config.stdin {
+        StdioConfig::Inherit => builder.inherit_stdin(),
+        StdioConfig::Null => builder.stdin(Box::new(ReadPipe::new(std::io::empty()))),
+        StdioConfig::Capture => return Err(DlinkError::Config("WASI stdin cannot be captured".to_string())),
+        StdioConfig::File(path) => builder.stdin(Box::new(ReadPipe::new(File::open(path)?))),
+    };
+
+    let mut captures = CaptureBuffers::default();
+    builder = match output_stream(&config.stdout, &mut captures.stdout)? {
+        Some(file) => builder.stdout(file),
+        None => builder.inherit_stdout(),
+    };
+    builder = match output_stream(&config.stderr, &mut captures.stderr)? {
+        Some(file) => builder.stderr(file),
+        None => builder.inherit_stderr(),
+    };
+    let wasi_ctx = builder.build();
+
+    // Preopens get consecutive file descriptors in configuration order
+    for preopen in &config.preopens {
+        let dir = Dir::open_ambient_dir(&preopen.host, ambient_authority())?;
+        let mut dir: Box<dyn WasiDir> = Box::new(wasmtime_wasi::sync::dir::Dir::from_cap_std(dir));
+        if preopen.read_only {
+            dir = Box::new(ReadOnlyDir(dir));
+        }
+        wasi_ctx
+            .push_preopened_dir(dir, &preopen.guest)
+            .map_err(|e| DlinkError::Config(format!("Cannot preopen '{}': {}", preopen.host, e)))?;
+    }
+
+    Ok((wasi_ctx, captures))
+}
+
+/// Builds a WASI preview2 context for a component from a module's configuration.
+///
+/// The component gets the same arguments, environment, stdio and preopens as a
+/// core module with this configuration would; read-only preopens are opened
+/// without write permissions.
+///
+/// # Parameters
+///
+/// - `config`: WASI configuration of the module
+/// - `table`: Resource table of the component's store, which receives the stdio
+///   streams and preopened directories
+///
+/// # Returns
+///
+/// The WASI context and the buffers of the captured output streams.
+///
+/// # Errors
+///
+/// Returns the same errors as `build_wasi_ctx`.
+///
+/// # Example
+///
+/// ```rust
+/// use dlink_wm::config::{StdioConfig, WasiConfig};
+/// use dlink_wm::wasi::build_preview2_ctx;
+/// use wasmtime_wasi::preview2::Table;
+///
+/// let config = WasiConfig { stdout: StdioConfig::Capture, ..Default::default() };
+/// let mut table = Table::new();
+/// let (_wasi_ctx, captures) = build_preview2_ctx(&config, &mut table).unwrap();
+/// assert!(captures.is_capturing());
+/// ```
+pub fn build_preview2_ctx(config: &WasiConfig, table: &mut Table) -> Result<(preview2::WasiCtx, CaptureBuffers)> {
+    let env: Vec<(String, String)> = config.env.iter().map(|(k, v)| (k.clone(), v.clone())).collect();
+    if let Some(arg) = config.args.iter().find(|arg| arg.contains('\0')) {
+        return Err(DlinkError::Config(format!("Invalid WASI argument: {:?}", arg)));
+    }
+    if let Some((key, _)) = env.iter().find(|(key, value)| key.contains('\0') || value.contains('\0')) {
+        return Err(DlinkError::Config(format!("Invalid WASI environment variable: {:?}", key)));
+    }
+    let mut builder = preview2::WasiCtxBuilder::new().set_args(&config.args).set_env(&env);
+
+    builder = match &config.stdin {
+        // wasmtime's inherited stdin needs a pollable descriptor, so read it directly
+        StdioConfig::Inherit => builder.set_stdin(ReadStream(std::io::stdin())),
+        StdioConfig::Null => builder,
+        StdioConfig::Capture => return Err(DlinkError::Config("WASI stdin cannot be captured".to_string())),
+        StdioConfig::File(path) => builder.set_stdin(ReadStream(File::open(path)?)),
+    };
+
+    let mut captures = CaptureBuffers::default();
+    builder = match &config.stdout {
+        StdioConfig::Inherit => builder.inherit_stdout(),
+        stdout => match preview2_output_stream(stdout, &mut captures.stdout)? {
+            Some(stream) => builder.set_stdout(stream),
+            None => builder,
+        },
+    };
+    builder = match &config.stderr {
+        StdioConfig::Inherit => builder.inherit_stderr(),
+        stderr => match preview2_output_stream(stderr, &mut captures.stderr)? {
+            Some(stream) => builder.set_stderr(stream),
+            None => builder,
+        },
+    };
+
+    for preopen in &config.preopens {
+        let dir = Dir::open_ambient_dir(&preopen.host, ambient_authority())?;
+        let (dir_perms, file_perms) = if preopen.read_only {
+            (DirPerms::READ, FilePerms::READ)
+        } else {
+            (DirPerms::all(), FilePerms::all())
+        };
+        builder = builder.push_preopened_dir(dir, dir_perms, file_perms, &preopen.guest);
+    }
+
+    let wasi_ctx = builder
+        .build(table)
+        .map_err(|e| DlinkError::Config(format!("Cannot build WASI context: {:#}", e)))?;
+    Ok((wasi_ctx, captures))
+}
+
+/// Opens a preview2 output stream, returning `None` for a discarded stream (the
+/// builder's default).
+fn preview2_output_stream(config: &StdioConfig, capture: &mut Option<CaptureBuffer>) -> Result<Option<OutputStream>> {
+    let stream = match config {
+        StdioConfig::Inherit | StdioConfig::Null => return Ok(None),
+        StdioConfig::Capture => OutputStream::Capture(Arc::clone(capture.get_or_insert_with(CaptureBuffer::default))),
+        StdioConfig::File(path) => OutputStream::File(OpenOptions::new().create(true).append(true).open(path)?),
+    };
+    Ok(Some(stream))
+}
+
+/// Preview2 output stream of a component writing to a host file or capture buffer.
+enum OutputStream {
+    File(File),
+    Capture(CaptureBuffer),
+}
+
+#[async_trait::async_trait]
+impl HostOutputStream for OutputStream {
+    fn write(&mut self, bytes: Bytes) -> anyhow::Result<(usize, StreamState)> {
+        match self {
+            OutputStream::File(file) => file.write_all(&bytes)?,
+            OutputStream::Capture(buffer) => buffer.write().unwrap().extend_from_slice(&bytes),
+        }
+        Ok((bytes.len(), StreamState::Open))
+    }
+
+    async fn ready(&mut self) -> anyhow::Result<()> {
+        Ok(())
+    }
+}
+
+/// Preview2 input stream of a component reading from a host file or stdin.
+struct ReadStream<R>(R);
+
+#[async_trait::async_trait]
+impl<R: Read + Send + Sync + 'static> HostInputStream for ReadStream<R> {
+    fn read(&mut self, size: usize) -> anyhow::Result<(Bytes, StreamState)> {
+        let mut buffer = vec![0; size];
+        let read = self.0.read(&mut buffer)?;
+        buffer.truncate(read);
+        let state = if read == 0 && size > 0 { StreamState::Closed } else { StreamState::Open };
+        Ok((Bytes::from(buffer), state))
+    }
+
+    async fn ready(&mut self) -> anyhow::Result<()> {
+        Ok(())
+    }
+}
+
+/// Opens an output stream, returning `None` for an inherited stream.
+fn output_stream(config: &StdioConfig, capture: &mut Option<CaptureBuffer>) -> Result<Option<Box<dyn WasiFile>>> {
+    let file: Box<dyn WasiFile> = match config {
+        StdioConfig::Inherit => return Ok(None),
+        StdioConfig::Null => Box::new(WritePipe::new(std::io::sink())),
+        StdioConfig::Capture => {
+            let buffer = capture.get_or_insert_with(CaptureBuffer::default);
+            Box::new(WritePipe::from_shared(Arc::clone(buffer)))
+        },
+        StdioConfig::File(path) => Box::new(WritePipe::new(OpenOptions::new().create(true).append(true).open(path)?)),
+    };
+    Ok(Some(file))
+}
+
+/// # Read-only Directory
+///
+/// Wraps a preopened directory and refuses every operation that writes to it.
+/// Subdirectories opened through it are wrapped as well.
+pub struct ReadOnlyDir(pub Box<dyn WasiDir>);
+
+#[async_trait::async_trait]
+impl WasiDir for ReadOnlyDir {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    async fn open_file(
+        &self,
+        symlink_follow: bool,
+        path: &str,
+        oflags: OFlags,
+        read: bool,
+        write: bool,
+        fdflags: FdFlags,
+    ) -> std::result::Result<OpenResult, wasi_common::Error> {
+        if write || oflags.intersects(OFlags::CREATE | OFlags::EXCLUSIVE | OFlags::TRUNCATE) {
+            return Err(wasi_common::Error::perm());
+        }
+        match self.0.open_file(symlink_follow, path, oflags, read, write, fdflags).await? {
+            OpenResult::Dir(dir) => Ok(OpenResult::Dir(Box::new(ReadOnlyDir(dir)))),
+            file => Ok(file),
+        }
+    }
+
+    async fn create_dir(&self, _path: &str) -> std::result::Result<(), wasi_common::Error> {
+        Err(wasi_common::Error::perm())
+    }
+
+    async fn readdir(
+        &self,
+        cursor: ReaddirCursor,
+    ) -> std::result::Result<Box<dyn Iterator<Item = std::result::Result<ReaddirEntity, wasi_common::Error>> + Send>, wasi_common::Error> {
+        self.0.readdir(cursor).await
+    }
+
+    async fn symlink(&self, _old_path: &str, _new_path: &str) -> std::result::Result<(), wasi_common::Error> {
+        Err(wasi_common::Error::perm())
+    }
+
+    async fn remove_dir(&self, _path: &str) -> std::result::Result<(), wasi_common::Error> {
+        Err(wasi_common::Error::perm())
+    }
+
+    async fn unlink_file(&self, _path: &str) -> std::result::Result<(), wasi_common::Error> {
+        Err(wasi_common::Error::perm())
+    }
+
+    async fn read_link(&self, path: &str) -> std::result::Result<PathBuf, wasi_common::Error> {
+        self.0.read_link(path).await
+    }
+
+    async fn get_filestat(&self) -> std::result::Result<Filestat, wasi_common::Error> {
+        self.0.get_filestat().await
+    }
+
+    async fn get_path_filestat(&self, path: &str, follow_symlinks: bool) -> std::result::Result<Filestat, wasi_common::Error> {
+        self.0.get_path_filestat(path, follow_symlinks).await
+    }
+
+    async fn rename(
+        &self,
+        _path: &str,
+        _dest_dir: &dyn WasiDir,
+        _dest_path: &str,
+    ) -> std::result::Result<(), wasi_common::Error> {
+        Err(wasi_common::Error::perm())
+    }
+
+    async fn hard_link(
+        &self,
+        _path: &str,
+        _target_dir: &dyn WasiDir,
+        _target_path: &str,
+    ) -> std::result::Result<(), wasi_common::Error> {
+        Err(wasi_common::Error::perm())
+    }
+
+    async fn set_times(
+        &self,
+        _path: &str,
+        _atime: Option<SystemTimeSpec>,
+        _mtime: Option<SystemTimeSpec>,
+        _follow_symlinks: bool,
+    ) -> std::result::Result<(), wasi_common::Error> {
+        Err(wasi_common::Error::perm())
+    }
+}
@@ -1,29 +1,206 @@
-use wasmtime::{Module, Instance, Store};
-use wasmtime_wasi::{WasiCtx};
-use std::fs::File;
+use wasmtime::{Engine, Instance, InstancePre, Linker, Store, Func, Val, WasmParams, WasmResults};
+use wasmtime::component::{
+    ComponentNamedList, Func as ComponentFunc, Instance as ComponentInstance, InstancePre as ComponentInstancePre, Lift,
+    Linker as ComponentLinker, Lower, Val as ComponentVal,
+};
+use std::fs::{self, File};
 use std::io::Read;
 use std::collections::HashMap;
-use std::sync::{Arc, RwLock};
+use std::path::{Path, PathBuf};
+use std::sync::{mpsc, Arc, Mutex, RwLock};
+use std::time::{Duration, Instant, SystemTime};
 use notify::Watcher;
 use std::thread;
-use crate::host_import::{init_store_with_wasi, create_dlinkwm_linker};
-use crate::config::DynamicConfig;
-use anyhow::{anyhow, Result as AnyResult};
+use crate::host_import::{create_component_store, create_engine, create_store, create_dlinkwm_linker, HostState};
+use crate::component::{self, create_component_linker};
+use crate::allocator::AllocatorStats;
+use crate::registry::HostMethodRegistry;
+use crate::config::{DlinkWMConfig, DynamicConfig, EngineConfig, PoolConfig};
+use crate::error::{DlinkError, Result};
+use crate::events::{ReloadEvent, ReloadEventKind, ReloadEvents, ReloadTimer};
+use crate::lifecycle::{self, shutdown_instance, HealthStatus};
+use crate::limits::{ExecutionBudget, ResourceLimits, Watchdog};
+use crate::module_cache::ModuleCache;
+use crate::pool::{InstancePool, PoolStats};
+use crate::state::{migrate_state, MigrationOutcome};
+use crate::wasi::CapturedOutput;
+
+/// # Cached Instance
+/// 
+/// A thread-safe handle to an instantiated WASM module and the store that owns it.
+pub type InstanceStore = Arc<RwLock<(Instance, Store<HostState>)>>;
+
+/// # Cached Component
+/// 
+/// A thread-safe handle to an instantiated component and the store that owns it.
+pub type ComponentStore = Arc<RwLock<(ComponentInstance, Store<HostState>)>>;
+
+/// # Call Options
+/// 
+/// Per-call settings of `call_wasm_function_with_options`.
+/// 
+/// # Example
+/// 
+/// ```rust
+/// use dlink_wm::wasm_manager::CallOptions;
+/// 
+/// // Discard the cached instance and start the call on a fresh one
+/// let options = CallOptions { force_fresh: true };
+/// assert_ne!(options, CallOptions::default());
+/// ```
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct CallOptions {
+    /// Replace the cached instance with a new one before the call, even if the
+    /// file has not changed (guest state is lost)
+    pub force_fresh: bool,
+}
+
+/// Identifies the file content a cached instance was created from.
+#[derive(Debug, Clone)]
+struct SourceFingerprint {
+    /// Modification time of the file when it was read
+    modified: Option<SystemTime>,
+    /// Size of the file when it was read
+    len: u64,
+    /// SHA-256 of the file content
+    hash: String,
+}
+
+impl SourceFingerprint {
+    /// Checks whether file metadata matches the file when it was read.
+    fn matches(&self, metadata: &fs::Metadata) -> bool {
+        self.modified == metadata.modified().ok() && self.len == metadata.len()
+    }
+}
+
+/// A published version of a WASM file: the instance shared by its calls, its
+/// pool of instances, or the component instance shared by its calls.
+#[derive(Clone)]
+enum ModuleVersion {
+    Shared(InstanceStore),
+    Pooled(Arc<InstancePool>),
+    Component(ComponentStore),
+}
+
+impl ModuleVersion {
+    /// Checks whether both handles refer to the same version.
+    fn is(&self, other: &ModuleVersion) -> bool {
+        match (self, other) {
+            (ModuleVersion::Shared(a), ModuleVersion::Shared(b)) => Arc::ptr_eq(a, b),
+            (ModuleVersion::Pooled(a), ModuleVersion::Pooled(b)) => Arc::ptr_eq(a, b),
+            (ModuleVersion::Component(a), ModuleVersion::Component(b)) => Arc::ptr_eq(a, b),
+            _ => false,
+        }
+    }
+
+    /// Releases an unpublished version. A pool is retired; a shared instance is
+    /// shut down and dropped once the calls still using it return. Components have
+    /// no shutdown export and are dropped with their last handle.
+    fn release(self, wasm_path: &str) {
+        match self {
+            ModuleVersion::Shared(instance_store) => {
+                if let Ok(mut guard) = instance_store.try_write() {
+                    shutdown_shared(wasm_path, &mut guard);
+                    return;
+                }
+                // Wait for the running calls (or a state migration) on another thread
+                let wasm_path = wasm_path.to_string();
+                thread::spawn(move || shutdown_shared(&wasm_path, &mut instance_store.write().unwrap()));
+            },
+            ModuleVersion::Pooled(pool) => pool.retire(),
+            ModuleVersion::Component(_) => {},
+        }
+    }
+}
+
+/// A reloaded WASM file whose previous version is kept until the new one has
+/// completed its probation calls.
+struct Probation {
+    /// Version restored if the new version traps
+    previous: ModuleVersion,
+    /// File content of `previous`
+    previous_source: SourceFingerprint,
+    /// The new version
+    current: ModuleVersion,
+    /// File content of `current`
+    current_source: SourceFingerprint,
+    /// Calls left before `previous` is released
+    remaining_calls: u64,
+}
 
 /// # WASM Instance Cache
 /// 
 /// Manages the caching of WASM modules and instances to reduce compilation and instantiation overhead.
 /// 
 /// The cache maintains two levels of caching:
-/// 1. **Module Cache**: Stores compiled WASM modules, which can be reused to instantiate multiple instances
+/// 1. **Module Cache**: Stores compiled WASM modules keyed by the SHA-256 of their bytes,
+///    optionally persisted to disk (see `ModuleCache`)
 /// 2. **Instance Cache**: Stores instantiated WASM modules, including their store context
 /// 
 /// This structure is thread-safe and can be shared across multiple threads.
+/// 
+/// All modules and stores share one `Engine`, configured through the `[engine]`
+/// section of the configuration, and one pre-built `Linker`. Instances are created
+/// through `InstancePre`, so imports are resolved once per module.
+/// 
+/// The cache also holds a handle to the configuration so that the typed call API
+/// (`call` and `call_dynamic`) can enforce the entry-function allow-list, and its own
+/// `HostMethodRegistry` so that plugins with different trust levels can live in
+/// one process. Individual modules can be given a dedicated registry with
+/// `set_module_registry`.
+/// 
+/// Hot reloads are atomic: the new version of a file is compiled and instantiated
+/// before it replaces the published one, calls already running finish on the
+/// version they started on, and the previous version is kept for the
+/// `[hot_reload] probation_calls` first calls so it can be restored if the new one
+/// traps.
+/// 
+/// Every instance the cache creates is initialized through the guest's
+/// `dlinkwm_init` export before it is used, and shut down through
+/// `dlinkwm_shutdown` before it is dropped (see the `lifecycle` module).
+/// 
+/// Files may hold core modules or components; the kind is detected from the
+/// file content (see the `component` module). Components are loaded with
+/// `load_component` and called with `call_component` or `call_component_dynamic`,
+/// under the same entry-function rules and hot reload guarantees.
 pub struct WasmInstanceCache {
-    /// Cache of compiled WASM modules (reduces compilation overhead)
-    module_cache: Arc<RwLock<HashMap<String, Module>>>,
+    /// Engine shared by every module and store of the cache
+    engine: Engine,
+    /// Linker with the WASI and DlinkWM host imports, built once per cache
+    linker: Linker<HostState>,
+    /// Linker with the WASI preview2 imports and the host interface for components
+    component_linker: ComponentLinker<HostState>,
+    /// Cache of compiled WASM modules keyed by content hash (reduces compilation overhead)
+    module_cache: Arc<ModuleCache>,
     /// Cache of instantiated WASM modules (each file has one instance)
-    instance_cache: Arc<RwLock<HashMap<String, Arc<RwLock<(Instance, Store<WasiCtx>)>>>>>,
+    instance_cache: Arc<RwLock<HashMap<String, InstanceStore>>>,
+    /// Instance pools of the files configured with a `pool`
+    pools: Arc<RwLock<HashMap<String, Arc<InstancePool>>>>,
+    /// Cache of instantiated components (each file has one instance)
+    components: Arc<RwLock<HashMap<String, ComponentStore>>>,
+    /// Held while a pool is created, so concurrent first calls create it once
+    pool_creation: Arc<Mutex<()>>,
+    /// Files the cached instances were created from, used to detect changes
+    sources: Arc<RwLock<HashMap<String, SourceFingerprint>>>,
+    /// Reloaded files whose previous version is kept for rollback
+    probations: Arc<Mutex<HashMap<String, Probation>>>,
+    /// File contents that failed to load or were rolled back; they are not loaded
+    /// again until the file changes
+    rejected: Arc<RwLock<HashMap<String, SourceFingerprint>>>,
+    /// Subscribers to reload events
+    events: Arc<ReloadEvents>,
+    /// Configuration used to validate entry functions (shared with `DynamicConfig` for hot reload)
+    config: Arc<RwLock<DlinkWMConfig>>,
+    /// Host methods visible to modules loaded by this cache
+    registry: Arc<HostMethodRegistry>,
+    /// Per-module registries overriding `registry`
+    module_registries: Arc<RwLock<HashMap<String, Arc<HostMethodRegistry>>>>,
+}
+
+impl Default for WasmInstanceCache {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl WasmInstanceCache {
@@ -31,127 +208,1491 @@ impl WasmInstanceCache {
     /// 
     /// # Returns
     /// 
-    /// A new instance of `WasmInstanceCache` with empty caches.
+    /// A new instance of `WasmInstanceCache` with empty caches and a default
+    /// configuration (no entry functions are allowed through `call`).
     pub fn new() -> Self {
+        Self::with_config(Arc::new(RwLock::new(DlinkWMConfig::default())))
+    }
+
+    /// Creates a new WASM instance cache bound to a configuration.
+    /// 
+    /// Pass `DynamicConfig::get_config()` to have the cache follow configuration
+    /// hot reloads. The cache gets its own host method registry, which inherits
+    /// from `HostMethodRegistry::global()`.
+    /// 
+    /// # Parameters
+    /// 
+    /// - `config`: Shared configuration used to validate entry functions
+    /// 
+    /// # Returns
+    /// 
+    /// A new instance of `WasmInstanceCache` with empty caches.
+    pub fn with_config(config: Arc<RwLock<DlinkWMConfig>>) -> Self {
+        let registry = Arc::new(HostMethodRegistry::with_parent(HostMethodRegistry::global()));
+        Self::with_registry(config, registry)
+    }
+
+    /// Creates a new WASM instance cache bound to a configuration and a host method registry.
+    /// 
+    /// Use an isolated registry (`HostMethodRegistry::new()`) to hide all globally
+    /// registered methods from the modules of this cache.
+    /// 
+    /// # Parameters
+    /// 
+    /// - `config`: Shared configuration used to validate entry functions
+    /// - `registry`: Host methods visible to modules loaded by this cache
+    /// 
+    /// # Returns
+    /// 
+    /// A new instance of `WasmInstanceCache` with empty caches.
+    pub fn with_registry(config: Arc<RwLock<DlinkWMConfig>>, registry: Arc<HostMethodRegistry>) -> Self {
+        let (engine_config, module_cache_config) = {
+            let config = config.read().unwrap();
+            (config.engine.clone(), config.module_cache.clone())
+        };
+        let engine = create_engine(&engine_config).unwrap_or_else(|e| {
+            log::error!("[Engine] Invalid engine configuration, using defaults: {}", e);
+            create_engine(&EngineConfig::default()).expect("the default engine configuration is valid")
+        });
+        let linker = create_dlinkwm_linker(&engine, Arc::clone(&registry)).expect("host imports are defined once");
+        let component_linker =
+            create_component_linker(&engine, Arc::clone(&registry)).expect("host imports are defined once");
+        let module_cache = Arc::new(ModuleCache::new(engine.clone(), &module_cache_config));
         Self {
-            module_cache: Arc::new(RwLock::new(HashMap::new())),
+            engine,
+            linker,
+            component_linker,
+            module_cache,
             instance_cache: Arc::new(RwLock::new(HashMap::new())),
+            pools: Arc::new(RwLock::new(HashMap::new())),
+            components: Arc::new(RwLock::new(HashMap::new())),
+            pool_creation: Arc::new(Mutex::new(())),
+            sources: Arc::new(RwLock::new(HashMap::new())),
+            probations: Arc::new(Mutex::new(HashMap::new())),
+            rejected: Arc::new(RwLock::new(HashMap::new())),
+            events: Arc::new(ReloadEvents::new()),
+            config,
+            registry,
+            module_registries: Arc::new(RwLock::new(HashMap::new())),
+        }
+    }
+
+    /// Gets the reload event bus of this cache.
+    /// 
+    /// Events are emitted when a file is reloaded, fails to reload, is rolled back
+    /// or, with a `WasmHotReloader`, removed. Share the bus with
+    /// `DynamicConfig::set_events` to also receive configuration changes.
+    pub fn events(&self) -> Arc<ReloadEvents> {
+        Arc::clone(&self.events)
+    }
+
+    /// Gets the engine shared by all modules and instances of this cache.
+    /// 
+    /// The engine is built from the `[engine]` settings of the configuration when
+    /// the cache is created.
+    pub fn engine(&self) -> &Engine {
+        &self.engine
+    }
+
+    /// Gets the compiled module cache, e.g. to list or evict its entries.
+    pub fn module_cache(&self) -> &ModuleCache {
+        &self.module_cache
+    }
+
+    /// Gets the configuration handle used by this cache.
+    pub fn config(&self) -> Arc<RwLock<DlinkWMConfig>> {
+        Arc::clone(&self.config)
+    }
+
+    /// Gets the host method registry of this cache.
+    /// 
+    /// Methods registered here are visible to every module of this cache that has
+    /// no dedicated registry, and to dedicated registries that inherit from it.
+    pub fn registry(&self) -> Arc<HostMethodRegistry> {
+        Arc::clone(&self.registry)
+    }
+
+    /// Gives a module its own host method registry.
+    /// 
+    /// The cached instance of the module is dropped so that the next call links it
+    /// against the new registry. To keep the cache-wide methods available, create
+    /// the registry with `HostMethodRegistry::with_parent(cache.registry())`.
+    /// 
+    /// # Parameters
+    /// 
+    /// - `wasm_path`: Path to the WASM file
+    /// - `registry`: Registry the module's `universal_invoke` resolves methods in
+    /// 
+    /// # Example
+    /// 
+    /// ```rust
+    /// use dlink_wm::registry::HostMethodRegistry;
+    /// use dlink_wm::wasm_manager::WasmInstanceCache;
+    /// use std::sync::Arc;
+    /// 
+    /// let cache = WasmInstanceCache::new();
+    /// let plugin_registry = Arc::new(HostMethodRegistry::with_parent(cache.registry()));
+    /// cache.set_module_registry("wasm/wasm_test.wasm", plugin_registry.clone());
+    /// assert!(Arc::ptr_eq(&cache.registry_for("wasm/wasm_test.wasm"), &plugin_registry));
+    /// ```
+    pub fn set_module_registry(&self, wasm_path: &str, registry: Arc<HostMethodRegistry>) {
+        self.module_registries.write().unwrap().insert(wasm_path.to_string(), registry);
+        self.clear_cache(wasm_path);
+    }
+
+    /// Gets the registry a module is linked against: its dedicated registry if one
+    /// was set with `set_module_registry`, otherwise the cache registry.
+    pub fn registry_for(&self, wasm_path: &str) -> Arc<HostMethodRegistry> {
+        self.module_registries
+            .read()
+            .unwrap()
+            .get(wasm_path)
+            .cloned()
+            .unwrap_or_else(|| self.registry())
+    }
+
+    /// Loads and instantiates a WASM file.
+    /// 
+    /// This function:
+    /// 1. Checks if the instance is already in cache and returns it if found
+    /// 2. If not in cache, creates a store on the shared engine
+    /// 3. Reads the WASM file and looks its content hash up in the module cache
+    /// 4. If not cached, compiles the module and caches it
+    /// 5. Pre-links and instantiates the module and caches the instance
+    /// 
+    /// # Parameters
+    /// 
+    /// - `wasm_path`: Path to the WASM file to load and instantiate
+    /// 
+    /// # Returns
+    /// 
+    /// An `InstanceStore` containing the instantiated WASM module
+    /// and its associated store context.
+    /// 
+    /// # Errors
+    /// 
+    /// Returns an error if:
+    /// - The WASM file cannot be read
+    /// - The module cannot be compiled
+    /// - The module cannot be instantiated
+    /// - The module's `dlinkwm_init` export fails
+    /// - The file holds a component (`DlinkError::UnsupportedModuleKind`); use
+    ///   `load_component` instead
+    pub fn load_and_instantiate(&self, wasm_path: &str) -> Result<InstanceStore> {
+        let wasm_path_str = wasm_path.to_string();
+        
+        // Try to get instance from cache
+        {
+            let cache_read = self.instance_cache.read().unwrap();
+            if let Some(instance_store) = cache_read.get(&wasm_path_str) {
+                return Ok(instance_store.clone());
+            }
+        }
+        
+        // Read and pre-link the module, then instantiate it in a new store
+        let (source, wasm_bytes) = read_source(wasm_path)?;
+        let instance_pre = self.prepare_module(wasm_path, &wasm_bytes)?;
+        let (instance, store) = instantiate(&self.engine, &instance_pre, wasm_path, &self.config, &self.registry_for(wasm_path))?;
+        
+        // Create thread-safe wrapper for instance and store
+        let instance_store = Arc::new(RwLock::new((instance, store)));
+        
+        // Cache instance and Store, remembering which file content they came from,
+        // unless a concurrent load or reload published one first
+        let mut cache_write = self.instance_cache.write().unwrap();
+        if let Some(instance_store) = cache_write.get(&wasm_path_str) {
+            return Ok(instance_store.clone());
+        }
+        self.sources.write().unwrap().insert(wasm_path_str.clone(), source);
+        cache_write.insert(wasm_path_str, instance_store.clone());
+        Ok(instance_store)
+    }
+
+    /// Pre-links the module of a WASM file; the module is only compiled if its
+    /// content is not cached yet.
+    fn prepare_module(&self, wasm_path: &str, wasm_bytes: &[u8]) -> Result<InstancePre<HostState>> {
+        if component::is_component(wasm_bytes) {
+            return Err(DlinkError::UnsupportedModuleKind {
+                wasm_path: wasm_path.to_string(),
+                detail: "the file holds a component, which must be loaded with load_component".to_string(),
+            });
+        }
+        let (_, module) = self.module_cache.get_or_compile(wasm_bytes)?;
+        Ok(self.linker.instantiate_pre(&module)?)
+    }
+
+    /// Pre-links the component of a WASM file; the component is only compiled if
+    /// its content is not cached yet.
+    fn prepare_component(&self, wasm_path: &str, wasm_bytes: &[u8]) -> Result<ComponentInstancePre<HostState>> {
+        let unsupported = |detail: &str| DlinkError::UnsupportedModuleKind {
+            wasm_path: wasm_path.to_string(),
+            detail: detail.to_string(),
+        };
+        if !component::is_component(wasm_bytes) {
+            return Err(unsupported("the file holds a core module, which must be loaded with load_and_instantiate"));
+        }
+        if self.config.read().unwrap().module_config(wasm_path).pool.is_some() {
+            return Err(unsupported("components cannot be pooled"));
+        }
+        let (_, component) = self.module_cache.get_or_compile_component(wasm_bytes)?;
+        Ok(self.component_linker.instantiate_pre(&component)?)
+    }
+
+    /// Loads and instantiates a component.
+    /// 
+    /// This is the component counterpart of `load_and_instantiate`: the instance is
+    /// cached, compiled components are shared by content hash, and the instance
+    /// gets the module's WASI sandbox (through WASI preview2), host method
+    /// registry, resource limits and execution budget. Components have no
+    /// lifecycle exports.
+    /// 
+    /// # Parameters
+    /// 
+    /// - `wasm_path`: Path to the component file to load and instantiate
+    /// 
+    /// # Returns
+    /// 
+    /// A `ComponentStore` containing the component instance and its store.
+    /// 
+    /// # Errors
+    /// 
+    /// Returns an error if:
+    /// - The file cannot be read
+    /// - The component cannot be compiled, linked or instantiated
+    /// - The file holds a core module, or is configured with a `pool`
+    ///   (`DlinkError::UnsupportedModuleKind`)
+    /// 
+    /// # Example
+    /// 
+    /// ```rust
+    /// use dlink_wm::config::{DlinkWMConfig, ModuleConfig};
+    /// use dlink_wm::wasm_manager::WasmInstanceCache;
+    /// use std::sync::{Arc, RwLock};
+    /// 
+    /// // A component creating two core instances
+    /// let wasm_path = std::env::temp_dir().join("dlinkwm_component_limits_doctest.wasm");
+    /// std::fs::write(&wasm_path, r#"(component
+    ///     (core module $m)
+    ///     (core instance (instantiate $m))
+    ///     (core instance (instantiate $m)))"#).unwrap();
+    /// let wasm_path = wasm_path.to_str().unwrap();
+    /// 
+    /// let mut config = DlinkWMConfig::default();
+    /// config.modules.insert(wasm_path.to_string(), ModuleConfig {
+    ///     max_instances: Some(1),
+    ///     ..Default::default()
+    /// });
+    /// let cache = WasmInstanceCache::with_config(Arc::new(RwLock::new(config)));
+    /// assert!(cache.load_component(wasm_path).is_err());
+    /// ```
+    pub fn load_component(&self, wasm_path: &str) -> Result<ComponentStore> {
+        if let Some(component_store) = self.components.read().unwrap().get(wasm_path) {
+            return Ok(component_store.clone());
+        }
+
+        let (source, wasm_bytes) = read_source(wasm_path)?;
+        let instance_pre = self.prepare_component(wasm_path, &wasm_bytes)?;
+        let (instance, store) =
+            instantiate_component(&self.engine, &instance_pre, wasm_path, &self.config, &self.registry_for(wasm_path))?;
+        let component_store = Arc::new(RwLock::new((instance, store)));
+
+        // Unless a concurrent load or reload published one first
+        let mut components = self.components.write().unwrap();
+        if let Some(component_store) = components.get(wasm_path) {
+            return Ok(component_store.clone());
+        }
+        self.sources.write().unwrap().insert(wasm_path.to_string(), source);
+        components.insert(wasm_path.to_string(), component_store.clone());
+        Ok(component_store)
+    }
+
+    /// Checks whether a WASM file holds a component.
+    /// 
+    /// Loaded files are answered from the cache; other files are read.
+    /// 
+    /// # Errors
+    /// 
+    /// Returns `DlinkError::Io` if the file is not loaded and cannot be read.
+    pub fn is_component(&self, wasm_path: &str) -> Result<bool> {
+        if self.components.read().unwrap().contains_key(wasm_path) {
+            return Ok(true);
+        }
+        if self.instance_cache.read().unwrap().contains_key(wasm_path) || self.pools.read().unwrap().contains_key(wasm_path) {
+            return Ok(false);
+        }
+        Ok(component::is_component(&fs::read(wasm_path)?))
+    }
+
+    /// Creates an empty pool of instances of a pre-linked module.
+    fn create_pool(&self, wasm_path: &str, pool_config: PoolConfig, instance_pre: InstancePre<HostState>) -> Arc<InstancePool> {
+        let engine = self.engine.clone();
+        let config = self.config();
+        let registry = self.registry_for(wasm_path);
+        let path = wasm_path.to_string();
+        InstancePool::new(
+            wasm_path,
+            pool_config,
+            Box::new(move || instantiate(&engine, &instance_pre, &path, &config, &registry)),
+        )
+    }
+
+    /// Gets the instance pool of a WASM file, creating it on first use.
+    /// 
+    /// A file is pooled when its `[modules."<path>".pool]` table is configured. The
+    /// pool is created with `min_idle` instances and is replaced when its settings
+    /// change in the configuration.
+    /// 
+    /// # Parameters
+    /// 
+    /// - `wasm_path`: Path to the WASM file
+    /// 
+    /// # Returns
+    /// 
+    /// The pool of the file, or `None` if the file is not configured for pooling.
+    /// 
+    /// # Errors
+    /// 
+    /// Returns an error if the file cannot be read or compiled, or one of the
+    /// `min_idle` instances cannot be created.
+    pub fn pool(&self, wasm_path: &str) -> Result<Option<Arc<InstancePool>>> {
+        let Some(pool_config) = self.config.read().unwrap().module_config(wasm_path).pool else {
+            // Pooling may have been switched off by a configuration reload
+            if let Some(pool) = self.pools.write().unwrap().remove(wasm_path) {
+                pool.retire();
+            }
+            return Ok(None);
+        };
+        let current = |pools: &HashMap<String, Arc<InstancePool>>| {
+            pools.get(wasm_path).filter(|pool| pool.config() == &pool_config).cloned()
+        };
+        if let Some(pool) = current(&self.pools.read().unwrap()) {
+            return Ok(Some(pool));
+        }
+        let _creating = self.pool_creation.lock().unwrap();
+        if let Some(pool) = current(&self.pools.read().unwrap()) {
+            return Ok(Some(pool));
+        }
+
+        let (source, wasm_bytes) = read_source(wasm_path)?;
+        let pool = self.create_pool(wasm_path, pool_config, self.prepare_module(wasm_path, &wasm_bytes)?);
+        pool.fill()?;
+        log::info!("[Pool] Created pool of {} (max {} instances)", wasm_path, pool.config().max_size);
+
+        self.sources.write().unwrap().insert(wasm_path.to_string(), source);
+        if let Some(previous) = self.pools.write().unwrap().insert(wasm_path.to_string(), Arc::clone(&pool)) {
+            previous.retire();
+        }
+        Ok(Some(pool))
+    }
+
+    /// Gets the statistics of the instance pool of a WASM file.
+    /// 
+    /// # Returns
+    /// 
+    /// The pool statistics, or `None` if the file has no pool yet.
+    pub fn pool_stats(&self, wasm_path: &str) -> Option<PoolStats> {
+        self.pools.read().unwrap().get(wasm_path).map(|pool| pool.stats())
+    }
+
+    /// Runs `f` on an instance of a WASM file.
+    /// 
+    /// Pooled files check an instance out of their pool for the duration of `f`, so
+    /// concurrent calls run in parallel. The instance is discarded instead of returned
+    /// to the pool if `f` fails with a trap, or runs out of fuel, time or resources.
+    /// Other files share the cached instance, whose write lock is held for the
+    /// duration of `f`.
+    /// 
+    /// # Parameters
+    /// 
+    /// - `wasm_path`: Path to the WASM file
+    /// - `fresh`: Run `f` on a new instance instead of a reused one. A new shared
+    ///   instance replaces the cached one without its guest state and without a
+    ///   reload event
+    /// - `f`: Function receiving the instance and its store
+    /// 
+    /// # Errors
+    /// 
+    /// Returns an error if the file cannot be loaded or instantiated, or the error
+    /// returned by `f`. Returns `DlinkError::SourceChanged` if `fresh` is set and
+    /// the file changed since it was loaded; `hot_reload` publishes the change.
+    /// 
+    /// # Example
+    /// 
+    /// ```rust
+    /// use dlink_wm::config::{DlinkWMConfig, ModuleConfig, PoolConfig};
+    /// use dlink_wm::wasm_manager::WasmInstanceCache;
+    /// use std::sync::{Arc, RwLock};
+    /// 
+    /// // A guest whose "fail" export corrupts its counter before trapping
+    /// let wasm_path = std::env::temp_dir().join("dlinkwm_pool_trap_doctest.wasm");
+    /// std::fs::write(&wasm_path, r#"(module
+    ///     (global $count (mut i32) (i32.const 0))
+    ///     (func (export "bump") (result i32)
+    ///         (global.set $count (i32.add (global.get $count) (i32.const 1)))
+    ///         (global.get $count))
+    ///     (func (export "fail") (global.set $count (i32.const 100)) unreachable))"#).unwrap();
+    /// let wasm_path = wasm_path.to_str().unwrap();
+    /// 
+    /// let mut config = DlinkWMConfig::default();
+    /// config.entry_functions.insert(wasm_path.to_string(), vec!["bump".to_string(), "fail".to_string()]);
+    /// config.modules.insert(wasm_path.to_string(), ModuleConfig {
+    ///     pool: Some(PoolConfig { max_size: 1, ..Default::default() }),
+    ///     ..Default::default()
+    /// });
+    /// let cache = WasmInstanceCache::with_config(Arc::new(RwLock::new(config)));
+    /// 
+    /// assert_eq!(cache.call::<(), i32>(wasm_path, "bump", ()).unwrap(), 1);
+    /// assert!(cache.call::<(), ()>(wasm_path, "fail", ()).unwrap_err().is_trap());
+    /// 
+    /// // The trapped instance was discarded, so the next call starts from a new one
+    /// assert_eq!(cache.call::<(), i32>(wasm_path, "bump", ()).unwrap(), 1);
+    /// assert_eq!(cache.pool_stats(wasm_path).unwrap().created, 2);
+    /// ```
+    /// 
+    /// ```rust
+    /// use dlink_wm::error::DlinkError;
+    /// use dlink_wm::wasm_manager::WasmInstanceCache;
+    /// 
+    /// let wasm_path = std::env::temp_dir().join("dlinkwm_fresh_doctest.wasm");
+    /// std::fs::write(&wasm_path, r#"(module (global (export "count") (mut i32) (i32.const 0)))"#).unwrap();
+    /// let wasm_path = wasm_path.to_str().unwrap();
+    /// let cache = WasmInstanceCache::new();
+    /// let events = cache.events().subscribe();
+    /// let count = |fresh| {
+    ///     cache.with_instance(wasm_path, fresh, |instance, store| {
+    ///         let count = instance.get_global(&mut *store, "count").unwrap();
+    ///         let value = count.get(&mut *store).unwrap_i32();
+    ///         count.set(&mut *store, (value + 1).into()).unwrap();
+    ///         Ok(value)
+    ///     })
+    /// };
+    /// 
+    /// assert_eq!(count(false).unwrap(), 0);
+    /// assert_eq!(count(false).unwrap(), 1);
+    /// // A fresh instance starts over and is not reported as a reload
+    /// assert_eq!(count(true).unwrap(), 0);
+    /// assert!(events.try_recv().is_err());
+    /// 
+    /// // Changed content is only published by a hot reload
+    /// std::fs::write(wasm_path, r#"(module (global (export "count") (mut i32) (i32.const 10)))"#).unwrap();
+    /// assert!(matches!(count(true), Err(DlinkError::SourceChanged { .. })));
+    /// assert_eq!(count(false).unwrap(), 1);
+    /// ```
+    pub fn with_instance<R>(
+        &self,
+        wasm_path: &str,
+        fresh: bool,
+        f: impl FnOnce(Instance, &mut Store<HostState>) -> Result<R>,
+    ) -> Result<R> {
+        if let Some(pool) = self.pool(wasm_path)? {
+            let mut pooled = pool.checkout(fresh)?;
+            let instance = pooled.instance();
+            let result = f(instance, pooled.store_mut());
+            // A guest that trapped or ran out of budget is in an unknown state
+            match &result {
+                Err(error) if error.is_trap() => pooled.discard(),
+                _ => drop(pooled),
+            }
+            self.complete_call(wasm_path, &ModuleVersion::Pooled(pool), &result);
+            return result;
         }
+
+        if fresh {
+            self.renew(wasm_path)?;
+        }
+        let mut f = Some(f);
+        loop {
+            let instance_store = self.load_and_instantiate(wasm_path)?;
+            let result = {
+                let mut guard = instance_store.write().unwrap();
+                // A reload may have replaced the instance (and taken its state) while this call waited
+                let replaced = self
+                    .instance_cache
+                    .read()
+                    .unwrap()
+                    .get(wasm_path)
+                    .is_some_and(|published| !Arc::ptr_eq(published, &instance_store));
+                if replaced {
+                    continue;
+                }
+                let (instance, ref mut store) = *guard;
+                (f.take().expect("the function runs once"))(instance, store)
+            };
+            self.complete_call(wasm_path, &ModuleVersion::Shared(instance_store), &result);
+            return result;
+        }
+    }
+
+    /// Runs `f` on the instance of a component.
+    /// 
+    /// The counterpart of `with_instance` for components: the write lock of the
+    /// cached instance is held for the duration of `f`, and the call counts against
+    /// the probation of a reloaded file.
+    /// 
+    /// # Parameters
+    /// 
+    /// - `wasm_path`: Path to the component file
+    /// - `fresh`: Run `f` on a new instance instead of the cached one, without a
+    ///   reload event
+    /// - `f`: Function receiving the component instance and its store
+    /// 
+    /// # Errors
+    /// 
+    /// Returns an error if the component cannot be loaded or instantiated, or the
+    /// error returned by `f`. Returns `DlinkError::SourceChanged` if `fresh` is set
+    /// and the file changed since it was loaded.
+    pub fn with_component<R>(
+        &self,
+        wasm_path: &str,
+        fresh: bool,
+        f: impl FnOnce(ComponentInstance, &mut Store<HostState>) -> Result<R>,
+    ) -> Result<R> {
+        if fresh {
+            self.renew(wasm_path)?;
+        }
+        let mut f = Some(f);
+        loop {
+            let component_store = self.load_component(wasm_path)?;
+            let result = {
+                let mut guard = component_store.write().unwrap();
+                // A reload may have replaced the instance while this call waited
+                let replaced = self
+                    .components
+                    .read()
+                    .unwrap()
+                    .get(wasm_path)
+                    .is_some_and(|published| !Arc::ptr_eq(published, &component_store));
+                if replaced {
+                    continue;
+                }
+                let (instance, ref mut store) = *guard;
+                (f.take().expect("the function runs once"))(instance, store)
+            };
+            self.complete_call(wasm_path, &ModuleVersion::Component(component_store), &result);
+            return result;
+        }
+    }
+
+    /// Calls the `dlinkwm_health` export of a WASM file.
+    /// 
+    /// Pooled files have one of their instances checked. The check counts as a
+    /// call, so a trap during the probation of a reloaded file rolls it back. Use a
+    /// `HealthMonitor` to run the checks on a timer.
+    /// 
+    /// # Parameters
+    /// 
+    /// - `wasm_path`: Path to the WASM file
+    /// 
+    /// # Returns
+    /// 
+    /// The status reported by the guest, or `HealthStatus::Unsupported` if the
+    /// module does not export `dlinkwm_health`.
+    /// 
+    /// # Errors
+    /// 
+    /// Returns an error if the file cannot be loaded or instantiated, or the guest
+    /// traps.
+    /// 
+    /// # Example
+    /// 
+    /// ```rust
+    /// use dlink_wm::config::{DlinkWMConfig, ModuleConfig};
+    /// use dlink_wm::error::DlinkError;
+    /// use dlink_wm::lifecycle::HealthStatus;
+    /// use dlink_wm::wasm_manager::WasmInstanceCache;
+    /// use std::sync::{Arc, RwLock};
+    /// 
+    /// // A guest that refuses to start with an empty configuration and is healthy
+    /// // once it received one
+    /// let wasm_path = std::env::temp_dir().join("dlinkwm_lifecycle_doctest.wasm");
+    /// std::fs::write(&wasm_path, r#"(module
+    ///     (memory (export "memory") 1)
+    ///     (global $config_len (mut i32) (i32.const 0))
+    ///     (func (export "dlinkwm_init") (param $ptr i32) (param $len i32) (result i32)
+    ///         (global.set $config_len (local.get $len))
+    ///         (i32.eqz (local.get $len)))
+    ///     (func (export "dlinkwm_health") (result i32)
+    ///         (i32.eqz (global.get $config_len))))"#).unwrap();
+    /// let wasm_path = wasm_path.to_str().unwrap();
+    /// 
+    /// let config = Arc::new(RwLock::new(DlinkWMConfig::default()));
+    /// let cache = WasmInstanceCache::with_config(config.clone());
+    /// let err = cache.check_health(wasm_path).unwrap_err();
+    /// assert!(matches!(err, DlinkError::InitFailed { status: 1, .. }));
+    /// 
+    /// // The init table is passed to the guest as JSON
+    /// config.write().unwrap().modules.insert(wasm_path.to_string(), ModuleConfig {
+    ///     init: Some(toml::toml! { greeting = "hello" }.into()),
+    ///     ..Default::default()
+    /// });
+    /// assert_eq!(cache.check_health(wasm_path).unwrap(), HealthStatus::Healthy);
+    /// ```
+    pub fn check_health(&self, wasm_path: &str) -> Result<HealthStatus> {
+        self.with_instance(wasm_path, false, lifecycle::health)
+    }
+
+    /// Counts a completed call against the probation of a reloaded file, and rolls
+    /// the file back to its previous version if the call trapped.
+    fn complete_call<R>(&self, wasm_path: &str, version: &ModuleVersion, result: &Result<R>) {
+        let mut probations = self.probations.lock().unwrap();
+        let Some(probation) = probations.get_mut(wasm_path) else {
+            return;
+        };
+        // Calls that started before the reload ran on the previous version
+        if !probation.current.is(version) {
+            return;
+        }
+
+        let rollback = match result {
+            Err(error) if error.is_trap() => {
+                let timer = ReloadTimer::start();
+                let probation = probations.remove(wasm_path).expect("probation was just found");
+                log::warn!(
+                    "[HotReload] New version {} of {} trapped, rolling back to {}: {}",
+                    short_hash(&probation.current_source.hash),
+                    wasm_path,
+                    short_hash(&probation.previous_source.hash),
+                    error
+                );
+                let restored_hash = probation.previous_source.hash.clone();
+                if let Some((failed, _)) = self.swap(wasm_path, probation.previous, probation.previous_source) {
+                    failed.release(wasm_path);
+                }
+                let failed_hash = probation.current_source.hash.clone();
+                self.forget_compiled(&failed_hash);
+                self.rejected.write().unwrap().insert(wasm_path.to_string(), probation.current_source);
+                Some(ReloadEvent {
+                    kind: ReloadEventKind::RolledBack { error: error.to_string() },
+                    path: wasm_path.to_string(),
+                    old_hash: Some(failed_hash),
+                    new_hash: Some(restored_hash),
+                    timings: timer.finish(),
+                })
+            },
+            _ => {
+                probation.remaining_calls = probation.remaining_calls.saturating_sub(1);
+                if probation.remaining_calls == 0 {
+                    let probation = probations.remove(wasm_path).expect("probation was just found");
+                    log::info!(
+                        "[HotReload] New version {} of {} passed probation",
+                        short_hash(&probation.current_source.hash),
+                        wasm_path
+                    );
+                    probation.previous.release(wasm_path);
+                    self.forget_compiled(&probation.previous_source.hash);
+                }
+                None
+            },
+        };
+
+        // Subscribers may call into the cache
+        drop(probations);
+        if let Some(event) = rollback {
+            self.events.emit(event);
+        }
+    }
+
+    /// Gets the content hash of the file a cached instance was created from.
+    /// 
+    /// # Parameters
+    /// 
+    /// - `wasm_path`: Path to the WASM file
+    /// 
+    /// # Returns
+    /// 
+    /// The SHA-256 of the loaded file in lowercase hex, or `None` if the file has
+    /// no cached instance.
+    pub fn loaded_hash(&self, wasm_path: &str) -> Option<String> {
+        self.sources.read().unwrap().get(wasm_path).map(|source| source.hash.clone())
+    }
+
+    /// Gets the paths of the files with a cached instance or pool.
+    pub fn loaded_paths(&self) -> Vec<String> {
+        self.sources.read().unwrap().keys().cloned().collect()
+    }
+
+    /// Checks whether a WASM file changed since its cached instance was created.
+    /// 
+    /// The modification time and size are compared first; only if one of them
+    /// differs is the file read and its content hash compared. A file that was
+    /// touched but not changed is therefore not reported as modified.
+    /// 
+    /// # Parameters
+    /// 
+    /// - `wasm_path`: Path to the WASM file
+    /// 
+    /// # Returns
+    /// 
+    /// `true` if the file content differs from the cached instance's, `false` if it
+    /// is the same, the file has no cached instance, or the content already failed
+    /// to load or was rolled back.
+    /// 
+    /// # Errors
+    /// 
+    /// Returns `DlinkError::Io` if the file cannot be read.
+    pub fn is_modified(&self, wasm_path: &str) -> Result<bool> {
+        let Some(source) = self.sources.read().unwrap().get(wasm_path).cloned() else {
+            return Ok(false);
+        };
+        let metadata = fs::metadata(wasm_path)?;
+        let rejected = self.rejected.read().unwrap().get(wasm_path).cloned();
+        if source.matches(&metadata) || rejected.as_ref().is_some_and(|rejected| rejected.matches(&metadata)) {
+            return Ok(false);
+        }
+
+        let hash = ModuleCache::content_hash(&fs::read(wasm_path)?);
+        // Same content: remember the new timestamp so the file is not read again
+        let known = if hash == source.hash {
+            &self.sources
+        } else if rejected.is_some_and(|rejected| rejected.hash == hash) {
+            &self.rejected
+        } else {
+            return Ok(true);
+        };
+        if let Some(source) = known.write().unwrap().get_mut(wasm_path) {
+            source.modified = metadata.modified().ok();
+            source.len = metadata.len();
+        }
+        Ok(false)
+    }
+
+    /// Gets the instance of a WASM file, reloading it first if the file changed.
+    /// 
+    /// Unlike `hot_reload`, the cached instance (and the guest state it holds) is
+    /// kept as long as the file content is unchanged; see `is_modified`.
+    /// 
+    /// # Parameters
+    /// 
+    /// - `wasm_path`: Path to the WASM file to load
+    /// 
+    /// # Returns
+    /// 
+    /// An `InstanceStore` for the current content of the file.
+    /// 
+    /// # Errors
+    /// 
+    /// Returns an error if the file cannot be read, compiled or instantiated. The
+    /// previous instance stays cached and is returned by later calls until the file
+    /// changes again.
+    /// 
+    /// # Example
+    /// 
+    /// ```rust
+    /// use dlink_wm::wasm_manager::WasmInstanceCache;
+    /// use std::sync::Arc;
+    /// 
+    /// let wasm_path = std::env::temp_dir().join("dlinkwm_reload_doctest.wasm");
+    /// std::fs::write(&wasm_path, r#"(module (func (export "version") (result i32) i32.const 1))"#).unwrap();
+    /// let wasm_path = wasm_path.to_str().unwrap();
+    /// let cache = WasmInstanceCache::new();
+    /// 
+    /// let first = cache.reload_if_modified(wasm_path).unwrap();
+    /// assert!(Arc::ptr_eq(&first, &cache.reload_if_modified(wasm_path).unwrap()));
+    /// 
+    /// // Rewriting the same bytes keeps the instance, new bytes replace it
+    /// std::fs::write(wasm_path, std::fs::read(wasm_path).unwrap()).unwrap();
+    /// assert!(Arc::ptr_eq(&first, &cache.reload_if_modified(wasm_path).unwrap()));
+    /// std::fs::write(wasm_path, r#"(module (func (export "version") (result i32) i32.const 22))"#).unwrap();
+    /// assert!(!Arc::ptr_eq(&first, &cache.reload_if_modified(wasm_path).unwrap()));
+    /// ```
+    pub fn reload_if_modified(&self, wasm_path: &str) -> Result<InstanceStore> {
+        if self.is_modified(wasm_path)? {
+            log::info!("[HotReload] Detected WASM change: {}", wasm_path);
+            self.hot_reload(wasm_path)?;
+        }
+        self.load_and_instantiate(wasm_path)
+    }
+
+    /// Calls an entry function with statically typed parameters and results.
+    /// 
+    /// The function must be listed in `entry_functions` for the WASM file, and its
+    /// signature must match `Params -> Results`. The instance is loaded from cache
+    /// (or instantiated on first use) and the results are returned to the caller.
+    /// 
+    /// # Parameters
+    /// 
+    /// - `wasm_path`: Path to the WASM file containing the function
+    /// - `func_name`: Name of the exported function to call
+    /// - `args`: Arguments to pass, e.g. `()` or `(1i32, 2i64)`
+    /// 
+    /// # Returns
+    /// 
+    /// The values returned by the WASM function.
+    /// 
+    /// # Errors
+    /// 
+    /// Returns an error if:
+    /// - The function is not configured as an entry function for the WASM file
+    /// - The WASM file cannot be loaded or instantiated
+    /// - The export is missing or is not a function
+    /// - The function signature does not match `Params -> Results`
+    /// - The function traps during execution
+    /// 
+    /// # Example
+    /// 
+    /// ```rust
+    /// use dlink_wm::config::DlinkWMConfig;
+    /// use dlink_wm::wasm_manager::WasmInstanceCache;
+    /// use std::sync::{Arc, RwLock};
+    /// 
+    /// let mut config = DlinkWMConfig::default();
+    /// config.entry_functions.insert(
+    ///     "wasm/wasm_test.wasm".to_string(),
+    ///     vec!["dlinkwm_print_hello_wasm".to_string()],
+    /// );
+    /// let cache = WasmInstanceCache::with_config(Arc::new(RwLock::new(config)));
+    /// 
+    /// let ptr: i32 = cache.call("wasm/wasm_test.wasm", "dlinkwm_print_hello_wasm", ()).unwrap();
+    /// assert_ne!(ptr, 0);
+    /// 
+    /// // Functions outside the allow-list are refused
+    /// assert!(cache.call::<(), ()>("wasm/wasm_test.wasm", "_start", ()).is_err());
+    /// ```
+    pub fn call<Params, Results>(&self, wasm_path: &str, func_name: &str, args: Params) -> Result<Results>
+    where
+        Params: WasmParams,
+        Results: WasmResults,
+    {
+        self.check_entry_function(wasm_path, func_name)?;
+        self.with_instance(wasm_path, false, |instance, store| {
+            let func = export_func(instance, store, func_name)?;
+            let typed_func = func.typed::<Params, Results>(&*store).map_err(|e| DlinkError::SignatureMismatch {
+                function: func_name.to_string(),
+                detail: e.to_string(),
+            })?;
+            let (budget, _watchdog) = prepare_call(store, &self.config.read().unwrap())?;
+            typed_func.call(&mut *store, args).map_err(|e| call_error(store, &budget, func_name, e))
+        })
+    }
+
+    /// Calls an entry function with dynamically typed parameters and results.
+    /// 
+    /// This is the untyped counterpart of `call`, useful when the signature is only
+    /// known at runtime. The arguments are checked against the function type before
+    /// the call.
+    /// 
+    /// # Parameters
+    /// 
+    /// - `wasm_path`: Path to the WASM file containing the function
+    /// - `func_name`: Name of the exported function to call
+    /// - `args`: Arguments to pass to the function
+    /// 
+    /// # Returns
+    /// 
+    /// The values returned by the WASM function, in declaration order.
+    /// 
+    /// # Errors
+    /// 
+    /// Returns the same errors as `call`, where a signature mismatch means the
+    /// number or types of `args` do not match the function parameters.
+    /// 
+    /// # Example
+    /// 
+    /// ```rust
+    /// use dlink_wm::config::DlinkWMConfig;
+    /// use dlink_wm::wasm_manager::WasmInstanceCache;
+    /// use std::sync::{Arc, RwLock};
+    /// 
+    /// let mut config = DlinkWMConfig::default();
+    /// config.entry_functions.insert(
+    ///     "wasm/wasm_test.wasm".to_string(),
+    ///     vec!["dlinkwm_print_hello_wasm".to_string()],
+    /// );
+    /// let cache = WasmInstanceCache::with_config(Arc::new(RwLock::new(config)));
+    /// 
+    /// let results = cache.call_dynamic("wasm/wasm_test.wasm", "dlinkwm_print_hello_wasm", &[]).unwrap();
+    /// assert_eq!(results.len(), 1);
+    /// assert!(results[0].i32().is_some());
+    /// ```
+    pub fn call_dynamic(&self, wasm_path: &str, func_name: &str, args: &[Val]) -> Result<Vec<Val>> {
+        self.check_entry_function(wasm_path, func_name)?;
+        self.with_instance(wasm_path, false, |instance, store| {
+            let func = export_func(instance, store, func_name)?;
+
+            // Validate the arguments against the function signature
+            let func_type = func.ty(&*store);
+            let param_types: Vec<_> = func_type.params().collect();
+            let arg_types: Vec<_> = args.iter().map(Val::ty).collect();
+            if param_types != arg_types {
+                return Err(DlinkError::SignatureMismatch {
+                    function: func_name.to_string(),
+                    detail: format!("expected parameters {:?}, got {:?}", param_types, arg_types),
+                });
+            }
+
+            let mut results = vec![Val::I32(0); func_type.results().len()];
+            let (budget, _watchdog) = prepare_call(store, &self.config.read().unwrap())?;
+            func.call(&mut *store, args, &mut results).map_err(|e| call_error(store, &budget, func_name, e))?;
+            Ok(results)
+        })
+    }
+
+    /// Calls an entry function of a component with statically typed parameters and
+    /// results.
+    /// 
+    /// The component counterpart of `call`. Functions exported from an interface
+    /// are named `<interface>#<function>`, e.g. `example:math/ops#add`, both here
+    /// and in `entry_functions`.
+    /// 
+    /// # Parameters
+    /// 
+    /// - `wasm_path`: Path to the component file
+    /// - `func_name`: Name of the exported function to call
+    /// - `args`: Arguments to pass, e.g. `()` or `(1i32, "text")`
+    /// 
+    /// # Returns
+    /// 
+    /// The values returned by the component function, as a tuple.
+    /// 
+    /// # Errors
+    /// 
+    /// Returns the same errors as `call`, and `DlinkError::UnsupportedModuleKind`
+    /// if the file holds a core module.
+    /// 
+    /// # Example
+    /// 
+    /// ```rust
+    /// use dlink_wm::config::DlinkWMConfig;
+    /// use dlink_wm::wasm_manager::WasmInstanceCache;
+    /// use std::sync::{Arc, RwLock};
+    /// 
+    /// // A component exporting `add: func(a: s32, b: s32) -> s32`
+    /// let wasm_path = std::env::temp_dir().join("dlinkwm_component_doctest.wasm");
+    /// std::fs::write(&wasm_path, r#"(component
+    ///     (core module $m
+    ///         (func (export "add") (param i32 i32) (result i32)
+    ///             (i32.add (local.get 0) (local.get 1))))
+    ///     (core instance $i (instantiate $m))
+    ///     (func (export "add") (param "a" s32) (param "b" s32) (result s32)
+    ///         (canon lift (core func $i "add"))))"#).unwrap();
+    /// let wasm_path = wasm_path.to_str().unwrap();
+    /// 
+    /// let mut config = DlinkWMConfig::default();
+    /// config.entry_functions.insert(wasm_path.to_string(), vec!["add".to_string()]);
+    /// let cache = WasmInstanceCache::with_config(Arc::new(RwLock::new(config)));
+    /// assert!(cache.is_component(wasm_path).unwrap());
+    /// assert_eq!(cache.call_component::<(i32, i32), (i32,)>(wasm_path, "add", (2, 3)).unwrap(), (5,));
+    /// 
+    /// // Core module APIs refuse components
+    /// assert!(cache.call::<(i32, i32), i32>(wasm_path, "add", (2, 3)).is_err());
+    /// ```
+    pub fn call_component<Params, Results>(&self, wasm_path: &str, func_name: &str, args: Params) -> Result<Results>
+    where
+        Params: ComponentNamedList + Lower,
+        Results: ComponentNamedList + Lift,
+    {
+        self.check_entry_function(wasm_path, func_name)?;
+        self.with_component(wasm_path, false, |instance, store| {
+            let func = component_export_func(instance, store, func_name)?;
+            let typed_func = func.typed::<Params, Results>(&*store).map_err(|e| DlinkError::SignatureMismatch {
+                function: func_name.to_string(),
+                detail: e.to_string(),
+            })?;
+            let (budget, _watchdog) = prepare_call(store, &self.config.read().unwrap())?;
+            let results = typed_func.call(&mut *store, args).map_err(|e| call_error(store, &budget, func_name, e))?;
+            typed_func.post_return(&mut *store).map_err(|e| call_error(store, &budget, func_name, e))?;
+            Ok(results)
+        })
+    }
+
+    /// Calls an entry function of a component with dynamically typed parameters
+    /// and results.
+    /// 
+    /// The component counterpart of `call_dynamic`; function names follow
+    /// `call_component`.
+    /// 
+    /// # Parameters
+    /// 
+    /// - `wasm_path`: Path to the component file
+    /// - `func_name`: Name of the exported function to call
+    /// - `args`: Arguments to pass to the function
+    /// 
+    /// # Returns
+    /// 
+    /// The values returned by the component function, in declaration order.
+    /// 
+    /// # Errors
+    /// 
+    /// Returns the same errors as `call_component`.
+    pub fn call_component_dynamic(&self, wasm_path: &str, func_name: &str, args: &[ComponentVal]) -> Result<Vec<ComponentVal>> {
+        self.check_entry_function(wasm_path, func_name)?;
+        self.with_component(wasm_path, false, |instance, store| {
+            let (budget, _watchdog) = prepare_call(store, &self.config.read().unwrap())?;
+            call_component_func(instance, store, &budget, func_name, args)
+        })
+    }
+
+    /// Gets the CPU budget applied to each call into a WASM file.
+    /// 
+    /// The budget comes from the file's `[modules]` entry in the configuration and
+    /// is read again for every call, so configuration reloads apply immediately.
+    /// 
+    /// # Parameters
+    /// 
+    /// - `wasm_path`: Path to the WASM file
+    /// 
+    /// # Example
+    /// 
+    /// ```rust
+    /// use dlink_wm::config::{DlinkWMConfig, ModuleConfig};
+    /// use dlink_wm::error::DlinkError;
+    /// use dlink_wm::wasm_manager::WasmInstanceCache;
+    /// use std::sync::{Arc, RwLock};
+    /// 
+    /// // A guest that never returns
+    /// let wasm_path = std::env::temp_dir().join("dlinkwm_budget_doctest.wasm");
+    /// std::fs::write(&wasm_path, r#"(module (func (export "spin") (loop br 0)))"#).unwrap();
+    /// let wasm_path = wasm_path.to_str().unwrap();
+    /// 
+    /// let mut config = DlinkWMConfig::default();
+    /// config.entry_functions.insert(wasm_path.to_string(), vec!["spin".to_string()]);
+    /// config.modules.insert(wasm_path.to_string(), ModuleConfig {
+    ///     fuel: Some(10_000),
+    ///     ..Default::default()
+    /// });
+    /// let config = Arc::new(RwLock::new(config));
+    /// let cache = WasmInstanceCache::with_config(config.clone());
+    /// assert_eq!(cache.execution_budget(wasm_path).fuel, Some(10_000));
+    /// assert!(matches!(cache.call::<(), ()>(wasm_path, "spin", ()), Err(DlinkError::OutOfFuel { .. })));
+    /// 
+    /// // Switch to a wall-clock deadline
+    /// config.write().unwrap().modules.get_mut(wasm_path).unwrap().fuel = None;
+    /// config.write().unwrap().modules.get_mut(wasm_path).unwrap().timeout_ms = Some(50);
+    /// assert!(matches!(cache.call::<(), ()>(wasm_path, "spin", ()), Err(DlinkError::Timeout { .. })));
+    /// ```
+    pub fn execution_budget(&self, wasm_path: &str) -> ExecutionBudget {
+        ExecutionBudget::from_config(&self.config.read().unwrap().module_config(wasm_path))
     }
 
-    /// Loads and instantiates a WASM file.
+    /// Gets the memory, table and instance limits of a WASM file.
     /// 
-    /// This function:
-    /// 1. Checks if the instance is already in cache and returns it if found
-    /// 2. If not in cache, reads the WASM file content
-    /// 3. Checks if the module is already compiled and cached
-    /// 4. If not, compiles the module and caches it
-    /// 5. Instantiates the module and caches the instance
+    /// Like the execution budget, the size limits are read again before every call.
+    /// Count limits apply when the file is instantiated.
     /// 
     /// # Parameters
     /// 
-    /// - `wasm_path`: Path to the WASM file to load and instantiate
+    /// - `wasm_path`: Path to the WASM file
     /// 
-    /// # Returns
+    /// # Example
     /// 
-    /// An `Arc<RwLock<(Instance, Store<WasiCtx>)>>` containing the instantiated WASM module
-    /// and its associated store context.
+    /// ```rust
+    /// use dlink_wm::config::{DlinkWMConfig, ModuleConfig};
+    /// use dlink_wm::error::DlinkError;
+    /// use dlink_wm::wasm_manager::WasmInstanceCache;
+    /// use std::sync::{Arc, RwLock};
     /// 
-    /// # Errors
+    /// // A guest that traps when memory.grow fails
+    /// let wasm_path = std::env::temp_dir().join("dlinkwm_limits_doctest.wasm");
+    /// std::fs::write(&wasm_path, r#"(module
+    ///     (memory 1)
+    ///     (func (export "grow")
+    ///         (if (i32.eq (memory.grow (i32.const 10)) (i32.const -1)) (then unreachable))))"#).unwrap();
+    /// let wasm_path = wasm_path.to_str().unwrap();
     /// 
-    /// Returns an error if:
-    /// - The WASM file cannot be read
-    /// - The module cannot be compiled
-    /// - The module cannot be instantiated
-    pub fn load_and_instantiate(&self, wasm_path: &str) -> AnyResult<Arc<RwLock<(Instance, Store<WasiCtx>)>>> {
-        let wasm_path_str = wasm_path.to_string();
-        
-        // Try to get instance from cache
-        {
-            let cache_read = self.instance_cache.read().unwrap();
-            if let Some(instance_store) = cache_read.get(&wasm_path_str) {
-                return Ok(instance_store.clone());
-            }
-        }
-        
-        // Read WASM file content
-        let mut file = File::open(wasm_path)?;
-        let mut wasm_bytes = Vec::new();
-        file.read_to_end(&mut wasm_bytes)?;
-        
-        // Initialize Store and WASI context
-        let (mut store, _, engine) = init_store_with_wasi();
-        
-        // Try to get module from cache
-        let module = {
-            // First check cache with minimal read lock scope
-            {
-                let cache_read = self.module_cache.read().unwrap();
-                if let Some(cached_module) = cache_read.get(&wasm_path_str) {
-                    cached_module.clone()
-                } else {
-                    // If not in cache, release read lock and compile
-                    drop(cache_read);
-                    
-                    // Compile WASM module
-                    let module = Module::new(&engine, &wasm_bytes)?;
-                    self.module_cache.write().unwrap().insert(wasm_path_str.clone(), module.clone());
-                    module
-                }
-            }
-        };
-        
-        // Create and configure Linker with host imports
-        let linker = create_dlinkwm_linker(&engine)?;
+    /// let mut config = DlinkWMConfig::default();
+    /// config.entry_functions.insert(wasm_path.to_string(), vec!["grow".to_string()]);
+    /// config.modules.insert(wasm_path.to_string(), ModuleConfig {
+    ///     max_memory_pages: Some(8),
+    ///     ..Default::default()
+    /// });
+    /// let cache = WasmInstanceCache::with_config(Arc::new(RwLock::new(config)));
+    /// assert_eq!(cache.resource_limits(wasm_path).max_memory_pages, Some(8));
+    /// 
+    /// let err = cache.call::<(), ()>(wasm_path, "grow", ()).unwrap_err();
+    /// assert!(matches!(err, DlinkError::ResourceLimitExceeded { .. }));
+    /// ```
+    pub fn resource_limits(&self, wasm_path: &str) -> ResourceLimits {
+        ResourceLimits::from_config(&self.config.read().unwrap().module_config(wasm_path))
+    }
 
-        // Instantiate module
-        let instance = linker.instantiate(&mut store, &module)?;
-        
-        // Create thread-safe wrapper for instance and store
-        let instance_store = Arc::new(RwLock::new((instance, store)));
-        
-        // Cache instance and Store
-        self.instance_cache.write().unwrap().insert(wasm_path_str, instance_store.clone());
-        Ok(instance_store)
+    /// Validates an entry function against the configuration.
+    fn check_entry_function(&self, wasm_path: &str, func_name: &str) -> Result<()> {
+        let config = self.config.read().unwrap();
+        if !config.is_entry_function(wasm_path, func_name) {
+            return Err(DlinkError::EntryFunctionNotAllowed {
+                function: func_name.to_string(),
+                wasm_path: wasm_path.to_string(),
+                allowed: config.entry_functions.get(wasm_path).cloned().unwrap_or_default(),
+            });
+        }
+        Ok(())
     }
 
     /// Clears the cache for a specific WASM file.
     /// 
-    /// This removes both the compiled module and the instantiated instance from cache.
+    /// This removes the instantiated instance or component, the instance pool and
+    /// the version kept for rollback from cache. Each instance is shut down through
+    /// `dlinkwm_shutdown` before it is dropped; instances still in use are shut
+    /// down when their calls return. Compiled modules are keyed by content, so the next
+    /// load only recompiles the file if its content changed.
     /// 
     /// # Parameters
     /// 
     /// - `wasm_path`: Path to the WASM file whose cache should be cleared
     pub fn clear_cache(&self, wasm_path: &str) {
         let wasm_path_str = wasm_path.to_string();
-        self.module_cache.write().unwrap().remove(&wasm_path_str);
-        self.instance_cache.write().unwrap().remove(&wasm_path_str);
+        if let Some(probation) = self.probations.lock().unwrap().remove(&wasm_path_str) {
+            probation.previous.release(wasm_path);
+            self.forget_compiled(&probation.previous_source.hash);
+        }
+        self.rejected.write().unwrap().remove(&wasm_path_str);
+        let source = self.sources.write().unwrap().remove(&wasm_path_str);
+        if let Some(source) = source {
+            self.forget_compiled(&source.hash);
+        }
+        if let Some(pool) = self.pools.write().unwrap().remove(&wasm_path_str) {
+            pool.retire();
+        }
+        if let Some(instance_store) = self.instance_cache.write().unwrap().remove(&wasm_path_str) {
+            ModuleVersion::Shared(instance_store).release(wasm_path);
+        }
+        self.components.write().unwrap().remove(&wasm_path_str);
+    }
+
+    /// Gets the host allocation accounting for a cached instance.
+    /// 
+    /// # Parameters
+    /// 
+    /// - `wasm_path`: Path to the WASM file whose instance should be inspected
+    /// 
+    /// # Returns
+    /// 
+    /// The allocator statistics of the instance's store, or `None` if the file has
+    /// no cached instance.
+    pub fn allocator_stats(&self, wasm_path: &str) -> Option<AllocatorStats> {
+        let cache_read = self.instance_cache.read().unwrap();
+        cache_read
+            .get(wasm_path)
+            .map(|instance_store| instance_store.read().unwrap().1.data().allocator_stats())
+    }
+
+    /// Takes the output a cached instance or component wrote to its captured stdio
+    /// streams.
+    /// 
+    /// Streams are captured when the file's `[modules."<path>".wasi]` configuration
+    /// sets `stdout` or `stderr` to `"capture"`. The buffers are emptied.
+    /// 
+    /// # Parameters
+    /// 
+    /// - `wasm_path`: Path to the WASM file whose instance should be inspected
+    /// 
+    /// # Returns
+    /// 
+    /// The captured output, or `None` if the file has no cached instance.
+    /// 
+    /// # Example
+    /// 
+    /// ```rust
+    /// use dlink_wm::config::{DlinkWMConfig, StdioConfig};
+    /// use dlink_wm::wasm_manager::WasmInstanceCache;
+    /// use std::sync::{Arc, RwLock};
+    /// 
+    /// // A guest that prints "hi" through WASI
+    /// let wasm_path = std::env::temp_dir().join("dlinkwm_capture_doctest.wasm");
+    /// std::fs::write(&wasm_path, r#"(module
+    ///     (import "wasi_snapshot_preview1" "fd_write" (func $fd_write (param i32 i32 i32 i32) (result i32)))
+    ///     (memory (export "memory") 1)
+    ///     (data (i32.const 16) "hi\n")
+    ///     (func (export "hello")
+    ///         (i32.store (i32.const 0) (i32.const 16))
+    ///         (i32.store (i32.const 4) (i32.const 3))
+    ///         (drop (call $fd_write (i32.const 1) (i32.const 0) (i32.const 1) (i32.const 8)))))"#).unwrap();
+    /// let wasm_path = wasm_path.to_str().unwrap();
+    /// 
+    /// let mut config = DlinkWMConfig::default();
+    /// config.entry_functions.insert(wasm_path.to_string(), vec!["hello".to_string()]);
+    /// config.modules.entry(wasm_path.to_string()).or_default().wasi.stdout = StdioConfig::Capture;
+    /// let cache = WasmInstanceCache::with_config(Arc::new(RwLock::new(config)));
+    /// 
+    /// cache.call::<(), ()>(wasm_path, "hello", ()).unwrap();
+    /// assert_eq!(cache.take_captured_output(wasm_path).unwrap().stdout, b"hi\n");
+    /// ```
+    pub fn take_captured_output(&self, wasm_path: &str) -> Option<CapturedOutput> {
+        if let Some(component_store) = self.components.read().unwrap().get(wasm_path) {
+            return Some(component_store.read().unwrap().1.data().take_captured_output());
+        }
+        let cache_read = self.instance_cache.read().unwrap();
+        cache_read
+            .get(wasm_path)
+            .map(|instance_store| instance_store.read().unwrap().1.data().take_captured_output())
     }
 
     /// Triggers a hot reload for a specific WASM file.
     /// 
     /// This function:
-    /// 1. Clears the cache for the specified WASM file
-    /// 2. Reloads and reinstantiates the file
-    /// 3. Returns the newly instantiated module
+    /// 1. Reads, compiles, instantiates and initializes (`dlinkwm_init`) the
+    ///    current content of the file; for a pooled file, a new pool is created and
+    ///    filled
+    /// 2. Publishes the new version in place of the previous one in a single swap;
+    ///    calls already running finish on the previous version
+    /// 3. Moves the guest state from the previous shared instance to the new one
+    ///    if both export the state migration protocol (see the `state` module);
+    ///    calls wait while the state is moved
+    /// 4. Keeps the previous version for `[hot_reload] probation_calls` calls and
+    ///    restores it if one of them traps; the restored version has the state it
+    ///    had when it was replaced. The version that is let go is shut down
+    ///    through `dlinkwm_shutdown`
+    /// 5. Emits a `Reloaded` or `ReloadFailed` event on `events()`
+    /// 
+    /// A file may switch between a core module and a component; components skip
+    /// the lifecycle exports and state migration.
     /// 
     /// # Parameters
     /// 
     /// - `wasm_path`: Path to the WASM file to hot reload
     /// 
-    /// # Returns
+    /// # Errors
     /// 
-    /// An `Arc<RwLock<(Instance, Store<WasiCtx>)>>` containing the newly instantiated WASM module
-    /// and its associated store context.
+    /// Returns an error if the WASM file cannot be read, compiled or instantiated,
+    /// its `dlinkwm_init` fails, or the new instance traps while restoring the
+    /// migrated state. The
+    /// previous version then stays published, and `is_modified` ignores the failed
+    /// content.
     /// 
-    /// # Errors
+    /// # Example
     /// 
-    /// Returns an error if the WASM file cannot be reloaded and reinstantiated.
-    pub fn hot_reload(&self, wasm_path: &str) -> AnyResult<Arc<RwLock<(Instance, Store<WasiCtx>)>>> {
-        // Clear cache to ensure fresh reload
-        self.clear_cache(wasm_path);
-        // Reload and instantiate
-        self.load_and_instantiate(wasm_path)
+    /// ```rust
+    /// use dlink_wm::config::DlinkWMConfig;
+    /// use dlink_wm::wasm_manager::WasmInstanceCache;
+    /// use std::sync::{Arc, RwLock};
+    /// 
+    /// let wasm_path = std::env::temp_dir().join("dlinkwm_hot_reload_doctest.wasm");
+    /// std::fs::write(&wasm_path, r#"(module (func (export "version") (result i32) i32.const 1))"#).unwrap();
+    /// let wasm_path = wasm_path.to_str().unwrap();
+    /// 
+    /// let mut config = DlinkWMConfig::default();
+    /// config.entry_functions.insert(wasm_path.to_string(), vec!["version".to_string()]);
+    /// let cache = WasmInstanceCache::with_config(Arc::new(RwLock::new(config)));
+    /// assert_eq!(cache.call::<(), i32>(wasm_path, "version", ()).unwrap(), 1);
+    /// 
+    /// // A version that does not compile is never published
+    /// std::fs::write(wasm_path, "(module (func").unwrap();
+    /// assert!(cache.hot_reload(wasm_path).is_err());
+    /// assert_eq!(cache.call::<(), i32>(wasm_path, "version", ()).unwrap(), 1);
+    /// 
+    /// // A version that traps during its probation is rolled back
+    /// std::fs::write(wasm_path, r#"(module (func (export "version") (result i32) unreachable))"#).unwrap();
+    /// cache.hot_reload(wasm_path).unwrap();
+    /// assert!(cache.call::<(), i32>(wasm_path, "version", ()).unwrap_err().is_trap());
+    /// assert_eq!(cache.call::<(), i32>(wasm_path, "version", ()).unwrap(), 1);
+    /// assert!(!cache.is_modified(wasm_path).unwrap());
+    /// 
+    /// // Only the published version stays compiled in memory
+    /// let entries = cache.module_cache().entries().unwrap();
+    /// assert_eq!(entries.iter().filter(|entry| entry.in_memory).count(), 1);
+    /// ```
+    pub fn hot_reload(&self, wasm_path: &str) -> Result<()> {
+        let mut timer = ReloadTimer::start();
+        let old_hash = self.loaded_hash(wasm_path);
+        let mut event = ReloadEvent {
+            kind: ReloadEventKind::Reloaded,
+            path: wasm_path.to_string(),
+            old_hash,
+            new_hash: None,
+            timings: timer.finish(),
+        };
+
+        let result = read_source(wasm_path).and_then(|(source, wasm_bytes)| {
+            event.new_hash = Some(source.hash.clone());
+            let version = match self.build_version(wasm_path, &wasm_bytes, &mut timer) {
+                Ok(version) => version,
+                Err(e) => {
+                    self.rejected.write().unwrap().insert(wasm_path.to_string(), source);
+                    return Err(e);
+                },
+            };
+
+            // Hold the outgoing instance until the new version is published, so no
+            // call changes the state after it was saved
+            let outgoing = match &version {
+                ModuleVersion::Shared(_) => self.instance_cache.read().unwrap().get(wasm_path).cloned(),
+                _ => None,
+            };
+            let mut outgoing_guard = outgoing.as_ref().map(|instance_store| instance_store.write().unwrap());
+            if let (Some(outgoing), ModuleVersion::Shared(incoming)) = (outgoing_guard.as_deref_mut(), &version) {
+                if let Err(e) = self.migrate(wasm_path, outgoing, incoming) {
+                    self.rejected.write().unwrap().insert(wasm_path.to_string(), source);
+                    return Err(e);
+                }
+            }
+            self.publish(wasm_path, version, source);
+            Ok(())
+        });
+
+        if let Err(e) = &result {
+            event.kind = ReloadEventKind::ReloadFailed { error: e.to_string() };
+        }
+        event.timings = timer.finish();
+        self.events.emit(event);
+        result
+    }
+
+    /// Replaces the published version of a WASM file with a new one of the same
+    /// content, without its guest state. No reload event is emitted; a file that
+    /// changed since it was loaded is refused and left to `hot_reload`.
+    fn renew(&self, wasm_path: &str) -> Result<()> {
+        let Some(loaded_hash) = self.loaded_hash(wasm_path) else {
+            // Not loaded yet: the first load creates a new instance anyway
+            return Ok(());
+        };
+        let (source, wasm_bytes) = read_source(wasm_path)?;
+        if source.hash != loaded_hash {
+            return Err(DlinkError::SourceChanged { wasm_path: wasm_path.to_string() });
+        }
+        let version = self.build_version(wasm_path, &wasm_bytes, &mut ReloadTimer::start())?;
+        self.publish(wasm_path, version, source);
+        Ok(())
+    }
+
+    /// Moves the guest state of the outgoing instance of a WASM file into the
+    /// incoming one.
+    fn migrate(&self, wasm_path: &str, outgoing: &mut (Instance, Store<HostState>), incoming: &InstanceStore) -> Result<()> {
+        let mut incoming = incoming.write().unwrap();
+        let config = self.config.read().unwrap();
+        let (old_instance, ref mut old_store) = *outgoing;
+        let (new_instance, ref mut new_store) = *incoming;
+        match migrate_state((old_instance, old_store), (new_instance, new_store), &config)? {
+            MigrationOutcome::Restored { from_version, to_version, bytes } => log::info!(
+                "[HotReload] Migrated {} bytes of state of {} (version {} -> {})",
+                bytes,
+                wasm_path,
+                from_version,
+                to_version
+            ),
+            MigrationOutcome::Skipped { reason } => {
+                log::debug!("[HotReload] Not migrating state of {}: {}", wasm_path, reason)
+            },
+        }
+        Ok(())
+    }
+
+    /// Compiles and instantiates a WASM file without publishing it. Pooled files
+    /// get a new pool holding at least one instance.
+    fn build_version(&self, wasm_path: &str, wasm_bytes: &[u8], timer: &mut ReloadTimer) -> Result<ModuleVersion> {
+        if component::is_component(wasm_bytes) {
+            let instance_pre = self.prepare_component(wasm_path, wasm_bytes)?;
+            timer.compiled();
+            let (instance, store) =
+                instantiate_component(&self.engine, &instance_pre, wasm_path, &self.config, &self.registry_for(wasm_path))?;
+            timer.instantiated();
+            return Ok(ModuleVersion::Component(Arc::new(RwLock::new((instance, store)))));
+        }
+
+        let instance_pre = self.prepare_module(wasm_path, wasm_bytes)?;
+        timer.compiled();
+        let pool_config = self.config.read().unwrap().module_config(wasm_path).pool;
+        let version = match pool_config {
+            Some(pool_config) => {
+                let pool = self.create_pool(wasm_path, pool_config, instance_pre);
+                pool.fill()?;
+                // Instantiate at least once, even with `min_idle = 0`
+                drop(pool.checkout(false)?);
+                ModuleVersion::Pooled(pool)
+            },
+            None => {
+                let (instance, store) = instantiate(&self.engine, &instance_pre, wasm_path, &self.config, &self.registry_for(wasm_path))?;
+                ModuleVersion::Shared(Arc::new(RwLock::new((instance, store))))
+            },
+        };
+        timer.instantiated();
+        Ok(version)
+    }
+
+    /// Publishes a new version of a WASM file and puts the previous one on
+    /// probation.
+    fn publish(&self, wasm_path: &str, version: ModuleVersion, source: SourceFingerprint) {
+        let mut probations = self.probations.lock().unwrap();
+        let replaced = self.swap(wasm_path, version.clone(), source.clone());
+        self.rejected.write().unwrap().remove(wasm_path);
+
+        // While a version is on probation, the version before it stays the rollback target
+        let rollback = match probations.remove(wasm_path) {
+            Some(probation) => {
+                if let Some((unproven, unproven_source)) = replaced {
+                    unproven.release(wasm_path);
+                    self.forget_compiled(&unproven_source.hash);
+                }
+                Some((probation.previous, probation.previous_source))
+            },
+            None => replaced,
+        };
+        let Some((previous, previous_source)) = rollback else {
+            log::info!("[HotReload] Loaded {} ({})", wasm_path, short_hash(&source.hash));
+            return;
+        };
+
+        let probation_calls = self.config.read().unwrap().hot_reload.probation_calls;
+        log::info!(
+            "[HotReload] Reloaded {} ({} -> {})",
+            wasm_path,
+            short_hash(&previous_source.hash),
+            short_hash(&source.hash)
+        );
+        if probation_calls == 0 || previous_source.hash == source.hash {
+            previous.release(wasm_path);
+            self.forget_compiled(&previous_source.hash);
+            return;
+        }
+        probations.insert(wasm_path.to_string(), Probation {
+            previous,
+            previous_source,
+            current: version,
+            current_source: source,
+            remaining_calls: probation_calls,
+        });
+    }
+
+    /// Replaces the published version of a WASM file.
+    /// 
+    /// Each map is updated under its own write lock, so callers see either the
+    /// previous or the new version. Versions of the other kinds (the file switched
+    /// between shared, pooled and component) are released.
+    /// 
+    /// # Returns
+    /// 
+    /// The replaced version and its file content, if the file had one.
+    fn swap(&self, wasm_path: &str, version: ModuleVersion, source: SourceFingerprint) -> Option<(ModuleVersion, SourceFingerprint)> {
+        let shared = || self.instance_cache.write().unwrap().remove(wasm_path).map(ModuleVersion::Shared);
+        let pooled = || self.pools.write().unwrap().remove(wasm_path).map(ModuleVersion::Pooled);
+        let component = || self.components.write().unwrap().remove(wasm_path).map(ModuleVersion::Component);
+        let (replaced, stale) = match version {
+            ModuleVersion::Shared(instance_store) => (
+                self.instance_cache.write().unwrap().insert(wasm_path.to_string(), instance_store).map(ModuleVersion::Shared),
+                [pooled(), component()],
+            ),
+            ModuleVersion::Pooled(pool) => (
+                self.pools.write().unwrap().insert(wasm_path.to_string(), pool).map(ModuleVersion::Pooled),
+                [shared(), component()],
+            ),
+            ModuleVersion::Component(component_store) => (
+                self.components.write().unwrap().insert(wasm_path.to_string(), component_store).map(ModuleVersion::Component),
+                [shared(), pooled()],
+            ),
+        };
+        let replaced_source = self.sources.write().unwrap().insert(wasm_path.to_string(), source);
+        for stale in stale.into_iter().flatten() {
+            stale.release(wasm_path);
+        }
+        match (replaced, replaced_source) {
+            (Some(replaced), Some(replaced_source)) => Some((replaced, replaced_source)),
+            (Some(replaced), None) => {
+                replaced.release(wasm_path);
+                None
+            },
+            (None, _) => None,
+        }
+    }
+
+    /// Drops the compiled code of a released version from the memory of the module
+    /// cache, unless a published file has the same content. The artifact on disk is
+    /// kept, so rolling back or reloading the content again does not recompile it.
+    fn forget_compiled(&self, hash: &str) {
+        if self.sources.read().unwrap().values().any(|source| source.hash == hash) {
+            return;
+        }
+        if self.module_cache.forget(hash) {
+            log::debug!("[HotReload] Dropped compiled module {} from memory", short_hash(hash));
+        }
     }
 }
 
@@ -159,21 +1700,71 @@ impl WasmInstanceCache {
 /// 
 /// Monitors WASM files for changes and automatically triggers hot reloads when they change.
 /// 
-/// This structure watches a directory for changes to `.wasm` files and automatically
-/// calls `hot_reload` on the associated `WasmInstanceCache` when changes are detected.
+/// This structure watches a directory for changes to `.wasm` files and calls
+/// `hot_reload` on the associated `WasmInstanceCache` for the loaded files that
+/// changed. Creations, modifications, renames and removals are all handled, so files
+/// written in place and files replaced through an atomic rename (as cargo and
+/// wasm-opt do) are both picked up.
+/// 
+/// Events are debounced per file: a file is only reloaded once no event arrived for
+/// it during the debounce window, so partially written files are not loaded. A
+/// removed file keeps its loaded version.
 /// 
 /// The hot reloader runs in a separate background thread, allowing the main application
-/// to continue executing while monitoring for changes.
+/// to continue executing while monitoring for changes. It runs until the
+/// `HotReloadHandle` returned by `start` is stopped or dropped.
+/// 
+/// # Example
+/// 
+/// ```rust
+/// use dlink_wm::config::DlinkWMConfig;
+/// use dlink_wm::wasm_manager::{WasmHotReloader, WasmInstanceCache};
+/// use std::sync::{Arc, RwLock};
+/// use std::time::{Duration, Instant};
+/// 
+/// let dir = std::env::temp_dir().join("dlinkwm_reloader_doctest");
+/// std::fs::create_dir_all(&dir).unwrap();
+/// let wasm_path = dir.join("plugin.wasm");
+/// let version = |n: i32| format!(r#"(module (func (export "version") (result i32) i32.const {n}))"#);
+/// std::fs::write(&wasm_path, version(1)).unwrap();
+/// let wasm_path = wasm_path.to_str().unwrap();
+/// 
+/// let mut config = DlinkWMConfig::default();
+/// config.entry_functions.insert(wasm_path.to_string(), vec!["version".to_string()]);
+/// let cache = Arc::new(WasmInstanceCache::with_config(Arc::new(RwLock::new(config))));
+/// assert_eq!(cache.call::<(), i32>(wasm_path, "version", ()).unwrap(), 1);
+/// 
+/// let reloader = WasmHotReloader::new(cache.clone(), dir.to_str().unwrap())
+///     .with_debounce(Duration::from_millis(50));
+/// let handle = reloader.start().unwrap();
+/// 
+/// // Replace the file through an atomic rename
+/// let staging = dir.join("plugin.wasm.tmp");
+/// std::fs::write(&staging, version(2)).unwrap();
+/// std::fs::rename(&staging, wasm_path).unwrap();
+/// 
+/// let deadline = Instant::now() + Duration::from_secs(10);
+/// while cache.call::<(), i32>(wasm_path, "version", ()).unwrap() != 2 {
+///     assert!(Instant::now() < deadline, "the file was not reloaded");
+///     std::thread::sleep(Duration::from_millis(20));
+/// }
+/// handle.stop();
+/// ```
 pub struct WasmHotReloader {
     /// Reference to the WASM instance cache to reload modules from
     instance_cache: Arc<WasmInstanceCache>,
     /// Directory path to watch for WASM file changes
     watch_path: String,
+    /// Time a file must stay unchanged before it is reloaded
+    debounce: Duration,
 }
 
 impl WasmHotReloader {
     /// Creates a new WASM hot reload manager.
     /// 
+    /// The debounce window is read from `[hot_reload] debounce_ms` of the cache's
+    /// configuration.
+    /// 
     /// # Parameters
     /// 
     /// - `instance_cache`: Reference to the WASM instance cache to use for reloading
@@ -183,71 +1774,215 @@ impl WasmHotReloader {
     /// 
     /// A new instance of `WasmHotReloader` configured to watch the specified directory.
     pub fn new(instance_cache: Arc<WasmInstanceCache>, watch_path: &str) -> Self {
+        let debounce_ms = instance_cache.config().read().unwrap().hot_reload.debounce_ms;
         Self {
             instance_cache,
             watch_path: watch_path.to_string(),
+            debounce: Duration::from_millis(debounce_ms),
         }
     }
 
+    /// Sets the time a file must stay unchanged before it is reloaded.
+    /// 
+    /// # Parameters
+    /// 
+    /// - `debounce`: Debounce window applied to each file
+    pub fn with_debounce(mut self, debounce: Duration) -> Self {
+        self.debounce = debounce;
+        self
+    }
+
     /// Starts the hot reload monitoring thread.
     /// 
     /// This function spawns a background thread that:
     /// 1. Watches the specified directory for file changes
-    /// 2. Detects when `.wasm` files are modified
-    /// 3. Automatically triggers hot reload for the modified files
+    /// 2. Collects the `.wasm` files that were created, modified, renamed or removed
+    /// 3. Once a file has been quiet for the debounce window, reloads it if it is
+    ///    loaded in the cache and its content changed
+    /// 
+    /// # Returns
+    /// 
+    /// A `HotReloadHandle` that keeps the watcher alive; monitoring stops when it is
+    /// stopped or dropped.
+    /// 
+    /// # Errors
     /// 
-    /// The monitoring continues until the application exits or the watcher encounters an error.
-    pub fn start(&self) {
+    /// Returns `DlinkError::Watcher` if the watcher cannot be created or the
+    /// directory cannot be watched.
+    pub fn start(&self) -> Result<HotReloadHandle> {
         // Create communication channel for watcher events
-        let (tx, rx) = std::sync::mpsc::channel();
-        let mut watcher = notify::RecommendedWatcher::new(tx, notify::Config::default()).unwrap();
+        let (tx, rx) = mpsc::channel();
+        let event_tx = tx.clone();
+        let mut watcher = notify::RecommendedWatcher::new(
+            move |event| {
+                // The receiver is gone once the handle is stopped
+                let _ = event_tx.send(WatchMessage::Event(event));
+            },
+            notify::Config::default(),
+        )?;
         
         // Recursively watch the directory
-        watcher.watch(std::path::Path::new(&self.watch_path), notify::RecursiveMode::Recursive).unwrap();
+        watcher.watch(Path::new(&self.watch_path), notify::RecursiveMode::Recursive)?;
         
-        let instance_cache_clone = self.instance_cache.clone();
-        let watch_path_clone = self.watch_path.clone();
+        let instance_cache = self.instance_cache.clone();
+        let watch_path = self.watch_path.clone();
+        let debounce = self.debounce;
         
         // Start monitoring thread
-        thread::spawn(move || {
+        let thread = thread::spawn(move || {
+            // Last event time of each changed file
+            let mut pending: HashMap<PathBuf, Instant> = HashMap::new();
             loop {
-                match rx.recv() {
-                    Ok(event_result) => match event_result {
-                        Ok(event) => {
-                            // Handle file modification events
-                            if let notify::EventKind::Modify(notify::event::ModifyKind::Data(_)) = event.kind {
-                                for path in event.paths {
-                                    // Check if the modified file is a WASM file
-                                    if let Some(ext) = path.extension() {
-                                        if ext == "wasm" {
-                                            let wasm_path = path.to_string_lossy().to_string();
-                                            log::info!("[HotReload] Detected WASM change: {}", wasm_path);
-                                            
-                                            // Trigger hot reload
-                                            match instance_cache_clone.hot_reload(&wasm_path) {
-                                                Ok(_) => log::info!("[HotReload] Successfully hot reloaded: {}", wasm_path),
-                                                Err(e) => log::error!("[HotReload] Failed to hot reload: {}, error: {}", wasm_path, e),
-                                            }
-                                        }
-                                    }
+                let message = match pending.values().min() {
+                    Some(first) => rx.recv_timeout((*first + debounce).saturating_duration_since(Instant::now())),
+                    None => rx.recv().map_err(|_| mpsc::RecvTimeoutError::Disconnected),
+                };
+                match message {
+                    Ok(WatchMessage::Event(Ok(event))) => {
+                        if matches!(
+                            event.kind,
+                            notify::EventKind::Create(_) | notify::EventKind::Modify(_) | notify::EventKind::Remove(_)
+                        ) {
+                            for path in event.paths {
+                                if path.extension().is_some_and(|ext| ext == "wasm") {
+                                    pending.insert(path, Instant::now());
                                 }
                             }
-                        },
-                        Err(e) => {
-                            log::error!("[HotReload] Watcher error: {}", e);
-                            break; // Exit loop on watcher error
-                        },
+                        }
                     },
-                    Err(_) => {
-                        // Channel closed, exit loop
-                        break;
-                    }
+                    Ok(WatchMessage::Event(Err(e))) => log::error!("[HotReload] Watcher error: {}", e),
+                    Ok(WatchMessage::Stop) | Err(mpsc::RecvTimeoutError::Disconnected) => break,
+                    Err(mpsc::RecvTimeoutError::Timeout) => {},
+                }
+
+                // Reload the files whose events have settled
+                let now = Instant::now();
+                let settled: Vec<PathBuf> = pending
+                    .iter()
+                    .filter(|(_, last_event)| now.duration_since(**last_event) >= debounce)
+                    .map(|(path, _)| path.clone())
+                    .collect();
+                for path in settled {
+                    pending.remove(&path);
+                    reload_changed(&instance_cache, &path);
                 }
             }
+            log::info!("[HotReload] Stopped watching: {}", watch_path);
         });
         
-        log::info!("[HotReload] Started watching: {}", watch_path_clone);
+        log::info!("[HotReload] Started watching: {}", self.watch_path);
+        Ok(HotReloadHandle {
+            watcher: Some(watcher),
+            sender: tx,
+            thread: Some(thread),
+        })
+    }
+}
+
+/// Message received by the hot reload thread.
+enum WatchMessage {
+    /// File system event reported by the watcher
+    Event(notify::Result<notify::Event>),
+    /// Sent by `HotReloadHandle` to stop the thread
+    Stop,
+}
+
+/// # Hot Reload Handle
+/// 
+/// Keeps a `WasmHotReloader` running. Monitoring stops when the handle is stopped
+/// or dropped.
+#[must_use = "the hot reloader stops watching when its handle is dropped"]
+pub struct HotReloadHandle {
+    /// File watcher feeding the reload thread
+    watcher: Option<notify::RecommendedWatcher>,
+    /// Sends the stop message to the reload thread
+    sender: mpsc::Sender<WatchMessage>,
+    /// The reload thread
+    thread: Option<thread::JoinHandle<()>>,
+}
+
+impl HotReloadHandle {
+    /// Stops watching and waits for the reload thread to exit, including a reload
+    /// it is running.
+    pub fn stop(mut self) {
+        self.shutdown();
+    }
+
+    /// Checks whether the reload thread is still running.
+    pub fn is_running(&self) -> bool {
+        self.thread.as_ref().is_some_and(|thread| !thread.is_finished())
+    }
+
+    fn shutdown(&mut self) {
+        drop(self.watcher.take());
+        let _ = self.sender.send(WatchMessage::Stop);
+        if let Some(thread) = self.thread.take() {
+            if thread.join().is_err() {
+                log::error!("[HotReload] Reload thread panicked");
+            }
+        }
+    }
+}
+
+impl Drop for HotReloadHandle {
+    fn drop(&mut self) {
+        self.shutdown();
+    }
+}
+
+/// Reloads the cached files a settled file system event refers to, if their
+/// content changed.
+fn reload_changed(instance_cache: &WasmInstanceCache, path: &Path) {
+    let loaded: Vec<String> = instance_cache
+        .loaded_paths()
+        .into_iter()
+        .filter(|wasm_path| same_file(Path::new(wasm_path), path))
+        .collect();
+    if loaded.is_empty() {
+        log::debug!("[HotReload] Ignoring change of {}, which is not loaded", path.display());
+        return;
+    }
+
+    for wasm_path in loaded {
+        if !path.exists() {
+            log::warn!("[HotReload] {} was removed, keeping the loaded version", wasm_path);
+            instance_cache.events.emit(ReloadEvent {
+                kind: ReloadEventKind::Removed,
+                path: wasm_path.clone(),
+                old_hash: instance_cache.loaded_hash(&wasm_path),
+                new_hash: None,
+                timings: ReloadTimer::start().finish(),
+            });
+            continue;
+        }
+        match instance_cache.is_modified(&wasm_path) {
+            Ok(true) => {},
+            Ok(false) => continue,
+            Err(e) => {
+                log::error!("[HotReload] Failed to check {}: {}", wasm_path, e);
+                continue;
+            },
+        }
+        log::info!("[HotReload] Detected WASM change: {}", wasm_path);
+        
+        // Trigger hot reload
+        match instance_cache.hot_reload(&wasm_path) {
+            Ok(()) => log::info!("[HotReload] Successfully hot reloaded: {}", wasm_path),
+            Err(e) => log::error!("[HotReload] Failed to hot reload: {}, error: {}", wasm_path, e),
+        }
+    }
+}
+
+/// Checks whether two paths name the same file, which may not exist anymore.
+fn same_file(a: &Path, b: &Path) -> bool {
+    fn normalize(path: &Path) -> Option<PathBuf> {
+        let parent = match path.parent() {
+            Some(parent) if !parent.as_os_str().is_empty() => parent,
+            _ => Path::new("."),
+        };
+        Some(fs::canonicalize(parent).ok()?.join(path.file_name()?))
     }
+    a == b || matches!((normalize(a), normalize(b)), (Some(a), Some(b)) if a == b)
 }
 
 /// # Load WASM Instance (Simplified API)
@@ -263,13 +1998,13 @@ impl WasmHotReloader {
 /// 
 /// # Returns
 /// 
-/// An `Arc<RwLock<(Instance, Store<WasiCtx>)>>` containing the instantiated WASM module
+/// An `InstanceStore` containing the instantiated WASM module
 /// and its associated store context.
 /// 
 /// # Errors
 /// 
 /// Returns an error if the WASM file cannot be loaded and instantiated.
-pub fn load_wasm_instance(wasm_path: &str, instance_cache: &Arc<WasmInstanceCache>) -> AnyResult<Arc<RwLock<(Instance, Store<WasiCtx>)>>> {
+pub fn load_wasm_instance(wasm_path: &str, instance_cache: &Arc<WasmInstanceCache>) -> Result<InstanceStore> {
     instance_cache.load_and_instantiate(wasm_path)
 }
 
@@ -279,9 +2014,11 @@ pub fn load_wasm_instance(wasm_path: &str, instance_cache: &Arc<WasmInstanceCach
 /// 
 /// This function provides a safe way to call WASM functions by:
 /// 1. Checking if the function is in the allowed entry functions list for the WASM file
-/// 2. Clearing the cache to ensure the latest WASM file is used
-/// 3. Loading and instantiating the WASM module
-/// 4. Calling the specified function with proper error handling
+/// 2. Reusing the cached instance (or a pooled one, see `WasmInstanceCache::pool`),
+///    reloading the file only if it changed (see `WasmInstanceCache::is_modified`);
+///    if the new content cannot be loaded, the previous version keeps serving calls
+/// 3. Loading and instantiating the WASM module if it is not cached yet
+/// 4. Calling the specified function under the module's fuel and timeout budget
 /// 5. Handling both string-returning and void functions
 /// 
 /// # Parameters
@@ -303,93 +2040,353 @@ pub fn load_wasm_instance(wasm_path: &str, instance_cache: &Arc<WasmInstanceCach
 /// - The function is not found in the WASM module
 /// - The function is not a function type
 /// - The function has an incompatible signature
-/// - The function call fails during execution
+/// - The function call fails during execution or runs out of budget
 pub fn call_wasm_function(
     wasm_path: &str,
     func_name: &str,
     instance_cache: &Arc<WasmInstanceCache>,
     dynamic_config: &DynamicConfig
-) -> AnyResult<()> {
+) -> Result<()> {
+    call_wasm_function_with_options(wasm_path, func_name, instance_cache, dynamic_config, CallOptions::default())
+}
+
+/// # Call WASM Function with Options
+/// 
+/// Same as `call_wasm_function`, with per-call options. Set `force_fresh` to run
+/// the function on a new instance even if the file has not changed; a changed
+/// file is hot reloaded first, as without the option.
+/// 
+/// # Parameters
+/// 
+/// - `wasm_path`: Path to the WASM file containing the function
+/// - `func_name`: Name of the function to call
+/// - `instance_cache`: Reference to the WASM instance cache to use
+/// - `dynamic_config`: Reference to the dynamic configuration to check function permissions against
+/// - `options`: Per-call options
+/// 
+/// # Returns
+/// 
+/// `Ok(())` if the function call is successful, otherwise an error.
+/// 
+/// # Errors
+/// 
+/// Returns the same errors as `call_wasm_function`.
+pub fn call_wasm_function_with_options(
+    wasm_path: &str,
+    func_name: &str,
+    instance_cache: &Arc<WasmInstanceCache>,
+    dynamic_config: &DynamicConfig,
+    options: CallOptions,
+) -> Result<()> {
     // Get allowed entry functions for the specified WASM file
     let entry_functions = dynamic_config.get_entry_functions_for_file(wasm_path);
     
     // Validate that the requested function is in the allowed list
     if !entry_functions.contains(&func_name.to_string()) {
-        return Err(anyhow!(
-            "Function '{}' is not configured as an entry function for WASM file '{}'. Allowed functions: {:?}",
-            func_name,
-            wasm_path,
-            entry_functions
-        ));
+        return Err(DlinkError::EntryFunctionNotAllowed {
+            function: func_name.to_string(),
+            wasm_path: wasm_path.to_string(),
+            allowed: entry_functions,
+        });
     }
     
-    // Clear cache to ensure we use the latest WASM file
-    instance_cache.clear_cache(wasm_path);
-    
-    // Load and instantiate the WASM module
-    let instance_store = instance_cache.load_and_instantiate(wasm_path)?;
+    // Reuse the cached instance unless the file changed or a fresh one is requested
+    if instance_cache.is_modified(wasm_path)? {
+        log::info!("[HotReload] Detected WASM change: {}", wasm_path);
+        if let Err(e) = instance_cache.hot_reload(wasm_path) {
+            log::error!("[HotReload] Failed to hot reload: {}, keeping the previous version, error: {}", wasm_path, e);
+        }
+    }
     
-    // Get exclusive access to the instance and store
-    let mut guard = instance_store.write().unwrap();
-    let (ref mut instance, ref mut store) = *guard;
+    // Components take typed parameters; entry functions are called without arguments
+    if instance_cache.is_component(wasm_path)? {
+        return instance_cache.with_component(wasm_path, options.force_fresh, |instance, store| {
+            let (budget, _watchdog) = prepare_call(store, &dynamic_config.get_config().read().unwrap())?;
+            let results = call_component_func(instance, store, &budget, func_name, &[])?;
+            log::info!("[Call] Called '{}' of {}, results: {:?}", func_name, wasm_path, results);
+            Ok(())
+        });
+    }
     
-    // Try to call the specified function
-    if let Some(extern_val) = instance.get_export(&mut *store, func_name) {
-        if let Some(func) = extern_val.into_func() {
-            // First try as function returning a string pointer (i32)
-            match func.typed::<(), i32>(&mut *store) {
-                Ok(test_func) => {
-                    let result_ptr = test_func.call(&mut *store, ())?;
-                    println!("✅ WASM function '{}' called successfully", func_name);
-                    println!("   Raw return value (pointer): {:#018x}", result_ptr);
-                    
-                    // Read the returned string from memory if available
-                    if let Some(memory) = instance.get_memory(&mut *store, "memory") {
-                        let mut buffer = Vec::new();
-                        let mut offset = 0;
+    // Get exclusive access to an instance and its store
+    instance_cache.with_instance(wasm_path, options.force_fresh, |instance, store| {
+        // Refuel the store, arm the timeout and refresh the limits of the module
+        let (budget, _watchdog) = prepare_call(store, &dynamic_config.get_config().read().unwrap())?;
+        
+        // Try to call the specified function
+        if let Some(extern_val) = instance.get_export(&mut *store, func_name) {
+            if let Some(func) = extern_val.into_func() {
+                // First try as function returning a string pointer (i32)
+                match func.typed::<(), i32>(&mut *store) {
+                    Ok(test_func) => {
+                        let result_ptr = test_func.call(&mut *store, ()).map_err(|e| call_error(store, &budget, func_name, e))?;
+                        log::debug!("[Call] '{}' of {} returned pointer {:#010x}", func_name, wasm_path, result_ptr);
                         
-                        // Read bytes until null terminator is found
-                        loop {
-                            let mut byte_buffer = [0u8; 1];
-                            memory.read(&mut *store, result_ptr as usize + offset, &mut byte_buffer)?;
-                            let byte = byte_buffer[0];
+                        // Read the returned string from memory if available
+                        if let Some(memory) = instance.get_memory(&mut *store, "memory") {
+                            let mut buffer = Vec::new();
+                            let mut offset = 0;
+                            
+                            // Read bytes until null terminator is found
+                            loop {
+                                let mut byte_buffer = [0u8; 1];
+                                memory
+                                    .read(&mut *store, result_ptr as usize + offset, &mut byte_buffer)
+                                    .map_err(|_| DlinkError::MemoryAccess { ptr: result_ptr, len: offset + 1 })?;
+                                let byte = byte_buffer[0];
+                                
+                                if byte == 0 {
+                                    break; // Null terminator found
+                                }
+                                buffer.push(byte);
+                                offset += 1;
+                            }
                             
-                            if byte == 0 {
-                                break; // Null terminator found
+                            // Process the read buffer
+                            if !buffer.is_empty() {
+                                let result = String::from_utf8(buffer).map_err(|_| DlinkError::InvalidUtf8)?;
+                                log::info!("[Call] Called '{}' of {}, result: {:?}", func_name, wasm_path, result);
+                            } else {
+                                log::info!("[Call] Called '{}' of {}, result: empty string", func_name, wasm_path);
                             }
-                            buffer.push(byte);
-                            offset += 1;
-                        }
-                        
-                        // Process the read buffer
-                        if !buffer.is_empty() {
-                            let result = String::from_utf8(buffer)?;
-                            println!("   Return value: '{}'", result);
-                            println!("   String length: {} bytes", result.len());
                         } else {
-                            println!("   Returned empty string");
+                            log::info!("[Call] Called '{}' of {}, result: {}", func_name, wasm_path, result_ptr);
                         }
-                    }
-                    Ok(())
-                },
-                Err(_) => {
-                    // If that fails, try as a void function (no return value)
-                    match func.typed::<(), ()>(&mut *store) {
-                        Ok(test_func) => {
-                            test_func.call(&mut *store, ())?;
-                            println!("✅ WASM function '{}' called successfully (no return value)", func_name);
-                            Ok(())
-                        },
-                        Err(err) => {
-                            Err(anyhow!("Cannot call function '{}': incompatible signature, error: {:?}", func_name, err))
+                        Ok(())
+                    },
+                    Err(_) => {
+                        // If that fails, try as a void function (no return value)
+                        match func.typed::<(), ()>(&mut *store) {
+                            Ok(test_func) => {
+                                test_func.call(&mut *store, ()).map_err(|e| call_error(store, &budget, func_name, e))?;
+                                log::info!("[Call] Called '{}' of {}", func_name, wasm_path);
+                                Ok(())
+                            },
+                            Err(err) => {
+                                Err(DlinkError::SignatureMismatch {
+                                    function: func_name.to_string(),
+                                    detail: err.to_string(),
+                                })
+                            }
                         }
                     }
                 }
+            } else {
+                Err(DlinkError::NotAFunction(func_name.to_string()))
             }
         } else {
-            Err(anyhow!("Export '{}' is not a function", func_name))
+            Err(DlinkError::ExportNotFound(func_name.to_string()))
         }
-    } else {
-        Err(anyhow!("Export '{}' not found in WASM module", func_name))
+    })
+}
+
+/// Reads a WASM file and fingerprints its content.
+fn read_source(wasm_path: &str) -> Result<(SourceFingerprint, Vec<u8>)> {
+    let mut file = File::open(wasm_path)?;
+    let metadata = file.metadata()?;
+    let mut wasm_bytes = Vec::new();
+    file.read_to_end(&mut wasm_bytes)?;
+    let source = SourceFingerprint {
+        modified: metadata.modified().ok(),
+        len: metadata.len(),
+        hash: ModuleCache::content_hash(&wasm_bytes),
+    };
+    Ok((source, wasm_bytes))
+}
+
+/// Shortens a content hash for log messages.
+fn short_hash(hash: &str) -> &str {
+    &hash[..hash.len().min(12)]
+}
+
+/// Shuts down a shared instance that is no longer published and logs the host
+/// allocations its guest never released.
+fn shutdown_shared(wasm_path: &str, instance: &mut (Instance, Store<HostState>)) {
+    let (instance, ref mut store) = *instance;
+    shutdown_instance(instance, store);
+    let stats = store.data().allocator_stats();
+    if stats.live_allocations > 0 {
+        log::warn!(
+            "[Allocator] Dropping instance of {} with {} live host allocations ({} bytes)",
+            wasm_path,
+            stats.live_allocations,
+            stats.bytes_in_use
+        );
+    }
+}
+
+/// Resolves an exported function of an instance.
+pub(crate) fn export_func(instance: Instance, store: &mut Store<HostState>, func_name: &str) -> Result<Func> {
+    match instance.get_export(&mut *store, func_name) {
+        Some(extern_val) => extern_val
+            .into_func()
+            .ok_or_else(|| DlinkError::NotAFunction(func_name.to_string())),
+        None => Err(DlinkError::ExportNotFound(func_name.to_string())),
+    }
+}
+
+/// Resolves an exported function of a component instance. Functions exported from
+/// an interface are named `<interface>#<function>`.
+fn component_export_func(instance: ComponentInstance, store: &mut Store<HostState>, func_name: &str) -> Result<ComponentFunc> {
+    let mut exports = instance.exports(&mut *store);
+    let func = match func_name.split_once('#') {
+        Some((interface, name)) => exports.instance(interface).and_then(|mut interface| interface.func(name)),
+        None => exports.root().func(func_name),
+    };
+    func.ok_or_else(|| DlinkError::ExportNotFound(func_name.to_string()))
+}
+
+/// Calls a component function with dynamically typed arguments on a prepared
+/// store and completes the call with `post_return`.
+fn call_component_func(
+    instance: ComponentInstance,
+    store: &mut Store<HostState>,
+    budget: &ExecutionBudget,
+    func_name: &str,
+    args: &[ComponentVal],
+) -> Result<Vec<ComponentVal>> {
+    let func = component_export_func(instance, store, func_name)?;
+
+    // Validate the arguments against the function signature
+    let param_types = func.params(&*store);
+    let arg_types: Vec<_> = args.iter().map(ComponentVal::ty).collect();
+    if param_types[..] != arg_types[..] {
+        return Err(DlinkError::SignatureMismatch {
+            function: func_name.to_string(),
+            detail: format!("expected parameters {:?}, got {:?}", param_types, arg_types),
+        });
     }
+
+    let mut results = vec![ComponentVal::Bool(false); func.results(&*store).len()];
+    func.call(&mut *store, args, &mut results).map_err(|e| call_error(store, budget, func_name, e))?;
+    func.post_return(&mut *store).map_err(|e| call_error(store, budget, func_name, e))?;
+    Ok(results)
+}
+
+/// Calls an export with a fixed signature under the module's execution budget.
+pub(crate) fn call_export<Params, Results>(
+    instance: Instance,
+    store: &mut Store<HostState>,
+    config: &DlinkWMConfig,
+    func_name: &str,
+    args: Params,
+) -> Result<Results>
+where
+    Params: WasmParams,
+    Results: WasmResults,
+{
+    let func = export_func(instance, store, func_name)?;
+    let typed = func.typed::<Params, Results>(&*store).map_err(|e| DlinkError::SignatureMismatch {
+        function: func_name.to_string(),
+        detail: e.to_string(),
+    })?;
+    let (budget, _watchdog) = prepare_call(store, config)?;
+    typed.call(&mut *store, args).map_err(|e| call_error(store, &budget, func_name, e))
+}
+
+/// Instantiates a pre-linked module in a new store with the module's WASI sandbox,
+/// host method registry, resource limits and execution budget, and initializes it
+/// through `dlinkwm_init`.
+fn instantiate(
+    engine: &Engine,
+    instance_pre: &InstancePre<HostState>,
+    wasm_path: &str,
+    config: &Arc<RwLock<DlinkWMConfig>>,
+    registry: &Arc<HostMethodRegistry>,
+) -> Result<(Instance, Store<HostState>)> {
+    let module_config = config.read().unwrap().module_config(wasm_path);
+
+    // Initialize Store and the module's WASI sandbox on the shared engine
+    let mut store = create_store(engine, &module_config.wasi)?;
+    store.data_mut().set_module_path(wasm_path);
+    store.data_mut().set_config(Arc::clone(config));
+    store.data_mut().set_registry(Arc::clone(registry));
+
+    // Install the module's resource limits; count limits are read on installation
+    store.data_mut().limiter_mut().set_limits(ResourceLimits::from_config(&module_config));
+    store.limiter(|state| state.limiter_mut());
+    store.data().limiter().check_module(wasm_path, instance_pre.module())?;
+
+    // Instantiate module, running any start function under the module's budget
+    let budget = ExecutionBudget::from_config(&module_config);
+    let instance = {
+        let _watchdog = budget.apply(&mut store)?;
+        instance_pre.instantiate(&mut store).map_err(|e| {
+            budget
+                .exceeded("start", &e)
+                .or_else(|| limit_error(&mut store, "start", &e))
+                .unwrap_or(DlinkError::Wasm(e))
+        })?
+    };
+
+    // The instance is only handed out once the guest accepted its configuration
+    lifecycle::init(instance, &mut store)?;
+    Ok((instance, store))
+}
+
+/// Instantiates a pre-linked component in a new store with the module's WASI
+/// sandbox, host method registry, resource limits and execution budget.
+fn instantiate_component(
+    engine: &Engine,
+    instance_pre: &ComponentInstancePre<HostState>,
+    wasm_path: &str,
+    config: &Arc<RwLock<DlinkWMConfig>>,
+    registry: &Arc<HostMethodRegistry>,
+) -> Result<(ComponentInstance, Store<HostState>)> {
+    let module_config = config.read().unwrap().module_config(wasm_path);
+
+    let mut store = create_component_store(engine, &module_config.wasi)?;
+    store.data_mut().set_module_path(wasm_path);
+    store.data_mut().set_config(Arc::clone(config));
+    store.data_mut().set_registry(Arc::clone(registry));
+
+    // Install the module's resource limits; count limits are read on installation
+    store.data_mut().limiter_mut().set_limits(ResourceLimits::from_config(&module_config));
+    store.limiter(|state| state.limiter_mut());
+
+    // Instantiate the component, running its start functions under the module's budget
+    let budget = ExecutionBudget::from_config(&module_config);
+    let _watchdog = budget.apply(&mut store)?;
+    let instance = instance_pre.instantiate(&mut store).map_err(|e| {
+        budget
+            .exceeded("start", &e)
+            .or_else(|| limit_error(&mut store, "start", &e))
+            .unwrap_or(DlinkError::Wasm(e))
+    })?;
+    Ok((instance, store))
+}
+
+/// Prepares a cached store for a call under its module's current configuration.
+/// 
+/// Refuels the store, arms the call timeout and refreshes the memory and table
+/// limits. Growth denied during an earlier, successful call is forgotten, and so is
+/// a response of `universal_invoke_v2` an earlier call did not take.
+pub(crate) fn prepare_call(store: &mut Store<HostState>, config: &DlinkWMConfig) -> Result<(ExecutionBudget, Watchdog)> {
+    let module_config = config.module_config(store.data().module_path());
+    store.data_mut().clear_pending_response();
+    let limiter = store.data_mut().limiter_mut();
+    limiter.set_limits(ResourceLimits::from_config(&module_config));
+    limiter.take_violation();
+
+    let budget = ExecutionBudget::from_config(&module_config);
+    let watchdog = budget.apply(store)?;
+    Ok((budget, watchdog))
+}
+
+/// Converts the error of a call into a `DlinkError`, naming the exceeded budget or
+/// resource limit when there is one.
+pub(crate) fn call_error(store: &mut Store<HostState>, budget: &ExecutionBudget, func_name: &str, error: anyhow::Error) -> DlinkError {
+    budget
+        .exceeded(func_name, &error)
+        .or_else(|| limit_error(store, func_name, &error))
+        .unwrap_or(DlinkError::Trap(error))
+}
+
+/// Reports a failure caused by a resource limit denied while the store was running.
+fn limit_error(store: &mut Store<HostState>, func_name: &str, error: &anyhow::Error) -> Option<DlinkError> {
+    let violation = store.data_mut().limiter_mut().take_violation()?;
+    Some(DlinkError::ResourceLimitExceeded {
+        module: store.data().module_path().to_string(),
+        detail: format!("{}; '{}' failed: {:#}", violation, func_name, error),
+    })
 }